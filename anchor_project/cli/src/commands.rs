@@ -0,0 +1,387 @@
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction, Discriminator};
+use anchor_spl::{token, token_2022};
+use anyhow::{anyhow, bail, Result};
+use escrow_cli::{
+    display::{format_amount, parse_amount, EscrowView, Mints},
+    rpc::{Memcmp, Rpc, Simulation},
+};
+use escrow_client::{
+    accounts::{decode_escrow, decode_payout_split, needs_migration},
+    instructions::{self, InitEscrow},
+    pda::{escrow_address, payout_split_address, receipt_mint_address, token_account},
+    Asset, BondTerms, Escrow, EscrowOptions, PayoutSplit, Referral, ID,
+};
+use serde_json::{json, Value};
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+use crate::{CreateArgs, Settings, StateFilter};
+
+// escrow_authority and receiver sit right after the version byte in every
+// layout since v1
+const AUTHORITY_OFFSET: usize = 8 + 1;
+const RECEIVER_OFFSET: usize = AUTHORITY_OFFSET + 32;
+
+enum Outcome {
+    Sent(String),
+    Simulated(Simulation),
+}
+
+pub fn create(settings: &Settings, args: CreateArgs) -> Result<()> {
+    let keypair = settings.keypair()?;
+    let rpc = &settings.rpc;
+    let mut mints = Mints::new(rpc);
+
+    let deposit = asset_for(rpc, args.deposit_mint)?;
+    let receive = asset_for(rpc, args.receive_mint)?;
+    let receive_decimals = mints.decimals(&receive)?;
+
+    let bond = match (args.bond, args.commit_window) {
+        (Some(amount), Some(window)) => Some(BondTerms {
+            amount: parse_amount(&amount, receive_decimals)?,
+            window,
+        }),
+        _ => None,
+    };
+
+    let options = EscrowOptions {
+        bond,
+        challenge_window: args.challenge_window,
+        split: (!args.split.is_empty()).then_some(args.split),
+        referral: args
+            .referrer
+            .zip(args.referral_bps)
+            .map(|(referrer, bps)| Referral { referrer, bps }),
+    };
+
+    let (escrow, _) = escrow_address(&args.id, &keypair.pubkey(), &args.receiver);
+
+    let instruction = InitEscrow {
+        escrow_id: args.id,
+        escrow_authority: keypair.pubkey(),
+        receiver: args.receiver,
+        deposit,
+        deposit_amount: parse_amount(&args.deposit_amount, mints.decimals(&deposit)?)?,
+        receive,
+        receive_amount: parse_amount(&args.receive_amount, receive_decimals)?,
+        options,
+    }
+    .instruction();
+
+    let outcome = execute(settings, &keypair, instruction)?;
+    report(settings, &escrow, "created", outcome)
+}
+
+pub fn accept(settings: &Settings, address: &Pubkey, wrapped: bool) -> Result<()> {
+    let keypair = settings.keypair()?;
+    let rpc = &settings.rpc;
+    let escrow = fetch_escrow(rpc, address)?;
+
+    if escrow.receiver != keypair.pubkey() {
+        bail!(
+            "only the receiver {} can accept escrow {address}",
+            escrow.receiver
+        );
+    }
+
+    let authority = authority_side(rpc, address, &escrow)?;
+    let split = fetch_split(rpc, address, &escrow)?;
+
+    if escrow.has_split && split.is_none() {
+        bail!("payout split of escrow {address} not found");
+    }
+
+    let instruction = instructions::accept(&escrow, &authority, wrapped, split.as_ref());
+
+    let outcome = execute(settings, &keypair, instruction)?;
+    report(settings, address, "accepted", outcome)
+}
+
+pub fn accept_counter(settings: &Settings, address: &Pubkey, amount: &str) -> Result<()> {
+    let keypair = settings.keypair()?;
+    let rpc = &settings.rpc;
+    let escrow = fetch_escrow(rpc, address)?;
+    let authority = authority_side(rpc, address, &escrow)?;
+
+    if authority != keypair.pubkey() {
+        bail!(
+            "only the authority side {authority} can accept the counter-offer on escrow {address}"
+        );
+    }
+
+    let decimals = Mints::new(rpc).decimals(&escrow.receive)?;
+    let expected_amount = parse_amount(amount, decimals)?;
+
+    if escrow.counter_amount == 0 {
+        bail!("escrow {address} has no counter-offer");
+    }
+    // the program checks this too, the receiver may still replace the counter
+    // before the transaction lands
+    if escrow.counter_amount != expected_amount {
+        bail!(
+            "the counter-offer on escrow {address} is {}, not {amount}",
+            format_amount(escrow.counter_amount, decimals)
+        );
+    }
+
+    let split = fetch_split(rpc, address, &escrow)?;
+
+    if escrow.counter_funded && escrow.has_split && split.is_none() {
+        bail!("payout split of escrow {address} not found");
+    }
+
+    let instruction =
+        instructions::accept_counter(&escrow, &authority, expected_amount, split.as_ref());
+
+    let outcome = execute(settings, &keypair, instruction)?;
+    report(settings, address, "accepted the counter-offer on", outcome)
+}
+
+pub fn close(settings: &Settings, address: &Pubkey) -> Result<()> {
+    let keypair = settings.keypair()?;
+    let rpc = &settings.rpc;
+    let escrow = fetch_escrow(rpc, address)?;
+    let authority = authority_side(rpc, address, &escrow)?;
+
+    if authority != keypair.pubkey() {
+        bail!("only the authority side {authority} can close escrow {address}");
+    }
+
+    let instruction = instructions::close(&escrow, &authority);
+
+    let outcome = execute(settings, &keypair, instruction)?;
+    report(settings, address, "closed", outcome)
+}
+
+pub fn show(settings: &Settings, address: &Pubkey) -> Result<()> {
+    let rpc = &settings.rpc;
+    let escrow = fetch_escrow(rpc, address)?;
+    let split = fetch_split(rpc, address, &escrow)?;
+    let view = EscrowView::fetch(&mut Mints::new(rpc), *address, escrow, split)?;
+
+    if settings.json {
+        println!("{:#}", view.to_json()?);
+        return Ok(());
+    }
+
+    view.print()
+}
+
+pub fn list(
+    settings: &Settings,
+    authority: Option<Pubkey>,
+    receiver: Option<Pubkey>,
+    state: Option<StateFilter>,
+) -> Result<()> {
+    let rpc = &settings.rpc;
+
+    let mut filters = vec![Memcmp {
+        offset: 0,
+        bytes: Escrow::DISCRIMINATOR.to_vec(),
+    }];
+    if let Some(authority) = authority {
+        filters.push(Memcmp {
+            offset: AUTHORITY_OFFSET,
+            bytes: authority.to_bytes().to_vec(),
+        });
+    }
+    if let Some(receiver) = receiver {
+        filters.push(Memcmp {
+            offset: RECEIVER_OFFSET,
+            bytes: receiver.to_bytes().to_vec(),
+        });
+    }
+
+    let mut mints = Mints::new(rpc);
+    let mut views = Vec::new();
+    let mut outdated = 0;
+
+    for (address, account) in rpc.program_accounts(&ID, &filters)? {
+        if needs_migration(&account.data) {
+            outdated += 1;
+            continue;
+        }
+
+        let escrow = decode_escrow(&account.data)
+            .map_err(|err| anyhow!("could not decode escrow {address}: {err}"))?;
+
+        if state.is_some_and(|state| !state.matches(escrow.state)) {
+            continue;
+        }
+
+        views.push(EscrowView::fetch(&mut mints, address, escrow, None)?);
+    }
+
+    views.sort_by(|a, b| a.escrow.escrow_id.cmp(&b.escrow.escrow_id));
+
+    if settings.json {
+        let views = views
+            .iter()
+            .map(EscrowView::to_json)
+            .collect::<Result<Vec<_>>>()?;
+        println!("{:#}", Value::from(views));
+    } else if views.is_empty() {
+        println!("no escrows found");
+    } else {
+        views.iter().for_each(EscrowView::print_row);
+    }
+
+    if outdated > 0 {
+        eprintln!("skipped {outdated} escrow(s) with an older layout, migrate them to list them");
+    }
+
+    Ok(())
+}
+
+fn execute(settings: &Settings, keypair: &Keypair, instruction: Instruction) -> Result<Outcome> {
+    let rpc = &settings.rpc;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&keypair.pubkey()),
+        &[keypair],
+        rpc.latest_blockhash()?,
+    );
+
+    if settings.dry_run {
+        return Ok(Outcome::Simulated(rpc.simulate(&transaction)?));
+    }
+
+    Ok(Outcome::Sent(rpc.send_and_confirm(&transaction)?))
+}
+
+fn report(settings: &Settings, escrow: &Pubkey, action: &str, outcome: Outcome) -> Result<()> {
+    match outcome {
+        Outcome::Sent(signature) => {
+            if settings.json {
+                println!(
+                    "{:#}",
+                    json!({ "escrow": escrow.to_string(), "signature": signature })
+                );
+            } else {
+                println!("{action} escrow {escrow}");
+                println!("signature {signature}");
+            }
+        }
+        Outcome::Simulated(simulation) => {
+            if settings.json {
+                println!(
+                    "{:#}",
+                    json!({
+                        "escrow": escrow.to_string(),
+                        "simulation": {
+                            "err": simulation.err,
+                            "logs": simulation.logs,
+                            "units_consumed": simulation.units_consumed,
+                        },
+                    })
+                );
+            } else {
+                simulation.logs.iter().for_each(|log| println!("  {log}"));
+
+                if simulation.err.is_none() {
+                    println!(
+                        "would have {action} escrow {escrow}, {} compute units",
+                        simulation.units_consumed.unwrap_or_default()
+                    );
+                }
+            }
+
+            if let Some(err) = simulation.err {
+                bail!("simulation failed: {err}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The leg a mint stands for, SOL without one
+fn asset_for(rpc: &Rpc, mint: Option<Pubkey>) -> Result<Asset> {
+    let Some(mint) = mint else {
+        return Ok(Asset::Native);
+    };
+
+    let account = rpc
+        .account(&mint)?
+        .ok_or_else(|| anyhow!("mint {mint} not found"))?;
+
+    if account.owner == token_2022::ID {
+        Ok(Asset::Token2022 { mint })
+    } else if account.owner == token::ID {
+        Ok(Asset::SplToken {
+            mint,
+            program: token::ID,
+        })
+    } else {
+        bail!("{mint} is not a token mint")
+    }
+}
+
+fn fetch_escrow(rpc: &Rpc, address: &Pubkey) -> Result<Escrow> {
+    let account = rpc
+        .account(address)?
+        .ok_or_else(|| anyhow!("no escrow at {address}"))?;
+
+    if account.owner != ID {
+        bail!("{address} is not an escrow account");
+    }
+    if needs_migration(&account.data) {
+        bail!("escrow {address} has an older layout, migrate it first");
+    }
+
+    decode_escrow(&account.data).map_err(|err| anyhow!("could not decode escrow {address}: {err}"))
+}
+
+// The payout split of an escrow, None once it was paid out and closed
+fn fetch_split(rpc: &Rpc, address: &Pubkey, escrow: &Escrow) -> Result<Option<PayoutSplit>> {
+    if !escrow.has_split {
+        return Ok(None);
+    }
+
+    let (split, _) = payout_split_address(address);
+
+    rpc.account(&split)?
+        .map(|account| {
+            decode_payout_split(&account.data)
+                .map_err(|err| anyhow!("could not decode payout split {split}: {err}"))
+        })
+        .transpose()
+}
+
+// Wallet on the authority side of an escrow: whoever holds its receipt, or
+// escrow_authority for escrows without one. The instructions pass the
+// holder's receipt ATA, a receipt moved to any other account has to go back
+// there first
+fn authority_side(rpc: &Rpc, address: &Pubkey, escrow: &Escrow) -> Result<Pubkey> {
+    if !escrow.has_receipt {
+        return Ok(escrow.escrow_authority);
+    }
+
+    let (receipt_mint, _) = receipt_mint_address(address);
+    let holding = rpc
+        .largest_token_accounts(&receipt_mint)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("receipt of escrow {address} has no holder"))?;
+
+    let account = rpc
+        .account(&holding)?
+        .ok_or_else(|| anyhow!("receipt account {holding} not found"))?;
+
+    // token account owner, right after the mint
+    let owner = account
+        .data
+        .get(32..64)
+        .ok_or_else(|| anyhow!("{holding} is not a token account"))?;
+    let holder = Pubkey::new_from_array(owner.try_into()?);
+
+    let receipt_account = token_account(&holder, &receipt_mint, &token_2022::ID);
+    if holding != receipt_account {
+        bail!(
+            "receipt of escrow {address} is held in {holding}, not in {holder}'s receipt account {receipt_account}"
+        );
+    }
+
+    Ok(holder)
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token_2022;
+use anyhow::{anyhow, bail, Result};
+use escrow_client::{Asset, Escrow, PayoutSplit};
+use serde_json::{json, Value};
+
+use crate::rpc::Rpc;
+
+const SOL_DECIMALS: u8 = 9;
+// mint authority option and supply come first in both token programs
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+// Decimals of the mints seen so far, so listing many escrows fetches each
+// mint once
+pub struct Mints<'a> {
+    rpc: &'a Rpc,
+    decimals: HashMap<Pubkey, u8>,
+}
+
+impl<'a> Mints<'a> {
+    pub fn new(rpc: &'a Rpc) -> Self {
+        Self {
+            rpc,
+            decimals: HashMap::new(),
+        }
+    }
+
+    pub fn decimals(&mut self, asset: &Asset) -> Result<u8> {
+        let Some(mint) = asset.mint() else {
+            return Ok(SOL_DECIMALS);
+        };
+
+        if let Some(decimals) = self.decimals.get(&mint) {
+            return Ok(*decimals);
+        }
+
+        let account = self
+            .rpc
+            .account(&mint)?
+            .ok_or_else(|| anyhow!("mint {mint} not found"))?;
+        let decimals = *account
+            .data
+            .get(MINT_DECIMALS_OFFSET)
+            .ok_or_else(|| anyhow!("{mint} is not a mint"))?;
+
+        self.decimals.insert(mint, decimals);
+
+        Ok(decimals)
+    }
+}
+
+// `amount` base units as a decimal string, "1.5" for 1_500_000_000 lamports
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{amount:0>width$}", width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{whole}.{fraction}")
+    }
+}
+
+// A decimal string in base units, the inverse of format_amount
+pub fn parse_amount(amount: &str, decimals: u8) -> Result<u64> {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        bail!("invalid amount {amount}");
+    }
+    if fraction.len() > decimals as usize {
+        bail!("{amount} has more than {decimals} decimals");
+    }
+
+    let digits = format!("{whole}{fraction:0<width$}", width = decimals as usize);
+    let digits = digits.trim_start_matches('0');
+
+    if digits.is_empty() {
+        return Ok(0);
+    }
+
+    digits.parse().map_err(|_| anyhow!("{amount} is too large"))
+}
+
+pub fn format_bps(bps: u16) -> String {
+    format!("{}.{:02}%", bps / 100, bps % 100)
+}
+
+pub fn asset_label(asset: &Asset) -> String {
+    match asset {
+        Asset::Native => "SOL".to_string(),
+        Asset::SplToken { mint, .. } => mint.to_string(),
+        Asset::Token2022 { mint } => format!("{mint} (token-2022)"),
+    }
+}
+
+// Everything `show` and `list` print about an escrow
+pub struct EscrowView {
+    pub address: Pubkey,
+    pub escrow: Escrow,
+    pub deposit_decimals: u8,
+    pub receive_decimals: u8,
+    pub split: Option<PayoutSplit>,
+}
+
+impl EscrowView {
+    pub fn fetch(
+        mints: &mut Mints,
+        address: Pubkey,
+        escrow: Escrow,
+        split: Option<PayoutSplit>,
+    ) -> Result<Self> {
+        Ok(Self {
+            address,
+            deposit_decimals: mints.decimals(&escrow.deposit)?,
+            receive_decimals: mints.decimals(&escrow.receive)?,
+            escrow,
+            split,
+        })
+    }
+
+    fn deposit(&self, amount: u64) -> String {
+        format_amount(amount, self.deposit_decimals)
+    }
+
+    fn receive(&self, amount: u64) -> String {
+        format_amount(amount, self.receive_decimals)
+    }
+
+    pub fn print(&self) -> Result<()> {
+        let escrow = &self.escrow;
+
+        let row = |label: &str, value: String| println!("{label:<18} {value}");
+
+        row("escrow", self.address.to_string());
+        row("id", escrow.escrow_id.clone());
+        row(
+            "state",
+            format!("{:?} (version {})", escrow.state, escrow.version),
+        );
+        row("authority", escrow.escrow_authority.to_string());
+        row("receiver", escrow.receiver.to_string());
+        row(
+            "deposit",
+            format!(
+                "{} {}",
+                self.deposit(escrow.deposit_amount),
+                asset_label(&escrow.deposit)
+            ),
+        );
+        row(
+            "receive",
+            format!(
+                "{} {}",
+                self.receive(escrow.receive_amount),
+                asset_label(&escrow.receive)
+            ),
+        );
+
+        if escrow.counter_amount > 0 {
+            row(
+                "counter-offer",
+                format!(
+                    "{}{}",
+                    self.receive(escrow.counter_amount),
+                    if escrow.counter_funded {
+                        " (funded)"
+                    } else {
+                        ""
+                    }
+                ),
+            );
+        }
+
+        if escrow.bond_amount > 0 {
+            let committed = match escrow.committed_until {
+                0 => "not committed".to_string(),
+                until => format!("committed until {until}"),
+            };
+            row(
+                "bond",
+                format!(
+                    "{}, {}s commit window, {committed}",
+                    self.receive(escrow.bond_amount),
+                    escrow.commit_window
+                ),
+            );
+        }
+
+        row(
+            "challenge window",
+            match escrow.challenge_ends {
+                0 => format!("{}s", escrow.challenge_window),
+                ends => format!("{}s, ends {ends}", escrow.challenge_window),
+            },
+        );
+
+        if let Some(referrer) = escrow.referrer() {
+            row(
+                "referral",
+                format!(
+                    "{referrer} {} (fee {})",
+                    format_bps(escrow.referral_bps),
+                    self.deposit(escrow.referral_fee().map_err(|err| anyhow!("{err}"))?)
+                ),
+            );
+        }
+
+        if let Some(split) = &self.split {
+            for (i, share) in split.shares.iter().enumerate() {
+                row(
+                    if i == 0 { "split" } else { "" },
+                    format!("{} {}", share.recipient, format_bps(share.bps)),
+                );
+            }
+        }
+
+        row(
+            "receipt",
+            if escrow.has_receipt { "yes" } else { "no" }.into(),
+        );
+
+        Ok(())
+    }
+
+    pub fn print_row(&self) {
+        let escrow = &self.escrow;
+
+        println!(
+            "{}  {:<9}  {} {} -> {} {}  {}",
+            self.address,
+            format!("{:?}", escrow.state),
+            self.deposit(escrow.deposit_amount),
+            asset_label(&escrow.deposit),
+            self.receive(escrow.receive_amount),
+            asset_label(&escrow.receive),
+            escrow.escrow_id
+        );
+    }
+
+    pub fn to_json(&self) -> Result<Value> {
+        let escrow = &self.escrow;
+
+        let leg = |asset: &Asset, amount: u64, decimals: u8| {
+            json!({
+                "mint": asset.mint().map(|mint| mint.to_string()),
+                "token_program": asset.token_program().map(|program| program.to_string()),
+                "token_2022": asset.token_program() == Some(token_2022::ID),
+                "amount": amount.to_string(),
+                "ui_amount": format_amount(amount, decimals),
+                "decimals": decimals,
+            })
+        };
+
+        let referral = match escrow.referrer() {
+            Some(referrer) => {
+                let fee = escrow.referral_fee().map_err(|err| anyhow!("{err}"))?;
+                json!({
+                    "referrer": referrer.to_string(),
+                    "bps": escrow.referral_bps,
+                    "fee": fee.to_string(),
+                    "ui_fee": self.deposit(fee),
+                })
+            }
+            None => Value::Null,
+        };
+
+        Ok(json!({
+            "address": self.address.to_string(),
+            "escrow_id": escrow.escrow_id,
+            "version": escrow.version,
+            "state": format!("{:?}", escrow.state),
+            "escrow_authority": escrow.escrow_authority.to_string(),
+            "seed_authority": escrow.seed_authority.to_string(),
+            "receiver": escrow.receiver.to_string(),
+            "deposit": leg(&escrow.deposit, escrow.deposit_amount, self.deposit_decimals),
+            "receive": leg(&escrow.receive, escrow.receive_amount, self.receive_decimals),
+            "authority_wrapped_sol": escrow.authority_wrapped_sol,
+            "receiver_wrapped_sol": escrow.receiver_wrapped_sol,
+            "counter_offer": (escrow.counter_amount > 0).then(|| json!({
+                "amount": escrow.counter_amount.to_string(),
+                "ui_amount": self.receive(escrow.counter_amount),
+                "funded": escrow.counter_funded,
+            })),
+            "bond": (escrow.bond_amount > 0).then(|| json!({
+                "amount": escrow.bond_amount.to_string(),
+                "ui_amount": self.receive(escrow.bond_amount),
+                "commit_window": escrow.commit_window,
+                "committed_until": escrow.committed_until,
+            })),
+            "challenge_window": escrow.challenge_window,
+            "challenge_ends": escrow.challenge_ends,
+            "referral": referral,
+            // only `show` fetches the split table
+            "split": self.split.as_ref().map(|split| {
+                split
+                    .shares
+                    .iter()
+                    .map(|share| json!({
+                        "recipient": share.recipient.to_string(),
+                        "bps": share.bps,
+                    }))
+                    .collect::<Vec<_>>()
+            }),
+            "has_split": escrow.has_split,
+            "has_receipt": escrow.has_receipt,
+        }))
+    }
+}
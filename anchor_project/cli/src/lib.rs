@@ -0,0 +1,4 @@
+// RPC access and amount formatting for the escrow CLI. The commands and
+// their arguments live with the binary in main.rs and commands.rs
+pub mod display;
+pub mod rpc;
@@ -0,0 +1,204 @@
+use std::{env, path::PathBuf, str::FromStr};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use escrow_cli::rpc;
+use escrow_client::{EscrowState, SplitShare};
+use solana_keypair::{read_keypair_file, Keypair};
+
+mod commands;
+
+#[derive(Parser)]
+#[command(
+    name = "escrow-cli",
+    version,
+    about = "Create, accept, close and inspect escrows"
+)]
+struct Cli {
+    /// RPC URL, or one of localhost, devnet, testnet, mainnet-beta
+    #[arg(long, short, global = true, default_value = "localhost")]
+    url: String,
+
+    /// Keypair file that signs and pays [default: ~/.config/solana/id.json]
+    #[arg(long, short, global = true)]
+    keypair: Option<PathBuf>,
+
+    /// Simulate transactions instead of sending them
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an escrow, paying in the deposit leg
+    Create(Box<CreateArgs>),
+    /// Accept an escrow as its receiver
+    Accept {
+        escrow: Pubkey,
+        /// Pay and get paid the SOL leg as wSOL
+        #[arg(long)]
+        wrapped: bool,
+    },
+    /// Accept the receiver's counter-offer as the authority side
+    AcceptCounter {
+        escrow: Pubkey,
+        /// Counter amount being accepted, in whole receive units. Fails if the
+        /// receiver replaced the counter in the meantime
+        #[arg(long)]
+        amount: String,
+    },
+    /// Close an escrow as its authority side, refunding whatever it holds
+    Close { escrow: Pubkey },
+    /// Show the terms of an escrow
+    Show { escrow: Pubkey },
+    /// List escrows, optionally only those of an authority or receiver
+    List {
+        #[arg(long)]
+        authority: Option<Pubkey>,
+        #[arg(long)]
+        receiver: Option<Pubkey>,
+        #[arg(long, value_enum)]
+        state: Option<StateFilter>,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct CreateArgs {
+    /// Id of the escrow, unique per authority and receiver
+    #[arg(long)]
+    pub id: String,
+    #[arg(long)]
+    pub receiver: Pubkey,
+    /// Mint of the deposit leg, SOL when left out. A wSOL mint deposits wSOL
+    #[arg(long)]
+    pub deposit_mint: Option<Pubkey>,
+    /// Deposit amount in whole units, 1.5 is 1.5 SOL or tokens
+    #[arg(long)]
+    pub deposit_amount: String,
+    /// Mint of the receive leg, SOL when left out. A wSOL mint is paid in wSOL
+    #[arg(long)]
+    pub receive_mint: Option<Pubkey>,
+    /// Receive amount in whole units
+    #[arg(long)]
+    pub receive_amount: String,
+    /// Bond the receiver posts to commit to the offer, in receive units
+    #[arg(long, requires = "commit_window")]
+    pub bond: Option<String>,
+    /// Seconds a commit locks the offer to the receiver
+    #[arg(long, requires = "bond")]
+    pub commit_window: Option<i64>,
+    /// Seconds between the receiver funding and settle
+    #[arg(long)]
+    pub challenge_window: Option<i64>,
+    /// Pay the receive leg across wallets, as RECIPIENT:BPS, repeatable
+    #[arg(long = "split", value_parser = parse_share)]
+    pub split: Vec<SplitShare>,
+    /// Wallet paid a cut of the deposit leg
+    #[arg(long, requires = "referral_bps")]
+    pub referrer: Option<Pubkey>,
+    #[arg(long, requires = "referrer")]
+    pub referral_bps: Option<u16>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StateFilter {
+    Active,
+    Funded,
+    Completed,
+    Closed,
+}
+
+impl StateFilter {
+    pub fn matches(self, state: EscrowState) -> bool {
+        matches!(
+            (self, state),
+            (StateFilter::Active, EscrowState::Active)
+                | (StateFilter::Funded, EscrowState::Funded)
+                | (StateFilter::Completed, EscrowState::Completed)
+                | (StateFilter::Closed, EscrowState::Closed)
+        )
+    }
+}
+
+// Where to send things and how to print them
+pub struct Settings {
+    pub rpc: rpc::Rpc,
+    keypair: Option<PathBuf>,
+    pub dry_run: bool,
+    pub json: bool,
+}
+
+impl Settings {
+    pub fn keypair(&self) -> Result<Keypair> {
+        let path = match &self.keypair {
+            Some(path) => path.clone(),
+            None => PathBuf::from(env::var("HOME").context("HOME is not set, pass --keypair")?)
+                .join(".config/solana/id.json"),
+        };
+
+        read_keypair_file(&path)
+            .map_err(|err| anyhow!("could not read keypair {}: {err}", path.display()))
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let settings = Settings {
+        rpc: rpc::Rpc::new(cluster_url(&cli.url)),
+        keypair: cli.keypair,
+        dry_run: cli.dry_run,
+        json: cli.json,
+    };
+
+    let result = match cli.command {
+        Command::Create(args) => commands::create(&settings, *args),
+        Command::Accept { escrow, wrapped } => commands::accept(&settings, &escrow, wrapped),
+        Command::AcceptCounter { escrow, amount } => {
+            commands::accept_counter(&settings, &escrow, &amount)
+        }
+        Command::Close { escrow } => commands::close(&settings, &escrow),
+        Command::Show { escrow } => commands::show(&settings, &escrow),
+        Command::List {
+            authority,
+            receiver,
+            state,
+        } => commands::list(&settings, authority, receiver, state),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {err:#}");
+        std::process::exit(1);
+    }
+}
+
+fn cluster_url(url: &str) -> String {
+    match url {
+        "l" | "localhost" => "http://127.0.0.1:8899",
+        "d" | "devnet" => "https://api.devnet.solana.com",
+        "t" | "testnet" => "https://api.testnet.solana.com",
+        "m" | "mainnet-beta" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+    .to_string()
+}
+
+fn parse_share(share: &str) -> Result<SplitShare> {
+    let (recipient, bps) = share
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected RECIPIENT:BPS, got {share}"))?;
+
+    Ok(SplitShare {
+        recipient: Pubkey::from_str(recipient)
+            .map_err(|err| anyhow!("invalid recipient {recipient}: {err}"))?,
+        bps: bps.parse().with_context(|| format!("invalid bps {bps}"))?,
+    })
+}
@@ -0,0 +1,268 @@
+use std::{str::FromStr, thread, time::Duration};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use escrow_client::errors::escrow_error;
+use serde_json::{json, Value};
+use solana_hash::Hash;
+use solana_transaction::Transaction;
+
+// Enough of the JSON RPC API for the cli, everything at confirmed commitment
+pub struct Rpc {
+    url: String,
+    agent: ureq::Agent,
+}
+
+pub struct Account {
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+}
+
+pub struct Simulation {
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+}
+
+// A memcmp filter for getProgramAccounts
+pub struct Memcmp {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl Rpc {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([address.to_string(), { "encoding": "base64", "commitment": "confirmed" }]),
+        )?;
+
+        match &result["value"] {
+            Value::Null => Ok(None),
+            value => parse_account(value).map(Some),
+        }
+    }
+
+    pub fn program_accounts(
+        &self,
+        program: &Pubkey,
+        filters: &[Memcmp],
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        let filters: Vec<Value> = filters
+            .iter()
+            .map(|filter| {
+                json!({ "memcmp": {
+                    "offset": filter.offset,
+                    "bytes": STANDARD.encode(&filter.bytes),
+                    "encoding": "base64",
+                }})
+            })
+            .collect();
+
+        let result = self.call(
+            "getProgramAccounts",
+            json!([program.to_string(), {
+                "encoding": "base64",
+                "commitment": "confirmed",
+                "filters": filters,
+            }]),
+        )?;
+
+        result
+            .as_array()
+            .ok_or_else(|| anyhow!("unexpected getProgramAccounts result"))?
+            .iter()
+            .map(|entry| {
+                Ok((
+                    parse_pubkey(&entry["pubkey"])?,
+                    parse_account(&entry["account"])?,
+                ))
+            })
+            .collect()
+    }
+
+    // Token accounts of `mint` with a non zero balance, largest first
+    pub fn largest_token_accounts(&self, mint: &Pubkey) -> Result<Vec<Pubkey>> {
+        let result = self.call(
+            "getTokenLargestAccounts",
+            json!([mint.to_string(), { "commitment": "confirmed" }]),
+        )?;
+
+        result["value"]
+            .as_array()
+            .ok_or_else(|| anyhow!("unexpected getTokenLargestAccounts result"))?
+            .iter()
+            .filter(|entry| entry["amount"].as_str() != Some("0"))
+            .map(|entry| parse_pubkey(&entry["address"]))
+            .collect()
+    }
+
+    pub fn latest_blockhash(&self) -> Result<Hash> {
+        let result = self.call("getLatestBlockhash", json!([{ "commitment": "confirmed" }]))?;
+
+        let blockhash = result["value"]["blockhash"]
+            .as_str()
+            .ok_or_else(|| anyhow!("unexpected getLatestBlockhash result"))?;
+
+        Hash::from_str(blockhash).map_err(|err| anyhow!("invalid blockhash {blockhash}: {err}"))
+    }
+
+    pub fn simulate(&self, transaction: &Transaction) -> Result<Simulation> {
+        let result = self.call(
+            "simulateTransaction",
+            json!([encode(transaction)?, {
+                "encoding": "base64",
+                "commitment": "confirmed",
+                "sigVerify": true,
+            }]),
+        )?;
+
+        let value = &result["value"];
+
+        Ok(Simulation {
+            err: (!value["err"].is_null()).then(|| describe_error(&value["err"])),
+            logs: logs(&value["logs"]),
+            units_consumed: value["unitsConsumed"].as_u64(),
+        })
+    }
+
+    // Send with preflight and wait until the cluster has it confirmed
+    pub fn send_and_confirm(&self, transaction: &Transaction) -> Result<String> {
+        let result = self
+            .call(
+                "sendTransaction",
+                json!([encode(transaction)?, {
+                    "encoding": "base64",
+                    "preflightCommitment": "confirmed",
+                }]),
+            )
+            .context("transaction rejected")?;
+
+        let signature = result
+            .as_str()
+            .ok_or_else(|| anyhow!("unexpected sendTransaction result"))?
+            .to_string();
+
+        for _ in 0..60 {
+            let result = self.call(
+                "getSignatureStatuses",
+                json!([[signature], { "searchTransactionHistory": false }]),
+            )?;
+            let status = &result["value"][0];
+
+            if !status.is_null() {
+                if !status["err"].is_null() {
+                    bail!(
+                        "transaction {signature} failed: {}",
+                        describe_error(&status["err"])
+                    );
+                }
+
+                if matches!(
+                    status["confirmationStatus"].as_str(),
+                    Some("confirmed" | "finalized")
+                ) {
+                    return Ok(signature);
+                }
+            }
+
+            thread::sleep(Duration::from_millis(500));
+        }
+
+        bail!("transaction {signature} was not confirmed in time")
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .with_context(|| format!("{method} request to {} failed", self.url))?
+            .into_json()
+            .with_context(|| format!("{method} returned an invalid response"))?;
+
+        let error = &response["error"];
+        if !error.is_null() {
+            let mut message = error["message"].as_str().unwrap_or("rpc error").to_string();
+
+            // preflight failures carry the simulation, the program error and
+            // logs say more than the message
+            let data = &error["data"];
+            if !data["err"].is_null() {
+                message = format!("{message}\n  {}", describe_error(&data["err"]));
+            }
+            for log in logs(&data["logs"]) {
+                message = format!("{message}\n  {log}");
+            }
+
+            bail!(message);
+        }
+
+        Ok(response["result"].take())
+    }
+}
+
+// A transaction error, with custom codes of the escrow program named
+pub fn describe_error(err: &Value) -> String {
+    let custom = err["InstructionError"][1]["Custom"]
+        .as_u64()
+        .and_then(|code| u32::try_from(code).ok());
+
+    match custom.and_then(escrow_error) {
+        Some(error) => format!("{error:?}: {error}"),
+        None => err.to_string(),
+    }
+}
+
+fn parse_account(value: &Value) -> Result<Account> {
+    let data = value["data"][0]
+        .as_str()
+        .ok_or_else(|| anyhow!("account data is not base64"))?;
+
+    Ok(Account {
+        owner: parse_pubkey(&value["owner"])?,
+        lamports: value["lamports"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("account without lamports"))?,
+        data: STANDARD.decode(data)?,
+    })
+}
+
+fn parse_pubkey(value: &Value) -> Result<Pubkey> {
+    let address = value
+        .as_str()
+        .ok_or_else(|| anyhow!("expected an address, got {value}"))?;
+
+    Pubkey::from_str(address).map_err(|err| anyhow!("invalid address {address}: {err}"))
+}
+
+fn logs(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|logs| {
+            logs.iter()
+                .filter_map(|log| log.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn encode(transaction: &Transaction) -> Result<String> {
+    Ok(STANDARD.encode(bincode::serialize(transaction)?))
+}
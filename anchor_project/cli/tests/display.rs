@@ -0,0 +1,65 @@
+// Amounts as the CLI reads and prints them, in base units of the mint
+use escrow_cli::display::{format_amount, parse_amount};
+
+#[test]
+fn formats_without_trailing_zeros() {
+    assert_eq!(format_amount(1_500_000_000, 9), "1.5");
+    assert_eq!(format_amount(2_000_000_000, 9), "2");
+    assert_eq!(format_amount(1, 9), "0.000000001");
+    assert_eq!(format_amount(0, 9), "0");
+    assert_eq!(format_amount(42, 0), "42");
+    assert_eq!(format_amount(u64::MAX, 6), "18446744073709.551615");
+}
+
+#[test]
+fn parses_what_it_formats() {
+    for (amount, decimals) in [(1_500_000_000, 9), (1, 9), (0, 6), (42, 0), (u64::MAX, 6)] {
+        assert_eq!(
+            parse_amount(&format_amount(amount, decimals), decimals).unwrap(),
+            amount
+        );
+    }
+}
+
+#[test]
+fn either_side_of_the_point_can_be_left_out() {
+    assert_eq!(parse_amount("1.", 6).unwrap(), 1_000_000);
+    assert_eq!(parse_amount(".5", 6).unwrap(), 500_000);
+    assert_eq!(parse_amount("007.10", 6).unwrap(), 7_100_000);
+    assert_eq!(parse_amount("0.000", 6).unwrap(), 0);
+}
+
+#[test]
+fn too_many_decimals_fail() {
+    let err = parse_amount("1.0000001", 6).unwrap_err();
+    assert!(err.to_string().contains("more than 6 decimals"), "{err}");
+    // trailing zeros count too
+    assert!(parse_amount("1.0000000", 6).is_err());
+    assert!(parse_amount("1.5", 0).is_err());
+}
+
+#[test]
+fn amounts_past_u64_fail() {
+    for amount in [
+        "18446744073709551616",
+        "18446744073709.551616",
+        "99999999999999999999",
+    ] {
+        let decimals = if amount.contains('.') { 6 } else { 0 };
+        let err = parse_amount(amount, decimals).unwrap_err();
+        assert!(err.to_string().contains("too large"), "{amount}: {err}");
+    }
+    assert!(parse_amount("18446744073709551615", 0).is_ok());
+    assert!(parse_amount("18446744073709551615", 1).is_err());
+}
+
+#[test]
+fn anything_but_digits_and_a_point_fails() {
+    for amount in ["", ".", "-1", "+1", "1e3", "1.2.3", " 1", "1,5", "0x10"] {
+        let err = parse_amount(amount, 6).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid amount"),
+            "{amount}: {err}"
+        );
+    }
+}
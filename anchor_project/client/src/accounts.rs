@@ -0,0 +1,44 @@
+use anchor_lang::{prelude::*, AccountDeserialize, Discriminator};
+use escrow::{
+    errors::EscrowError,
+    state::{legacy, Config, Escrow, PayoutSplit, ESCROW_VERSION},
+};
+
+// Escrow account data in the current layout. Anything older has to go
+// through migrate_escrow first
+pub fn decode_escrow(data: &[u8]) -> Result<Escrow> {
+    require!(!needs_migration(data), EscrowError::UnknownVersion);
+
+    Escrow::try_deserialize(&mut &data[..])
+}
+
+// An escrow account not in the current layout: the legacy one until
+// migrate_escrow rewrites it, or a version this client doesn't know
+pub fn needs_migration(data: &[u8]) -> bool {
+    data.starts_with(Escrow::DISCRIMINATOR)
+        && (data.len() != 8 + Escrow::INIT_SPACE || data[8] != ESCROW_VERSION)
+}
+
+// An escrow still in the layout from before the version byte, as
+// migrate_escrow would rewrite it
+pub fn decode_legacy_escrow(data: &[u8]) -> Result<Escrow> {
+    require!(
+        data.starts_with(Escrow::DISCRIMINATOR) && is_legacy(data),
+        EscrowError::UnknownVersion
+    );
+
+    legacy::decode(data)
+}
+
+// Only the legacy layout has this size
+pub fn is_legacy(data: &[u8]) -> bool {
+    data.len() == 8 + legacy::SentinelEscrow::INIT_SPACE
+}
+
+pub fn decode_payout_split(data: &[u8]) -> Result<PayoutSplit> {
+    PayoutSplit::try_deserialize(&mut &data[..])
+}
+
+pub fn decode_config(data: &[u8]) -> Result<Config> {
+    Config::try_deserialize(&mut &data[..])
+}
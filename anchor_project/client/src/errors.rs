@@ -0,0 +1,66 @@
+use anchor_lang::{error::ERROR_CODE_OFFSET, solana_program::instruction::error::InstructionError};
+
+pub use escrow::errors::EscrowError;
+
+// Every EscrowError in declaration order, so its code is the offset plus the
+// index. tests/errors.rs holds it against the program's errors.rs
+pub const ESCROW_ERRORS: [EscrowError; 39] = [
+    EscrowError::OverFlow,
+    EscrowError::InvalidState,
+    EscrowError::InvalidDepositMint,
+    EscrowError::InvalidReceiveMint,
+    EscrowError::SameTokenTransferNotAllowed,
+    EscrowError::SameBuyerSellerNotAllowed,
+    EscrowError::EscrowNotActive,
+    EscrowError::UnauthorizedSigner,
+    EscrowError::IdTooLong,
+    EscrowError::IdTooShort,
+    EscrowError::DepositAmountLow,
+    EscrowError::ReceiveAmountLow,
+    EscrowError::InsufficientBalance,
+    EscrowError::InvalidTokenProgram,
+    EscrowError::InvalidTokenAccount,
+    EscrowError::AlreadyMigrated,
+    EscrowError::UnknownVersion,
+    EscrowError::MissingSolVault,
+    EscrowError::InvalidBondTerms,
+    EscrowError::CommitNotOffered,
+    EscrowError::AlreadyCommitted,
+    EscrowError::OfferCommitted,
+    EscrowError::MissingBondVault,
+    EscrowError::InvalidChallengeWindow,
+    EscrowError::ChallengeWindowOpen,
+    EscrowError::ChallengeWindowOver,
+    EscrowError::NoCounterOffer,
+    EscrowError::CounterPending,
+    EscrowError::MissingReceipt,
+    EscrowError::NotReceiptHolder,
+    EscrowError::InvalidSplit,
+    EscrowError::MissingSplit,
+    EscrowError::InvalidReferral,
+    EscrowError::ReferralTooHigh,
+    EscrowError::MissingReferrer,
+    EscrowError::InvalidConfig,
+    EscrowError::CounterChanged,
+    EscrowError::MissingConfig,
+    EscrowError::ReferralBelowRent,
+];
+
+// EscrowError behind a custom program error code
+pub fn escrow_error(code: u32) -> Option<EscrowError> {
+    let index = code.checked_sub(ERROR_CODE_OFFSET)?;
+
+    ESCROW_ERRORS
+        .get(index as usize)
+        .copied()
+        .filter(|error| u32::from(*error) == code)
+}
+
+// EscrowError a failed instruction returned, None for runtime and Anchor
+// framework errors
+pub fn from_instruction_error(error: &InstructionError) -> Option<EscrowError> {
+    match error {
+        InstructionError::Custom(code) => escrow_error(*code),
+        _ => None,
+    }
+}
@@ -0,0 +1,690 @@
+use anchor_lang::{
+    prelude::*, solana_program::instruction::Instruction, system_program, InstructionData,
+};
+use anchor_spl::{associated_token, token_2022};
+use escrow::{
+    accounts, instruction,
+    state::{Asset, Escrow, EscrowOptions, EscrowState, PayoutSplit, SolCustody},
+    ID,
+};
+
+use crate::pda::{
+    config_address, escrow_address, event_authority, leg_mint, leg_token_account,
+    payout_split_address, program_data_address, receipt_mint_address, sol_vault_address,
+    token_account,
+};
+
+// Token program an instruction runs with: the one its token legs share, token
+// 2022 when both legs are SOL
+pub fn token_program_for(deposit: &Asset, receive: &Asset) -> Pubkey {
+    deposit
+        .token_program()
+        .or(receive.token_program())
+        .unwrap_or(token_2022::ID)
+}
+
+pub struct InitEscrow {
+    pub escrow_id: String,
+    pub escrow_authority: Pubkey,
+    pub receiver: Pubkey,
+    // a wSOL mint means the authority deals in wSOL for its SOL leg
+    pub deposit: Asset,
+    pub deposit_amount: u64,
+    pub receive: Asset,
+    pub receive_amount: u64,
+    pub options: EscrowOptions,
+}
+
+impl InitEscrow {
+    pub fn instruction(self) -> Instruction {
+        let token_program = token_program_for(&self.deposit, &self.receive);
+        let (escrow, _) = escrow_address(&self.escrow_id, &self.escrow_authority, &self.receiver);
+        let (receipt_mint, _) = receipt_mint_address(&escrow);
+        let deposit_mint = self.deposit.mint();
+
+        let accounts = accounts::InitializeEscrow {
+            escrow_authority: self.escrow_authority,
+            receiver: self.receiver,
+            escrow,
+            deposit_mint,
+            token_vault: deposit_mint.map(|mint| token_account(&escrow, &mint, &token_program)),
+            authority_token_vault: deposit_mint
+                .map(|mint| token_account(&self.escrow_authority, &mint, &token_program)),
+            receipt_mint,
+            authority_receipt_ata: token_account(
+                &self.escrow_authority,
+                &receipt_mint,
+                &token_2022::ID,
+            ),
+            payout_split: self
+                .options
+                .split
+                .as_ref()
+                .map(|_| payout_split_address(&escrow).0),
+            config: self.options.referral.map(|_| config_address().0),
+            token_program,
+            receipt_token_program: token_2022::ID,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+            event_authority: event_authority(),
+            program: ID,
+        };
+
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: instruction::InitEscrow {
+                escrow_id: self.escrow_id,
+                deposit: self.deposit,
+                deposit_amount: self.deposit_amount,
+                receive: self.receive,
+                receive_amount: self.receive_amount,
+                options: self.options,
+            }
+            .data(),
+        }
+    }
+}
+
+// Accept `escrow` as its receiver. `escrow_authority` is the authority side,
+// the receipt holder for escrows with a receipt. `split` is the escrow's
+// PayoutSplit, needed when it has one
+pub fn accept(
+    escrow: &Escrow,
+    escrow_authority: &Pubkey,
+    wrapped: bool,
+    split: Option<&PayoutSplit>,
+) -> Instruction {
+    let token_program = token_program_for(&escrow.deposit, &escrow.receive);
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    let receiver = &escrow.receiver;
+
+    let deposit_mint = leg_mint(&escrow.deposit, wrapped, &token_program);
+    let receive_mint = leg_mint(
+        &escrow.receive,
+        wrapped || escrow.authority_wrapped_sol,
+        &token_program,
+    );
+    let (receipt_mint, receipt_account) = receipt_accounts(escrow, &escrow_key, escrow_authority);
+
+    let accounts = accounts::Accept {
+        receiver: *receiver,
+        escrow_authority: *escrow_authority,
+        escrow: escrow_key,
+        sol_vault: legacy_sol_vault(escrow, &escrow_key),
+        deposit_mint,
+        escrow_deposit_mint_ata: escrow
+            .deposit
+            .mint()
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        receiver_deposit_mint_ata: deposit_mint
+            .map(|mint| token_account(receiver, &mint, &token_program)),
+        receive_mint,
+        receiver_receive_mint_ata: leg_token_account(
+            receiver,
+            &escrow.receive,
+            wrapped,
+            &token_program,
+        ),
+        // a split pays its recipients instead, but a bond can still be
+        // forfeited to the authority side
+        authority_receive_mint_ata: leg_token_account(
+            escrow_authority,
+            &escrow.receive,
+            escrow.authority_wrapped_sol,
+            &token_program,
+        )
+        .filter(|_| !escrow.has_split || escrow.committed_until != 0),
+        bond_vault: escrow
+            .receive
+            .mint()
+            .filter(|_| escrow.committed_until != 0)
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        receipt_mint,
+        receipt_account,
+        payout_split: escrow
+            .has_split
+            .then(|| payout_split_address(&escrow_key).0),
+        referrer: escrow.referrer(),
+        referrer_deposit_mint_ata: escrow.referrer().and_then(|referrer| {
+            escrow
+                .deposit
+                .mint()
+                .map(|mint| token_account(&referrer, &mint, &token_program))
+        }),
+        token_program,
+        receipt_token_program: escrow.has_receipt.then_some(token_2022::ID),
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(split_metas(escrow, split, &token_program));
+
+    Instruction {
+        program_id: ID,
+        accounts: metas,
+        data: instruction::Accept {
+            escrow_id: escrow.escrow_id.clone(),
+            wrapped,
+        }
+        .data(),
+    }
+}
+
+// Accept the receiver's counter-offer on `escrow` as its authority side,
+// `escrow_authority` is the receipt holder for escrows with a receipt.
+// `expected_amount` is the counter being accepted, the instruction fails if
+// the receiver replaced it in the meantime
+pub fn accept_counter(
+    escrow: &Escrow,
+    escrow_authority: &Pubkey,
+    expected_amount: u64,
+    split: Option<&PayoutSplit>,
+) -> Instruction {
+    let token_program = token_program_for(&escrow.deposit, &escrow.receive);
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    let receiver = &escrow.receiver;
+
+    // only a funded counter moves the legs
+    let funded = escrow.counter_funded;
+    let deposit_mint =
+        leg_mint(&escrow.deposit, escrow.receiver_wrapped_sol, &token_program).filter(|_| funded);
+    let receive_mint = leg_mint(
+        &escrow.receive,
+        escrow.authority_wrapped_sol,
+        &token_program,
+    )
+    .filter(|_| funded);
+    let (receipt_mint, receipt_account) = receipt_accounts(escrow, &escrow_key, escrow_authority);
+
+    let accounts = accounts::AcceptCounter {
+        escrow_authority: *escrow_authority,
+        receiver: *receiver,
+        escrow: escrow_key,
+        sol_vault: legacy_sol_vault(escrow, &escrow_key),
+        deposit_mint,
+        escrow_deposit_mint_ata: escrow
+            .deposit
+            .mint()
+            .filter(|_| funded)
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        receiver_deposit_mint_ata: deposit_mint
+            .map(|mint| token_account(receiver, &mint, &token_program)),
+        receive_mint,
+        receive_vault: escrow
+            .receive
+            .mint()
+            .filter(|_| funded)
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        authority_receive_mint_ata: receive_mint
+            .filter(|_| !escrow.has_split)
+            .map(|mint| token_account(escrow_authority, &mint, &token_program)),
+        receipt_mint,
+        receipt_account,
+        payout_split: (funded && escrow.has_split).then(|| payout_split_address(&escrow_key).0),
+        referrer: escrow.referrer().filter(|_| funded),
+        referrer_deposit_mint_ata: escrow.referrer().filter(|_| funded).and_then(|referrer| {
+            escrow
+                .deposit
+                .mint()
+                .map(|mint| token_account(&referrer, &mint, &token_program))
+        }),
+        token_program,
+        receipt_token_program: escrow.has_receipt.then_some(token_2022::ID),
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    let mut metas = accounts.to_account_metas(None);
+    if funded {
+        metas.extend(split_metas(escrow, split, &token_program));
+    }
+
+    Instruction {
+        program_id: ID,
+        accounts: metas,
+        data: instruction::AcceptCounter {
+            escrow_id: escrow.escrow_id.clone(),
+            expected_amount,
+        }
+        .data(),
+    }
+}
+
+// Commit to `escrow` as its receiver, posting the bond it asks for. `wrapped`
+// pays a SOL bond in wSOL
+pub fn commit(escrow: &Escrow, wrapped: bool) -> Instruction {
+    let token_program = token_program_for(&escrow.deposit, &escrow.receive);
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    let receive_mint = leg_mint(&escrow.receive, wrapped, &token_program);
+
+    let accounts = accounts::Commit {
+        receiver: escrow.receiver,
+        escrow_authority: escrow.escrow_authority,
+        escrow: escrow_key,
+        receive_mint,
+        receiver_receive_mint_ata: receive_mint
+            .map(|mint| token_account(&escrow.receiver, &mint, &token_program)),
+        bond_vault: escrow
+            .receive
+            .mint()
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        token_program,
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::Commit {
+            escrow_id: escrow.escrow_id.clone(),
+            wrapped,
+        }
+        .data(),
+    }
+}
+
+// Fund the receive leg of `escrow` as its receiver, settled once the
+// challenge window is over
+pub fn fund(escrow: &Escrow, wrapped: bool) -> Instruction {
+    let token_program = token_program_for(&escrow.deposit, &escrow.receive);
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    let receive_mint = leg_mint(&escrow.receive, wrapped, &token_program);
+
+    let accounts = accounts::Fund {
+        receiver: escrow.receiver,
+        escrow_authority: escrow.escrow_authority,
+        escrow: escrow_key,
+        receive_mint,
+        receiver_receive_mint_ata: receive_mint
+            .map(|mint| token_account(&escrow.receiver, &mint, &token_program)),
+        receive_vault: escrow
+            .receive
+            .mint()
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        token_program,
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::Fund {
+            escrow_id: escrow.escrow_id.clone(),
+            wrapped,
+        }
+        .data(),
+    }
+}
+
+// Counter `escrow` as its receiver with `receive_amount`, paid in right away
+// when `fund` is set
+pub fn counter_offer(
+    escrow: &Escrow,
+    receive_amount: u64,
+    fund: bool,
+    wrapped: bool,
+) -> Instruction {
+    let token_program = token_program_for(&escrow.deposit, &escrow.receive);
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    // a funded counter being replaced is refunded the way it was paid
+    let moves_tokens = fund || escrow.counter_funded;
+    let receive_mint = leg_mint(
+        &escrow.receive,
+        wrapped || escrow.counter_funded && escrow.receiver_wrapped_sol,
+        &token_program,
+    )
+    .filter(|_| moves_tokens);
+
+    let accounts = accounts::CounterOffer {
+        receiver: escrow.receiver,
+        escrow_authority: escrow.escrow_authority,
+        escrow: escrow_key,
+        receive_mint,
+        receiver_receive_mint_ata: receive_mint
+            .map(|mint| token_account(&escrow.receiver, &mint, &token_program)),
+        receive_vault: escrow
+            .receive
+            .mint()
+            .filter(|_| moves_tokens)
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        token_program,
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::CounterOffer {
+            escrow_id: escrow.escrow_id.clone(),
+            receive_amount,
+            fund,
+            wrapped,
+        }
+        .data(),
+    }
+}
+
+// Settle a Funded `escrow` as `signer`, either party. `escrow_authority` is
+// the authority side, the receipt holder for escrows with a receipt
+pub fn settle(
+    escrow: &Escrow,
+    signer: &Pubkey,
+    escrow_authority: &Pubkey,
+    split: Option<&PayoutSplit>,
+) -> Instruction {
+    let token_program = token_program_for(&escrow.deposit, &escrow.receive);
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    let receiver = &escrow.receiver;
+
+    let deposit_mint = leg_mint(&escrow.deposit, escrow.receiver_wrapped_sol, &token_program);
+    let receive_mint = leg_mint(
+        &escrow.receive,
+        escrow.authority_wrapped_sol,
+        &token_program,
+    );
+    let (receipt_mint, receipt_account) = receipt_accounts(escrow, &escrow_key, escrow_authority);
+
+    let accounts = accounts::Settle {
+        signer: *signer,
+        escrow_authority: *escrow_authority,
+        receiver: *receiver,
+        escrow: escrow_key,
+        sol_vault: legacy_sol_vault(escrow, &escrow_key),
+        deposit_mint,
+        escrow_deposit_mint_ata: escrow
+            .deposit
+            .mint()
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        receiver_deposit_mint_ata: deposit_mint
+            .map(|mint| token_account(receiver, &mint, &token_program)),
+        receive_mint,
+        receive_vault: escrow
+            .receive
+            .mint()
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        // a split pays its recipients instead, but a bond can still be
+        // forfeited to the authority side
+        authority_receive_mint_ata: leg_token_account(
+            escrow_authority,
+            &escrow.receive,
+            escrow.authority_wrapped_sol,
+            &token_program,
+        )
+        .filter(|_| !escrow.has_split || escrow.committed_until != 0),
+        receipt_mint,
+        receipt_account,
+        payout_split: escrow
+            .has_split
+            .then(|| payout_split_address(&escrow_key).0),
+        referrer: escrow.referrer(),
+        referrer_deposit_mint_ata: escrow.referrer().and_then(|referrer| {
+            escrow
+                .deposit
+                .mint()
+                .map(|mint| token_account(&referrer, &mint, &token_program))
+        }),
+        token_program,
+        receipt_token_program: escrow.has_receipt.then_some(token_2022::ID),
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(split_metas(escrow, split, &token_program));
+
+    Instruction {
+        program_id: ID,
+        accounts: metas,
+        data: instruction::Settle {
+            escrow_id: escrow.escrow_id.clone(),
+        }
+        .data(),
+    }
+}
+
+// Close `escrow` as its authority side, refunding whatever it still holds.
+// `escrow_authority` is the receipt holder for escrows with a receipt
+pub fn close(escrow: &Escrow, escrow_authority: &Pubkey) -> Instruction {
+    let token_program = token_program_for(&escrow.deposit, &escrow.receive);
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    let receiver = &escrow.receiver;
+
+    // only an escrow that is still open has legs to refund
+    let open = matches!(escrow.state, EscrowState::Active | EscrowState::Funded);
+    let holds_receive_leg = open && (escrow.state == EscrowState::Funded || escrow.counter_funded);
+    let holds_bond = open && escrow.committed_until != 0;
+
+    let deposit_mint = leg_mint(
+        &escrow.deposit,
+        escrow.authority_wrapped_sol,
+        &token_program,
+    )
+    .filter(|_| open);
+    let receive_mint = leg_mint(
+        &escrow.receive,
+        escrow.receiver_wrapped_sol || escrow.authority_wrapped_sol,
+        &token_program,
+    )
+    .filter(|_| holds_receive_leg || holds_bond);
+    let (receipt_mint, receipt_account) = receipt_accounts(escrow, &escrow_key, escrow_authority);
+
+    let accounts = accounts::Close {
+        escrow_authority: *escrow_authority,
+        receiver: *receiver,
+        escrow: escrow_key,
+        sol_vault: legacy_sol_vault(escrow, &escrow_key),
+        deposit_mint,
+        token_vault: escrow
+            .deposit
+            .mint()
+            .filter(|_| open)
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        authority_token_vault: deposit_mint
+            .map(|mint| token_account(escrow_authority, &mint, &token_program)),
+        receive_mint,
+        receive_vault: escrow
+            .receive
+            .mint()
+            .filter(|_| holds_receive_leg || holds_bond)
+            .map(|mint| token_account(&escrow_key, &mint, &token_program)),
+        receiver_receive_token_vault: leg_token_account(
+            receiver,
+            &escrow.receive,
+            escrow.receiver_wrapped_sol,
+            &token_program,
+        )
+        .filter(|_| holds_receive_leg),
+        authority_receive_token_vault: leg_token_account(
+            escrow_authority,
+            &escrow.receive,
+            escrow.authority_wrapped_sol,
+            &token_program,
+        )
+        .filter(|_| holds_bond),
+        receipt_mint,
+        receipt_account,
+        payout_split: escrow
+            .has_split
+            .then(|| payout_split_address(&escrow_key).0),
+        token_program,
+        receipt_token_program: escrow.has_receipt.then_some(token_2022::ID),
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::Close {
+            escrow_id: escrow.escrow_id.clone(),
+        }
+        .data(),
+    }
+}
+
+// Hand the authority side of `escrow`, and its receipt, to `new_authority`.
+// `escrow_authority` is the current receipt holder for escrows with a receipt
+pub fn transfer_authority(
+    escrow: &Escrow,
+    escrow_authority: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    let (escrow_key, _) =
+        escrow_address(&escrow.escrow_id, &escrow.seed_authority, &escrow.receiver);
+    let (receipt_mint, receipt_account) = receipt_accounts(escrow, &escrow_key, escrow_authority);
+
+    let accounts = accounts::TransferAuthority {
+        escrow_authority: *escrow_authority,
+        new_authority: *new_authority,
+        escrow: escrow_key,
+        receipt_mint,
+        receipt_account,
+        new_authority_receipt_ata: receipt_mint
+            .map(|mint| token_account(new_authority, &mint, &token_2022::ID)),
+        receipt_token_program: escrow.has_receipt.then_some(token_2022::ID),
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::TransferAuthority {
+            escrow_id: escrow.escrow_id.clone(),
+        }
+        .data(),
+    }
+}
+
+// Rewrite the legacy escrow at `escrow` in the current layout, `payer` tops
+// up its rent. Anyone can
+pub fn migrate_escrow(payer: &Pubkey, escrow: &Pubkey) -> Instruction {
+    let accounts = accounts::MigrateEscrow {
+        payer: *payer,
+        escrow: *escrow,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::MigrateEscrow {}.data(),
+    }
+}
+
+// Create the program Config, `admin` has to be the upgrade authority
+pub fn init_config(admin: &Pubkey, max_referral_bps: u16) -> Instruction {
+    let accounts = accounts::InitConfig {
+        admin: *admin,
+        config: config_address().0,
+        program_data: program_data_address(),
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::InitConfig { max_referral_bps }.data(),
+    }
+}
+
+pub fn update_config(
+    admin: &Pubkey,
+    max_referral_bps: u16,
+    new_admin: Option<Pubkey>,
+) -> Instruction {
+    let accounts = accounts::UpdateConfig {
+        admin: *admin,
+        config: config_address().0,
+        event_authority: event_authority(),
+        program: ID,
+    };
+
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: instruction::UpdateConfig {
+            max_referral_bps,
+            new_admin,
+        }
+        .data(),
+    }
+}
+
+// Receipt mint and the holder's receipt account, for escrows that have one
+fn receipt_accounts(
+    escrow: &Escrow,
+    escrow_key: &Pubkey,
+    holder: &Pubkey,
+) -> (Option<Pubkey>, Option<Pubkey>) {
+    if !escrow.has_receipt {
+        return (None, None);
+    }
+
+    let (receipt_mint, _) = receipt_mint_address(escrow_key);
+    (
+        Some(receipt_mint),
+        Some(token_account(holder, &receipt_mint, &token_2022::ID)),
+    )
+}
+
+fn legacy_sol_vault(escrow: &Escrow, escrow_key: &Pubkey) -> Option<Pubkey> {
+    (escrow.sol_custody == SolCustody::Vault && escrow.deposit.is_native())
+        .then(|| sol_vault_address(escrow_key).0)
+}
+
+// Split recipients lead the remaining accounts in table order: wallets for a
+// SOL leg, their token accounts otherwise
+fn split_metas(
+    escrow: &Escrow,
+    split: Option<&PayoutSplit>,
+    token_program: &Pubkey,
+) -> Vec<AccountMeta> {
+    let Some(split) = split.filter(|_| escrow.has_split) else {
+        return Vec::new();
+    };
+
+    split
+        .shares
+        .iter()
+        .map(|share| {
+            let recipient = match escrow.receive.mint() {
+                Some(mint) => token_account(&share.recipient, &mint, token_program),
+                None => share.recipient,
+            };
+            AccountMeta::new(recipient, false)
+        })
+        .collect()
+}
@@ -0,0 +1,19 @@
+// Off-chain helpers for the escrow program: PDAs, instruction builders,
+// account decoders, error codes and solvency checks. Everything else is
+// re-exported from the program crate so the types always match what it was
+// built with
+pub mod accounts;
+pub mod errors;
+pub mod instructions;
+pub mod pda;
+pub mod solvency;
+
+pub use escrow::{
+    decode::{decode_events, decode_instructions, EscrowEvent},
+    events,
+    state::{
+        Asset, BondTerms, Config, Escrow, EscrowOptions, EscrowState, PayoutSplit, Referral,
+        SplitShare,
+    },
+    ID,
+};
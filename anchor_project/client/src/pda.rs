@@ -0,0 +1,73 @@
+use anchor_lang::{prelude::Pubkey, solana_program::bpf_loader_upgradeable};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use escrow::{
+    state::{
+        native_mint_for, Asset, CONFIG_SEED, ESCROW_SEED, RECEIPT_SEED, SOL_VAULT_SEED, SPLIT_SEED,
+    },
+    ID,
+};
+
+// signer of the program's self-CPI events
+pub use escrow::decode::event_authority;
+
+// The escrow is derived from the authority it was created by, its
+// seed_authority, even after the authority side moved on
+pub fn escrow_address(escrow_id: &str, seed_authority: &Pubkey, receiver: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            ESCROW_SEED.as_bytes(),
+            escrow_id.as_bytes(),
+            seed_authority.as_ref(),
+            receiver.as_ref(),
+        ],
+        &ID,
+    )
+}
+
+// Only migrated legacy escrows hold SOL deposits here
+pub fn sol_vault_address(escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SOL_VAULT_SEED.as_bytes(), escrow.as_ref()], &ID)
+}
+
+pub fn receipt_mint_address(escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_SEED.as_bytes(), escrow.as_ref()], &ID)
+}
+
+pub fn payout_split_address(escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SPLIT_SEED.as_bytes(), escrow.as_ref()], &ID)
+}
+
+pub fn config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED.as_bytes()], &ID)
+}
+
+// ProgramData of the upgradeable program, init_config reads its upgrade
+// authority from there
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+// ATA of `wallet` for `mint` under the token program that owns the mint
+pub fn token_account(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(wallet, mint, token_program)
+}
+
+// Mint a leg is paid in: its own for token legs, the wSOL mint of
+// `token_program` for a native leg paid in wSOL, none for plain SOL
+pub fn leg_mint(asset: &Asset, wrapped: bool, token_program: &Pubkey) -> Option<Pubkey> {
+    match asset.mint() {
+        Some(mint) => Some(mint),
+        None if wrapped => native_mint_for(token_program),
+        None => None,
+    }
+}
+
+// ATA of `wallet` for a leg, None when the leg moves plain SOL
+pub fn leg_token_account(
+    wallet: &Pubkey,
+    asset: &Asset,
+    wrapped: bool,
+    token_program: &Pubkey,
+) -> Option<Pubkey> {
+    leg_mint(asset, wrapped, token_program).map(|mint| token_account(wallet, &mint, token_program))
+}
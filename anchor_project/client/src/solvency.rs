@@ -0,0 +1,126 @@
+use anchor_lang::{prelude::*, Space};
+use escrow::state::{Asset, Escrow, EscrowState, SolCustody};
+
+use crate::pda::{sol_vault_address, token_account};
+
+// What a custody account holds: lamports, or tokens of one mint
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Holding {
+    Lamports,
+    Tokens { mint: Pubkey },
+}
+
+// An account that has to hold at least `amount` for the escrow to pay out
+// everything it owes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Requirement {
+    pub account: Pubkey,
+    pub holding: Holding,
+    pub amount: u64,
+}
+
+// Everything `escrow` at `address` must hold right now. An open escrow owes
+// its deposit, a funded receive leg, a commit bond and a funded counter, the
+// last three in the receive asset. SOL sits on the escrow on top of its rent,
+// or for a legacy escrow's deposit in the sol vault on top of the vault's,
+// tokens in the escrow ATA of their mint
+pub fn requirements(address: &Pubkey, escrow: &Escrow, rent: &Rent) -> Vec<Requirement> {
+    owed(
+        address,
+        escrow,
+        rent.minimum_balance(8 + Escrow::INIT_SPACE),
+        rent,
+    )
+}
+
+// Same for an escrow still in the legacy layout, decoded by
+// decode_legacy_escrow. Until it is migrated the account only has the rent
+// for its own `size`
+pub fn legacy_requirements(
+    address: &Pubkey,
+    escrow: &Escrow,
+    size: usize,
+    rent: &Rent,
+) -> Vec<Requirement> {
+    owed(address, escrow, rent.minimum_balance(size), rent)
+}
+
+fn owed(address: &Pubkey, escrow: &Escrow, escrow_rent: u64, rent: &Rent) -> Vec<Requirement> {
+    let open = matches!(escrow.state, EscrowState::Active | EscrowState::Funded);
+    let deposit = if open { escrow.deposit_amount } else { 0 };
+    let receive = [
+        (escrow.state == EscrowState::Funded, escrow.receive_amount),
+        (open && escrow.committed_until != 0, escrow.bond_amount),
+        (open && escrow.counter_funded, escrow.counter_amount),
+    ]
+    .iter()
+    .filter(|(owed, _)| *owed)
+    .map(|(_, amount)| *amount)
+    .fold(0u64, u64::saturating_add);
+
+    let mut on_escrow = escrow_rent;
+    let mut requirements = Vec::new();
+
+    if deposit > 0 {
+        match (escrow.deposit, escrow.sol_custody) {
+            (Asset::Native, SolCustody::Escrow) => on_escrow = on_escrow.saturating_add(deposit),
+            (Asset::Native, SolCustody::Vault) => requirements.push(Requirement {
+                account: sol_vault_address(address).0,
+                holding: Holding::Lamports,
+                amount: rent.minimum_balance(0).saturating_add(deposit),
+            }),
+            (asset, _) => requirements.push(token_requirement(address, &asset, deposit)),
+        }
+    }
+
+    if receive > 0 {
+        match escrow.receive {
+            Asset::Native => on_escrow = on_escrow.saturating_add(receive),
+            asset => requirements.push(token_requirement(address, &asset, receive)),
+        }
+    }
+
+    requirements.insert(
+        0,
+        Requirement {
+            account: *address,
+            holding: Holding::Lamports,
+            amount: on_escrow,
+        },
+    );
+    requirements
+}
+
+// Accounts besides the escrow itself that hold its legs while it is open.
+// With the escrow gone none of them should be left
+pub fn custody_accounts(address: &Pubkey, deposit: &Asset, receive: &Asset) -> Vec<Pubkey> {
+    let mut accounts = vec![sol_vault_address(address).0];
+    for asset in [deposit, receive] {
+        if let (Some(mint), Some(program)) = (asset.mint(), asset.token_program()) {
+            accounts.push(token_account(address, &mint, &program));
+        }
+    }
+    accounts
+}
+
+// How much of `holding` an account with these lamports and data has. Token
+// accounts of another mint, and anything that isn't one, hold no tokens
+pub fn held(holding: &Holding, lamports: u64, data: &[u8]) -> u64 {
+    match holding {
+        Holding::Lamports => lamports,
+        Holding::Tokens { mint } if data.len() >= 72 && data[..32] == mint.to_bytes() => {
+            u64::from_le_bytes(data[64..72].try_into().unwrap())
+        }
+        Holding::Tokens { .. } => 0,
+    }
+}
+
+fn token_requirement(address: &Pubkey, asset: &Asset, amount: u64) -> Requirement {
+    let mint = asset.mint().expect("token leg");
+    let program = asset.token_program().expect("token leg");
+    Requirement {
+        account: token_account(address, &mint, &program),
+        holding: Holding::Tokens { mint },
+        amount,
+    }
+}
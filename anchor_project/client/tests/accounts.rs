@@ -0,0 +1,124 @@
+// Account data the decoders take and the layouts they turn down
+mod common;
+
+use anchor_lang::{prelude::Pubkey, AccountSerialize, Discriminator, Space};
+use common::*;
+use escrow::state::{SolCustody, ESCROW_VERSION};
+use escrow_client::{
+    accounts::{
+        decode_config, decode_escrow, decode_legacy_escrow, decode_payout_split, is_legacy,
+        needs_migration,
+    },
+    Asset, Config, Escrow, PayoutSplit, SplitShare,
+};
+
+fn data(account: &impl AccountSerialize) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+// an escrow account is allocated for the longest id, the rest stays zeroed
+fn escrow_data(escrow: &Escrow) -> Vec<u8> {
+    let mut data = data(escrow);
+    data.resize(8 + Escrow::INIT_SPACE, 0);
+    data
+}
+
+#[test]
+fn current_escrows_decode() {
+    let (_, escrow) = escrow(Asset::Native, spl_token(Pubkey::new_unique()));
+    let data = escrow_data(&escrow);
+
+    assert!(!needs_migration(&data));
+    let decoded = decode_escrow(&data).unwrap();
+    assert_eq!(decoded.escrow_id, escrow.escrow_id);
+    assert_eq!(decoded.receiver, escrow.receiver);
+    assert_eq!(decoded.receive, escrow.receive);
+    assert_eq!(decoded.deposit_amount, escrow.deposit_amount);
+}
+
+#[test]
+fn other_layouts_need_migration() {
+    let (_, mut escrow) = escrow(Asset::Native, Asset::Native);
+    // a version past the one this client knows
+    escrow.version = ESCROW_VERSION + 1;
+    let unknown = escrow_data(&escrow);
+    // the legacy layout is shorter than the current one
+    let legacy = [Escrow::DISCRIMINATOR, &[0; 150]].concat();
+
+    for data in [unknown, legacy] {
+        assert!(needs_migration(&data));
+        assert!(decode_escrow(&data).is_err());
+    }
+}
+
+#[test]
+fn legacy_escrows_decode_as_migrated() {
+    let (_, _, data) = legacy_escrow();
+
+    assert!(is_legacy(&data) && needs_migration(&data));
+    assert!(decode_escrow(&data).is_err());
+
+    let escrow = decode_legacy_escrow(&data).unwrap();
+    assert_eq!(escrow.escrow_id, "legacy_01");
+    assert_eq!(escrow.seed_authority, escrow.escrow_authority);
+    // a default mint was SOL, any other a Token-2022 mint
+    assert_eq!(escrow.deposit, Asset::Native);
+    assert!(matches!(escrow.receive, Asset::Token2022 { .. }));
+    assert!(escrow.sol_custody == SolCustody::Vault);
+    assert!(!escrow.has_receipt);
+
+    // nothing but the legacy layout decodes as one
+    let (_, current) = common::escrow(Asset::Native, Asset::Native);
+    assert!(decode_legacy_escrow(&escrow_data(&current)).is_err());
+}
+
+#[test]
+fn other_accounts_are_no_escrow() {
+    let config = data(&Config {
+        admin: Pubkey::new_unique(),
+        max_referral_bps: 100,
+        bump: 255,
+    });
+
+    assert!(!needs_migration(&config));
+    assert!(decode_escrow(&config).is_err());
+    assert!(decode_escrow(&[]).is_err());
+}
+
+#[test]
+fn configs_and_splits_decode() {
+    let admin = Pubkey::new_unique();
+    let config = decode_config(&data(&Config {
+        admin,
+        max_referral_bps: 250,
+        bump: 254,
+    }))
+    .unwrap();
+    assert_eq!((config.admin, config.max_referral_bps), (admin, 250));
+
+    let escrow = Pubkey::new_unique();
+    let shares = vec![
+        SplitShare {
+            recipient: Pubkey::new_unique(),
+            bps: 7_000,
+        },
+        SplitShare {
+            recipient: Pubkey::new_unique(),
+            bps: 3_000,
+        },
+    ];
+    let split = data(&PayoutSplit {
+        escrow,
+        shares: shares.clone(),
+        bump: 253,
+    });
+    let decoded = decode_payout_split(&split).unwrap();
+    assert_eq!(decoded.escrow, escrow);
+    assert!(decoded.shares == shares);
+
+    // each decoder checks the discriminator
+    assert!(decode_config(&split).is_err());
+    assert!(decode_payout_split(&data(&config)).is_err());
+}
@@ -0,0 +1,94 @@
+// Escrow accounts built in memory, the way init_escrow leaves them, and a
+// legacy one byte by byte. Each test binary uses part of it
+#![allow(dead_code)]
+
+use anchor_lang::{prelude::*, Discriminator};
+use escrow::state::{legacy::SentinelEscrow, SolCustody, ESCROW_RESERVED_SPACE, ESCROW_VERSION};
+use escrow_client::{
+    pda::{escrow_address, sol_vault_address},
+    Asset, Escrow, EscrowState,
+};
+
+pub const SOL: u64 = 1_000_000_000;
+
+pub fn escrow(deposit: Asset, receive: Asset) -> (Pubkey, Escrow) {
+    let authority = Pubkey::new_unique();
+    let receiver = Pubkey::new_unique();
+    let (address, bump) = escrow_address("deal", &authority, &receiver);
+
+    let escrow = Escrow {
+        version: ESCROW_VERSION,
+        escrow_authority: authority,
+        receiver,
+        deposit,
+        deposit_amount: 5 * SOL,
+        receive,
+        receive_amount: 7 * SOL,
+        authority_wrapped_sol: false,
+        state: EscrowState::Active,
+        bump,
+        escrow_id: "deal".to_string(),
+        sol_custody: SolCustody::Escrow,
+        bond_amount: 0,
+        commit_window: 0,
+        committed_until: 0,
+        challenge_window: 600,
+        challenge_ends: 0,
+        receiver_wrapped_sol: false,
+        counter_amount: 0,
+        counter_funded: false,
+        seed_authority: authority,
+        has_receipt: true,
+        has_split: false,
+        referrer: Pubkey::default(),
+        referral_bps: 0,
+        reserved: [0; ESCROW_RESERVED_SPACE],
+    };
+    (address, escrow)
+}
+
+pub fn spl_token(mint: Pubkey) -> Asset {
+    Asset::SplToken {
+        mint,
+        program: anchor_spl::token::ID,
+    }
+}
+
+// Address, lamports and data of a native SOL for token deal in the legacy
+// layout, the one the test validator loads from tests/fixtures
+pub fn legacy_escrow() -> (Pubkey, u64, Vec<u8>) {
+    let authority = legacy_authority();
+    let receiver = Pubkey::new_from_array([2; 32]);
+    let (address, bump) = escrow_address("legacy_01", &authority, &receiver);
+
+    let mut data = Escrow::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(authority.as_ref());
+    data.extend_from_slice(receiver.as_ref());
+    // Pubkey::default() deposit mint, native SOL
+    data.extend_from_slice(&[0; 32]);
+    data.extend_from_slice(&200_000_000u64.to_le_bytes());
+    data.extend_from_slice(&[3; 32]);
+    data.extend_from_slice(&10_000_000u64.to_le_bytes());
+    // Active
+    data.extend_from_slice(&[0, bump]);
+    data.extend_from_slice(&9u32.to_le_bytes());
+    data.extend_from_slice(b"legacy_01");
+    data.resize(8 + SentinelEscrow::INIT_SPACE, 0);
+
+    (address, Rent::default().minimum_balance(data.len()), data)
+}
+
+// Its sol vault, holding the deposit on top of its rent
+pub fn legacy_sol_vault() -> (Pubkey, u64) {
+    let (escrow, _, _) = legacy_escrow();
+    (
+        sol_vault_address(&escrow).0,
+        Rent::default().minimum_balance(0) + 200_000_000,
+    )
+}
+
+pub fn legacy_authority() -> Pubkey {
+    "FLFhyrQ4Aocyd8qE6EBFRJ7YxyX5wihP9boNPv32nTGU"
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,51 @@
+// ESCROW_ERRORS is written out by hand, held against the enum in the
+// program source so a new variant can't be left out of it
+use anchor_lang::{error::ERROR_CODE_OFFSET, solana_program::instruction::error::InstructionError};
+use escrow_client::errors::{escrow_error, from_instruction_error, ESCROW_ERRORS};
+
+// variant names of EscrowError in declaration order
+fn declared() -> Vec<&'static str> {
+    include_str!("../../programs/escrow/src/errors.rs")
+        .split_once("pub enum EscrowError {")
+        .expect("EscrowError in errors.rs")
+        .1
+        .lines()
+        .map(str::trim)
+        .take_while(|line| *line != "}")
+        .filter(|line| !line.is_empty() && !line.starts_with("#[") && !line.starts_with("//"))
+        .map(|line| line.trim_end_matches(','))
+        .collect()
+}
+
+#[test]
+fn every_variant_in_declaration_order() {
+    let listed: Vec<String> = ESCROW_ERRORS
+        .iter()
+        .map(|error| format!("{error:?}"))
+        .collect();
+
+    assert_eq!(listed, declared());
+}
+
+#[test]
+fn codes_are_the_offset_plus_the_index() {
+    for (index, error) in ESCROW_ERRORS.iter().enumerate() {
+        let code = ERROR_CODE_OFFSET + index as u32;
+        assert_eq!(u32::from(*error), code, "{error:?}");
+        assert_eq!(escrow_error(code).map(u32::from), Some(code));
+    }
+}
+
+#[test]
+fn other_codes_are_no_escrow_error() {
+    let past_the_end = ERROR_CODE_OFFSET + ESCROW_ERRORS.len() as u32;
+
+    assert!(escrow_error(past_the_end).is_none());
+    // Anchor's own, AccountNotInitialized
+    assert!(escrow_error(3012).is_none());
+    assert!(from_instruction_error(&InstructionError::MissingRequiredSignature).is_none());
+    assert_eq!(
+        from_instruction_error(&InstructionError::Custom(ERROR_CODE_OFFSET + 1)).map(u32::from),
+        Some(ERROR_CODE_OFFSET + 1)
+    );
+}
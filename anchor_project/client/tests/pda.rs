@@ -0,0 +1,96 @@
+// Addresses the client derives, checked against the seeds the program
+// declares for them
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{token, token_2022};
+use common::*;
+use escrow_client::{
+    pda::{
+        escrow_address, leg_mint, leg_token_account, payout_split_address, receipt_mint_address,
+        sol_vault_address, token_account,
+    },
+    Asset, ID,
+};
+
+#[test]
+fn escrow_address_follows_its_seeds() {
+    let authority = Pubkey::new_unique();
+    let receiver = Pubkey::new_unique();
+    let (address, bump) = escrow_address("deal", &authority, &receiver);
+
+    let signed = Pubkey::create_program_address(
+        &[
+            b"ESCROW_SEED",
+            b"deal",
+            authority.as_ref(),
+            receiver.as_ref(),
+            &[bump],
+        ],
+        &ID,
+    )
+    .unwrap();
+    assert_eq!(address, signed);
+
+    // every seed tells escrows apart, the order of the parties too
+    assert_ne!(escrow_address("deal2", &authority, &receiver).0, address);
+    assert_ne!(escrow_address("deal", &receiver, &authority).0, address);
+}
+
+#[test]
+fn accounts_of_an_escrow_are_its_own() {
+    let (first, _) = escrow(Asset::Native, Asset::Native);
+    let (second, _) = escrow(Asset::Native, Asset::Native);
+
+    for derive in [
+        sol_vault_address,
+        receipt_mint_address,
+        payout_split_address,
+    ] {
+        assert_ne!(derive(&first).0, derive(&second).0);
+        assert_ne!(derive(&first).0, first);
+    }
+    // same escrow, different seed
+    assert_ne!(sol_vault_address(&first).0, receipt_mint_address(&first).0);
+    assert_ne!(
+        receipt_mint_address(&first).0,
+        payout_split_address(&first).0
+    );
+}
+
+#[test]
+fn leg_mints() {
+    let mint = Pubkey::new_unique();
+
+    assert_eq!(leg_mint(&spl_token(mint), false, &token::ID), Some(mint));
+    assert_eq!(leg_mint(&spl_token(mint), true, &token::ID), Some(mint));
+    assert_eq!(leg_mint(&Asset::Native, false, &token::ID), None);
+    assert_eq!(
+        leg_mint(&Asset::Native, true, &token::ID),
+        Some(token::spl_token::native_mint::ID)
+    );
+    assert_eq!(
+        leg_mint(&Asset::Native, true, &token_2022::ID),
+        Some(token_2022::spl_token_2022::native_mint::ID)
+    );
+}
+
+#[test]
+fn leg_token_accounts() {
+    let wallet = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+
+    assert_eq!(
+        leg_token_account(&wallet, &Asset::Token2022 { mint }, false, &token_2022::ID),
+        Some(token_account(&wallet, &mint, &token_2022::ID))
+    );
+    assert_eq!(
+        leg_token_account(&wallet, &Asset::Native, false, &token::ID),
+        None
+    );
+    // the ATA depends on the token program that owns the mint
+    assert_ne!(
+        token_account(&wallet, &mint, &token::ID),
+        token_account(&wallet, &mint, &token_2022::ID)
+    );
+}
@@ -0,0 +1,156 @@
+// What each escrow owes out of which account, for every leg and state
+mod common;
+
+use anchor_lang::{prelude::*, Space};
+use common::*;
+use escrow::state::SolCustody;
+use escrow_client::{
+    accounts::decode_legacy_escrow,
+    pda::{sol_vault_address, token_account},
+    solvency::{held, legacy_requirements, requirements, Holding, Requirement},
+    Asset, Escrow, EscrowState,
+};
+
+fn rent() -> Rent {
+    Rent::default()
+}
+
+fn escrow_rent() -> u64 {
+    rent().minimum_balance(8 + Escrow::INIT_SPACE)
+}
+
+fn on_escrow(address: Pubkey, amount: u64) -> Requirement {
+    Requirement {
+        account: address,
+        holding: Holding::Lamports,
+        amount,
+    }
+}
+
+fn in_ata(address: Pubkey, asset: Asset, amount: u64) -> Requirement {
+    let mint = asset.mint().unwrap();
+    Requirement {
+        account: token_account(&address, &mint, &asset.token_program().unwrap()),
+        holding: Holding::Tokens { mint },
+        amount,
+    }
+}
+
+#[test]
+fn open_sol_deposit_sits_on_the_escrow() {
+    let receive = spl_token(Pubkey::new_unique());
+    let (address, escrow) = escrow(Asset::Native, receive);
+
+    assert_eq!(
+        requirements(&address, &escrow, &rent()),
+        vec![on_escrow(address, escrow_rent() + 5 * SOL)]
+    );
+}
+
+#[test]
+fn legacy_sol_deposit_sits_in_the_vault() {
+    let (address, mut escrow) = escrow(Asset::Native, spl_token(Pubkey::new_unique()));
+    escrow.sol_custody = SolCustody::Vault;
+
+    assert_eq!(
+        requirements(&address, &escrow, &rent()),
+        vec![
+            on_escrow(address, escrow_rent()),
+            Requirement {
+                account: sol_vault_address(&address).0,
+                holding: Holding::Lamports,
+                amount: rent().minimum_balance(0) + 5 * SOL,
+            },
+        ]
+    );
+}
+
+#[test]
+fn legacy_escrow_owes_its_own_rent_and_the_vault() {
+    let (address, lamports, data) = legacy_escrow();
+    let (vault, vault_lamports) = legacy_sol_vault();
+    let escrow = decode_legacy_escrow(&data).unwrap();
+
+    let owed = legacy_requirements(&address, &escrow, data.len(), &rent());
+    assert_eq!(
+        owed,
+        vec![
+            on_escrow(address, rent().minimum_balance(data.len())),
+            Requirement {
+                account: vault,
+                holding: Holding::Lamports,
+                amount: rent().minimum_balance(0) + escrow.deposit_amount,
+            },
+        ]
+    );
+    // the legacy accounts hold exactly that
+    assert_eq!((owed[0].amount, owed[1].amount), (lamports, vault_lamports));
+}
+
+#[test]
+fn funded_escrow_owes_both_legs() {
+    let deposit = Asset::Token2022 {
+        mint: Pubkey::new_unique(),
+    };
+    let (address, mut escrow) = escrow(deposit, Asset::Native);
+    escrow.state = EscrowState::Funded;
+
+    assert_eq!(
+        requirements(&address, &escrow, &rent()),
+        vec![
+            on_escrow(address, escrow_rent() + 7 * SOL),
+            in_ata(address, deposit, 5 * SOL),
+        ]
+    );
+}
+
+#[test]
+fn bond_and_funded_counter_add_to_the_receive_leg() {
+    let receive = spl_token(Pubkey::new_unique());
+    let (address, mut escrow) = escrow(Asset::Native, receive);
+    escrow.bond_amount = SOL;
+    escrow.committed_until = 1;
+    escrow.counter_amount = 6 * SOL;
+    escrow.counter_funded = true;
+
+    assert_eq!(
+        requirements(&address, &escrow, &rent()),
+        vec![
+            on_escrow(address, escrow_rent() + 5 * SOL),
+            in_ata(address, receive, 7 * SOL),
+        ]
+    );
+
+    // an offered bond nobody committed to and an unfunded counter owe nothing
+    escrow.committed_until = 0;
+    escrow.counter_funded = false;
+    assert_eq!(requirements(&address, &escrow, &rent()).len(), 1);
+}
+
+#[test]
+fn ended_escrows_only_owe_their_rent() {
+    let (address, mut escrow) = escrow(spl_token(Pubkey::new_unique()), Asset::Native);
+    escrow.bond_amount = SOL;
+    escrow.committed_until = 1;
+
+    for state in [EscrowState::Completed, EscrowState::Closed] {
+        escrow.state = state;
+        assert_eq!(
+            requirements(&address, &escrow, &rent()),
+            vec![on_escrow(address, escrow_rent())]
+        );
+    }
+}
+
+#[test]
+fn held_reads_lamports_or_tokens_of_the_mint() {
+    let mint = Pubkey::new_unique();
+    let mut data = [mint.to_bytes(), Pubkey::new_unique().to_bytes()].concat();
+    data.extend(42u64.to_le_bytes());
+
+    assert_eq!(held(&Holding::Lamports, 99, &data), 99);
+    assert_eq!(held(&Holding::Tokens { mint }, 99, &data), 42);
+    let other = Pubkey::new_unique();
+    assert_eq!(held(&Holding::Tokens { mint: other }, 99, &data), 0);
+    assert_eq!(held(&Holding::Tokens { mint }, 99, &data[..40]), 0);
+}
@@ -0,0 +1,20 @@
+// Accept, close and settle with substituted accounts and data against the
+// escrows of programs/escrow/tests/svm/attack.rs. A crash is either a broken
+// invariant there or the program doing something the runtime forbids
+#![no_main]
+
+#[allow(dead_code)]
+#[path = "../../programs/escrow/tests/svm/attack.rs"]
+mod attack;
+#[allow(dead_code)]
+#[path = "../../programs/escrow/tests/svm/runtime.rs"]
+mod runtime;
+#[allow(dead_code)]
+#[path = "../../programs/escrow/tests/svm/setup.rs"]
+mod setup;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|attacks: Vec<attack::Attack>| {
+    attack::Harness::new().run(&attacks);
+});
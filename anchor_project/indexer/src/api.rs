@@ -0,0 +1,380 @@
+use std::str::FromStr;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Result};
+use rusqlite::{params_from_iter, types::Value as Sql, Connection, OptionalExtension, Row};
+use serde_json::{json, Value};
+
+use crate::store::Store;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+// Escrow rows with the time of their created event, which the escrows table
+// itself doesn't keep
+const ESCROWS: &str = "
+SELECT e.address, e.escrow_id, e.escrow_authority, e.receiver,
+    e.deposit_mint, e.deposit_program, e.deposit_amount,
+    e.receive_mint, e.receive_program, e.receive_amount,
+    e.bond_amount, e.committed_until, e.challenge_window, e.challenge_ends,
+    e.counter_amount, e.counter_funded, e.has_split, e.referrer, e.referral_bps,
+    e.version, e.state, e.created_slot, c.timestamp, e.updated_slot
+FROM escrows e
+LEFT JOIN transitions c
+    ON c.escrow = e.address AND c.event = 'created' AND c.slot = e.created_slot
+";
+
+// A response, JSON both when it worked and when it didn't
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+}
+
+// Answer queries on `address` until the process is stopped, one at a time.
+// The indexer can keep writing to the same database meanwhile
+pub fn serve(store: &Store, address: &str) -> Result<()> {
+    let server = tiny_http::Server::http(address)
+        .map_err(|err| anyhow!("could not listen on {address}: {err}"))?;
+    // the frontend calls this straight from the browser
+    let headers = [
+        "Content-Type: application/json",
+        "Access-Control-Allow-Origin: *",
+    ]
+    .map(|header| header.parse::<tiny_http::Header>().expect("valid header"));
+
+    for request in server.incoming_requests() {
+        let response = match request.method() {
+            tiny_http::Method::Get => handle(store.connection(), request.url()),
+            _ => Response::error(405, "only GET is supported"),
+        };
+
+        let mut reply = tiny_http::Response::from_string(response.body.to_string())
+            .with_status_code(response.status);
+        for header in &headers {
+            reply.add_header(header.clone());
+        }
+        if let Err(err) = request.respond(reply) {
+            eprintln!("could not respond: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+// Answer one GET, `url` being the path and query string
+//
+//   /status                  where the index stands
+//   /escrows                 escrows, newest first, filtered by any of
+//                            authority, receiver, deposit_mint, receive_mint
+//                            (sol for SOL), state, and from / to in unix
+//                            seconds of their creation, paged with limit and
+//                            offset
+//   /escrows/<address>       one escrow and its transitions
+//   /volume                  completed volume per mint, both legs counted,
+//                            optionally only for mint and from / to
+pub fn handle(conn: &Connection, url: &str) -> Response {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let query: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let query = Query(&query);
+
+    let result = match path.trim_end_matches('/').split('/').collect::<Vec<_>>()[..] {
+        ["", "status"] => status(conn),
+        ["", "escrows"] => escrows(conn, &query),
+        ["", "escrows", address] => escrow(conn, address),
+        ["", "volume"] => volume(conn, &query),
+        _ => return Response::error(404, format!("no route for {path}")),
+    };
+
+    result.unwrap_or_else(|err| match err.downcast::<BadRequest>() {
+        Ok(BadRequest(message)) => Response::error(400, message),
+        Err(err) => Response::error(500, format!("{err:#}")),
+    })
+}
+
+#[derive(Debug)]
+struct BadRequest(String);
+
+impl std::fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadRequest {}
+
+fn bad_request(message: String) -> anyhow::Error {
+    BadRequest(message).into()
+}
+
+struct Query<'a>(&'a [(String, String)]);
+
+impl Query<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn address(&self, name: &str) -> Result<Option<String>> {
+        self.get(name)
+            .map(|value| {
+                Pubkey::from_str(value)
+                    .map(|address| address.to_string())
+                    .map_err(|_| bad_request(format!("{name} is not an address: {value}")))
+            })
+            .transpose()
+    }
+
+    // A mint, None inside for SOL
+    fn mint(&self, name: &str) -> Result<Option<Option<String>>> {
+        match self.get(name) {
+            Some("sol") => Ok(Some(None)),
+            _ => Ok(self.address(name)?.map(Some)),
+        }
+    }
+
+    fn int(&self, name: &str) -> Result<Option<i64>> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| bad_request(format!("{name} is not a number: {value}")))
+            })
+            .transpose()
+    }
+}
+
+fn status(conn: &Connection) -> Result<Response> {
+    let cursor = conn
+        .query_row("SELECT slot, signature FROM cursor", [], |row| {
+            Ok(json!({ "slot": row.get::<_, i64>(0)?, "signature": row.get::<_, String>(1)? }))
+        })
+        .optional()?;
+    let escrows: i64 = conn.query_row("SELECT COUNT(*) FROM escrows", [], |row| row.get(0))?;
+
+    Ok(Response::ok(json!({
+        "cursor": cursor,
+        "escrows": escrows,
+    })))
+}
+
+fn escrows(conn: &Connection, query: &Query) -> Result<Response> {
+    let mut filters = Vec::new();
+    let mut values = Vec::new();
+
+    for (name, column) in [("authority", "escrow_authority"), ("receiver", "receiver")] {
+        if let Some(address) = query.address(name)? {
+            filters.push(format!("e.{column} = ?"));
+            values.push(Sql::Text(address));
+        }
+    }
+    for column in ["deposit_mint", "receive_mint"] {
+        match query.mint(column)? {
+            Some(Some(mint)) => {
+                filters.push(format!("e.{column} = ?"));
+                values.push(Sql::Text(mint));
+            }
+            // SOL legs have no mint
+            Some(None) => filters.push(format!("e.{column} IS NULL")),
+            None => {}
+        }
+    }
+    if let Some(state) = query.get("state") {
+        if !["active", "funded", "completed", "closed"].contains(&state) {
+            return Err(bad_request(format!("unknown state {state}")));
+        }
+        filters.push("e.state = ?".to_string());
+        values.push(Sql::Text(state.to_string()));
+    }
+    time_range(query, "c.timestamp", &mut filters, &mut values)?;
+
+    let limit = query.int("limit")?.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(bad_request(format!("limit has to be 1 to {MAX_LIMIT}")));
+    }
+    let offset = query.int("offset")?.unwrap_or(0).max(0);
+
+    // one more than asked for tells if there is a next page
+    let sql = format!(
+        "{ESCROWS} {} ORDER BY e.created_slot DESC, e.address LIMIT {} OFFSET {offset}",
+        where_clause(&filters),
+        limit + 1
+    );
+    let mut statement = conn.prepare(&sql)?;
+    let mut escrows: Vec<Value> = statement
+        .query_map(params_from_iter(values), escrow_json)?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let next_offset = (escrows.len() as i64 > limit).then(|| offset + limit);
+    escrows.truncate(limit as usize);
+
+    Ok(Response::ok(json!({
+        "escrows": escrows,
+        "next_offset": next_offset,
+    })))
+}
+
+fn escrow(conn: &Connection, address: &str) -> Result<Response> {
+    let Ok(address) = Pubkey::from_str(address) else {
+        return Err(bad_request(format!("not an address: {address}")));
+    };
+    let address = address.to_string();
+
+    let mut statement = conn.prepare(&format!("{ESCROWS} WHERE e.address = ?1"))?;
+    let Some(mut escrow) = statement
+        .query_map([&address], escrow_json)?
+        .next()
+        .transpose()?
+    else {
+        return Ok(Response::error(404, format!("no escrow {address}")));
+    };
+
+    let mut statement = conn.prepare(
+        "SELECT signature, position, event, from_state, to_state, party, amount, slot, timestamp
+         FROM transitions WHERE escrow = ?1 ORDER BY slot, signature, position",
+    )?;
+    let transitions: Vec<Value> = statement
+        .query_map([&address], |row| {
+            Ok(json!({
+                "signature": row.get::<_, String>(0)?,
+                "position": row.get::<_, i64>(1)?,
+                "event": row.get::<_, String>(2)?,
+                "from_state": row.get::<_, Option<String>>(3)?,
+                "to_state": row.get::<_, Option<String>>(4)?,
+                "party": row.get::<_, Option<String>>(5)?,
+                "amount": row.get::<_, Option<i64>>(6)?,
+                "slot": row.get::<_, i64>(7)?,
+                "timestamp": row.get::<_, i64>(8)?,
+            }))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    escrow["transitions"] = transitions.into();
+
+    Ok(Response::ok(escrow))
+}
+
+// Both legs of every completion in the range, summed per mint. The terms come
+// from the escrow row, so a completion whose id has been used again since is
+// left out rather than counted with the new terms
+fn volume(conn: &Connection, query: &Query) -> Result<Response> {
+    let mut filters = vec![
+        "t.event = 'completed'".to_string(),
+        "e.created_slot <= t.slot".to_string(),
+    ];
+    let mut values = Vec::new();
+    time_range(query, "t.timestamp", &mut filters, &mut values)?;
+
+    let mint = query.mint("mint")?;
+    let sql = format!(
+        "WITH completed AS (
+            SELECT e.deposit_mint, e.deposit_amount, e.receive_mint, e.receive_amount
+            FROM transitions t JOIN escrows e ON e.address = t.escrow
+            {}
+        )
+        SELECT mint, SUM(amount), COUNT(*) FROM (
+            SELECT deposit_mint AS mint, deposit_amount AS amount FROM completed
+            UNION ALL
+            SELECT receive_mint, receive_amount FROM completed
+        )
+        {}
+        GROUP BY mint ORDER BY mint",
+        where_clause(&filters),
+        match &mint {
+            Some(Some(_)) => "WHERE mint = ?",
+            Some(None) => "WHERE mint IS NULL",
+            None => "",
+        }
+    );
+    if let Some(Some(mint)) = mint {
+        values.push(Sql::Text(mint));
+    }
+
+    let mut statement = conn.prepare(&sql)?;
+    let volume: Vec<Value> = statement
+        .query_map(params_from_iter(values), |row| {
+            Ok(json!({
+                "mint": row.get::<_, Option<String>>(0)?.unwrap_or_else(|| "sol".to_string()),
+                "volume": row.get::<_, i64>(1)?,
+                "escrows": row.get::<_, i64>(2)?,
+            }))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(Response::ok(json!({ "volume": volume })))
+}
+
+// from is inclusive, to is not
+fn time_range(
+    query: &Query,
+    column: &str,
+    filters: &mut Vec<String>,
+    values: &mut Vec<Sql>,
+) -> Result<()> {
+    if let Some(from) = query.int("from")? {
+        filters.push(format!("{column} >= ?"));
+        values.push(Sql::Integer(from));
+    }
+    if let Some(to) = query.int("to")? {
+        filters.push(format!("{column} < ?"));
+        values.push(Sql::Integer(to));
+    }
+    Ok(())
+}
+
+fn where_clause(filters: &[String]) -> String {
+    if filters.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", filters.join(" AND "))
+    }
+}
+
+// One row of ESCROWS, legs nested
+fn escrow_json(row: &Row) -> rusqlite::Result<Value> {
+    let leg = |mint: usize| -> rusqlite::Result<Value> {
+        Ok(json!({
+            "mint": row.get::<_, Option<String>>(mint)?,
+            "program": row.get::<_, Option<String>>(mint + 1)?,
+            "amount": row.get::<_, Option<i64>>(mint + 2)?,
+        }))
+    };
+
+    Ok(json!({
+        "address": row.get::<_, String>(0)?,
+        "escrow_id": row.get::<_, Option<String>>(1)?,
+        "escrow_authority": row.get::<_, Option<String>>(2)?,
+        "receiver": row.get::<_, Option<String>>(3)?,
+        "deposit": leg(4)?,
+        "receive": leg(7)?,
+        "bond_amount": row.get::<_, Option<i64>>(10)?,
+        "committed_until": row.get::<_, Option<i64>>(11)?,
+        "challenge_window": row.get::<_, Option<i64>>(12)?,
+        "challenge_ends": row.get::<_, Option<i64>>(13)?,
+        "counter_amount": row.get::<_, Option<i64>>(14)?,
+        "counter_funded": row.get::<_, Option<bool>>(15)?,
+        "has_split": row.get::<_, Option<bool>>(16)?,
+        "referrer": row.get::<_, Option<String>>(17)?,
+        "referral_bps": row.get::<_, Option<i64>>(18)?,
+        "version": row.get::<_, Option<i64>>(19)?,
+        "state": row.get::<_, Option<String>>(20)?,
+        "created_slot": row.get::<_, Option<i64>>(21)?,
+        "created_at": row.get::<_, Option<i64>>(22)?,
+        "updated_slot": row.get::<_, i64>(23)?,
+    }))
+}
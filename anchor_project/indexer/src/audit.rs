@@ -0,0 +1,260 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use anchor_lang::{prelude::Pubkey, Discriminator};
+use anyhow::{anyhow, Context, Result};
+use escrow_client::{
+    accounts::{decode_escrow, decode_legacy_escrow, is_legacy, needs_migration},
+    solvency::{custody_accounts, held, legacy_requirements, requirements, Holding, Requirement},
+    Asset, Escrow, ID,
+};
+
+use crate::{
+    rpc::{Account, Rpc},
+    store::Store,
+};
+
+// A discrepancy the audit found
+#[derive(Debug, PartialEq)]
+pub enum Finding {
+    // an account holds less than the escrow owes out of it
+    Short {
+        escrow: Pubkey,
+        requirement: Requirement,
+        held: u64,
+    },
+    // an account that held a leg of an escrow that no longer exists
+    Orphaned {
+        escrow: Pubkey,
+        account: Pubkey,
+        lamports: u64,
+    },
+    // an escrow in neither the current layout nor the legacy one
+    // migrate_escrow takes, nothing it owes could be checked
+    Unreadable {
+        escrow: Pubkey,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Short {
+                escrow,
+                requirement,
+                held,
+            } => {
+                let unit = match requirement.holding {
+                    Holding::Lamports => "lamports".to_string(),
+                    Holding::Tokens { mint } => format!("of {mint}"),
+                };
+                write!(
+                    f,
+                    "short   {escrow}: {} holds {held} {unit}, owes {}",
+                    requirement.account, requirement.amount
+                )
+            }
+            Finding::Orphaned {
+                escrow,
+                account,
+                lamports,
+            } => write!(
+                f,
+                "orphan  {escrow}: {account} is still there with {lamports} lamports"
+            ),
+            Finding::Unreadable { escrow } => {
+                write!(f, "unknown {escrow}: layout not readable, nothing checked")
+            }
+        }
+    }
+}
+
+pub struct Report {
+    // escrow accounts on chain
+    pub escrows: usize,
+    // of those, still in the legacy layout
+    pub legacy: usize,
+    // escrows the index knows that are gone
+    pub gone: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    pub fn discrepancies(&self) -> usize {
+        self.findings.len()
+    }
+}
+
+// Check every escrow account of the program against what its custody
+// accounts hold, and every escrow the index has seen that is gone for
+// custody accounts it left behind. Only the index remembers those, an empty
+// one means no orphan check
+pub fn audit(rpc: &Rpc, store: &Store) -> Result<Report> {
+    let (_, escrows) = rpc.program_accounts(&ID, Escrow::DISCRIMINATOR)?;
+    let rent = rpc.rent()?;
+
+    let mut findings = Vec::new();
+    let mut owed = Vec::new();
+    let mut legacy = 0;
+    for (address, account) in &escrows {
+        let data = &account.data;
+        // a legacy escrow owes what it will once migrated, out of the
+        // accounts it holds its legs in now
+        let requirements = if is_legacy(data) {
+            legacy += 1;
+            let escrow = decode_legacy_escrow(data)
+                .map_err(|err| anyhow!("{address} is not a legacy escrow: {err}"))?;
+            legacy_requirements(address, &escrow, data.len(), &rent)
+        } else if needs_migration(data) {
+            findings.push(Finding::Unreadable { escrow: *address });
+            continue;
+        } else {
+            let escrow =
+                decode_escrow(data).map_err(|err| anyhow!("{address} is not an escrow: {err}"))?;
+            requirements(address, &escrow, &rent)
+        };
+        owed.extend(
+            requirements
+                .into_iter()
+                .map(|requirement| (*address, requirement)),
+        );
+    }
+
+    // the escrows themselves came with the listing
+    let listed: HashMap<Pubkey, &Account> = escrows
+        .iter()
+        .map(|(address, account)| (*address, account))
+        .collect();
+    let custody: Vec<Pubkey> = owed
+        .iter()
+        .map(|(_, requirement)| requirement.account)
+        .filter(|account| !listed.contains_key(account))
+        .collect();
+    let fetched: HashMap<Pubkey, Account> = custody
+        .iter()
+        .copied()
+        .zip(rpc.accounts(&custody)?)
+        .filter_map(|(address, account)| Some((address, account?)))
+        .collect();
+
+    let mut short = Vec::new();
+    for (escrow, requirement) in owed {
+        let held = listed
+            .get(&requirement.account)
+            .copied()
+            .or_else(|| fetched.get(&requirement.account))
+            .map_or(0, |account| {
+                held(&requirement.holding, account.lamports, &account.data)
+            });
+        if held < requirement.amount {
+            short.push((escrow, requirement, held));
+        }
+    }
+
+    // an escrow that moved between the listing and the custody reads can
+    // look short without being so, those are read again and left out if
+    // they changed
+    let suspects: Vec<Pubkey> = short
+        .iter()
+        .map(|(escrow, ..)| *escrow)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let moved: HashSet<Pubkey> = suspects
+        .iter()
+        .zip(rpc.accounts(&suspects)?)
+        .filter(|(address, account)| {
+            account.as_ref().map(|account| &account.data) != Some(&listed[*address].data)
+        })
+        .map(|(address, _)| *address)
+        .collect();
+    findings.extend(
+        short
+            .into_iter()
+            .filter(|(escrow, ..)| !moved.contains(escrow))
+            .map(|(escrow, requirement, held)| Finding::Short {
+                escrow,
+                requirement,
+                held,
+            }),
+    );
+
+    let gone: Vec<(Pubkey, Vec<Pubkey>)> = indexed_custody(store)?
+        .into_iter()
+        .filter(|(escrow, _)| !listed.contains_key(escrow))
+        .collect();
+    let leftovers: Vec<(Pubkey, Pubkey)> = gone
+        .iter()
+        .flat_map(|(escrow, accounts)| accounts.iter().map(|account| (*escrow, *account)))
+        .collect();
+    let addresses: Vec<Pubkey> = leftovers.iter().map(|(_, account)| *account).collect();
+    for ((escrow, account), found) in leftovers.into_iter().zip(rpc.accounts(&addresses)?) {
+        if let Some(found) = found {
+            findings.push(Finding::Orphaned {
+                escrow,
+                account,
+                lamports: found.lamports,
+            });
+        }
+    }
+
+    Ok(Report {
+        escrows: escrows.len(),
+        legacy,
+        gone: gone.len(),
+        findings,
+    })
+}
+
+// Every escrow in the index with the accounts that held its legs, every event
+// names them
+pub fn indexed_custody(store: &Store) -> Result<Vec<(Pubkey, Vec<Pubkey>)>> {
+    let conn = store.connection();
+    let mut statement = conn.prepare(
+        "SELECT address, deposit_mint, deposit_program, receive_mint, receive_program
+         FROM escrows ORDER BY address",
+    )?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+
+    rows.map(|row| {
+        let (address, deposit_mint, deposit_program, receive_mint, receive_program) = row?;
+        let escrow = pubkey(&address)?;
+        let deposit = stored_asset(deposit_mint, deposit_program)
+            .with_context(|| format!("deposit of {address}"))?;
+        let receive = stored_asset(receive_mint, receive_program)
+            .with_context(|| format!("receive leg of {address}"))?;
+        Ok((escrow, custody_accounts(&escrow, &deposit, &receive)))
+    })
+    .collect()
+}
+
+// A leg as the store keeps it, mint and program NULL for SOL
+fn stored_asset(mint: Option<String>, program: Option<String>) -> Result<Asset> {
+    Ok(match (mint, program) {
+        (Some(mint), Some(program)) => {
+            let (mint, program) = (pubkey(&mint)?, pubkey(&program)?);
+            let token_2022 = Asset::Token2022 { mint };
+            if token_2022.token_program() == Some(program) {
+                token_2022
+            } else {
+                Asset::SplToken { mint, program }
+            }
+        }
+        _ => Asset::Native,
+    })
+}
+
+fn pubkey(address: &str) -> Result<Pubkey> {
+    Pubkey::from_str(address).map_err(|err| anyhow!("invalid address {address}: {err}"))
+}
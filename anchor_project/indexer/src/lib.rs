@@ -0,0 +1,15 @@
+// Follows the escrow program from a validator or a ledger file and keeps
+// every escrow and every transition it went through in SQLite. Events are
+// the source of truth, escrow accounts only fill in what no event carried.
+// api.rs answers queries over the result as JSON, audit.rs checks the chain
+// still holds what the escrows owe
+pub mod api;
+pub mod audit;
+pub mod rpc;
+pub mod sources;
+pub mod store;
+pub mod transaction;
+
+pub use sources::{backfill, replay};
+pub use store::{Cursor, Store};
+pub use transaction::ProgramTransaction;
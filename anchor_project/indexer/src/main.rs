@@ -0,0 +1,174 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use escrow_indexer::{api, audit::audit, backfill, replay, rpc::Rpc, Store};
+
+#[derive(Parser)]
+#[command(
+    name = "escrow-indexer",
+    version,
+    about = "Index escrow events and account states into SQLite"
+)]
+struct Cli {
+    /// SQLite database, created if missing
+    #[arg(long, global = true, default_value = "escrow.db")]
+    db: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Backfill from a validator, from its first transaction or from where
+    /// the last run stopped
+    Rpc {
+        /// RPC URL, or one of localhost, devnet, testnet, mainnet-beta
+        #[arg(long, short, default_value = "localhost")]
+        url: String,
+        /// Keep polling for new transactions after the backfill
+        #[arg(long)]
+        follow: bool,
+        /// Seconds between polls with --follow
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+        /// Append every transaction fetched to a ledger file for `replay`
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+    /// Index a ledger file, one getTransaction result (json encoding) per line
+    Replay { file: PathBuf },
+    /// Answer HTTP/JSON queries over the index, see api.rs for the routes
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8090")]
+        listen: String,
+    },
+    /// Check that every escrow's vaults hold what it owes and that escrows
+    /// the index has seen closed left no vault behind. Exits with 1 on any
+    /// discrepancy
+    Audit {
+        /// RPC URL, or one of localhost, devnet, testnet, mainnet-beta
+        #[arg(long, short, default_value = "localhost")]
+        url: String,
+    },
+    /// Print where the index stands
+    Status,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = run(cli) {
+        eprintln!("error: {err:#}");
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let mut store = Store::open(&cli.db)?;
+
+    match cli.command {
+        Command::Rpc {
+            url,
+            follow,
+            interval,
+            save,
+        } => {
+            let rpc = Rpc::new(cluster_url(&url));
+            let mut save = save
+                .map(|path| {
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&path)
+                        .with_context(|| format!("could not open {}", path.display()))
+                })
+                .transpose()?;
+
+            loop {
+                let indexed = backfill(&rpc, &mut store, save.as_mut().map(|file| file as _))?;
+                if indexed > 0 || !follow {
+                    report(&store, indexed)?;
+                }
+                if !follow {
+                    return Ok(());
+                }
+                thread::sleep(Duration::from_secs(interval));
+            }
+        }
+        Command::Replay { file } => {
+            let reader = BufReader::new(
+                File::open(&file).with_context(|| format!("could not open {}", file.display()))?,
+            );
+            let indexed = replay(reader, &mut store)?;
+            report(&store, indexed)
+        }
+        Command::Serve { listen } => {
+            println!("listening on http://{listen}");
+            api::serve(&store, &listen)
+        }
+        Command::Audit { url } => {
+            let report = audit(&Rpc::new(cluster_url(&url)), &store)?;
+            for finding in &report.findings {
+                println!("{finding}");
+            }
+            println!(
+                "{} escrows checked, {} of them in the legacy layout, {} closed ones checked for vaults left behind",
+                report.escrows, report.legacy, report.gone
+            );
+
+            match report.discrepancies() {
+                0 => Ok(()),
+                1 => bail!("1 discrepancy"),
+                count => bail!("{count} discrepancies"),
+            }
+        }
+        Command::Status => report(&store, 0),
+    }
+}
+
+fn report(store: &Store, indexed: usize) -> Result<()> {
+    let count = |table: &str| -> Result<i64> {
+        Ok(store
+            .connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })?)
+    };
+
+    let mut out = std::io::stdout().lock();
+    if indexed > 0 {
+        writeln!(out, "indexed {indexed} transactions")?;
+    }
+    match store.cursor()? {
+        Some(cursor) => writeln!(
+            out,
+            "at slot {} ({}), {} escrows, {} transitions",
+            cursor.slot,
+            cursor.signature,
+            count("escrows")?,
+            count("transitions")?
+        )?,
+        None => writeln!(out, "nothing indexed yet")?,
+    }
+    Ok(())
+}
+
+fn cluster_url(url: &str) -> String {
+    match url {
+        "l" | "localhost" => "http://127.0.0.1:8899",
+        "d" | "devnet" => "https://api.devnet.solana.com",
+        "t" | "testnet" => "https://api.testnet.solana.com",
+        "m" | "mainnet-beta" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+    .to_string()
+}
@@ -0,0 +1,224 @@
+use std::{str::FromStr, time::Duration};
+
+use anchor_lang::{
+    prelude::{Pubkey, Rent},
+    solana_program::sysvar,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+
+// Most signatures getSignaturesForAddress returns per call
+pub const SIGNATURES_LIMIT: usize = 1000;
+
+// What the indexer reads from a validator, everything at confirmed commitment
+pub struct Rpc {
+    url: String,
+    agent: ureq::Agent,
+}
+
+// Most accounts getMultipleAccounts takes per call
+const ACCOUNTS_LIMIT: usize = 100;
+
+pub struct Account {
+    pub lamports: u64,
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+impl Rpc {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    // Signatures of transactions that touched `address`, newest first, older
+    // than `before` and newer than `until`
+    pub fn signatures(
+        &self,
+        address: &Pubkey,
+        before: Option<&str>,
+        until: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let result = self.call(
+            "getSignaturesForAddress",
+            json!([address.to_string(), {
+                "commitment": "confirmed",
+                "limit": SIGNATURES_LIMIT,
+                "before": before,
+                "until": until,
+            }]),
+        )?;
+
+        result
+            .as_array()
+            .ok_or_else(|| anyhow!("unexpected getSignaturesForAddress result"))?
+            .iter()
+            .map(|entry| {
+                entry["signature"]
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("signature missing from {entry}"))
+            })
+            .collect()
+    }
+
+    // The transaction in json encoding, as the ledger file stores it
+    pub fn transaction(&self, signature: &str) -> Result<Value> {
+        let result = self.call(
+            "getTransaction",
+            json!([signature, {
+                "encoding": "json",
+                "commitment": "confirmed",
+                "maxSupportedTransactionVersion": 0,
+            }]),
+        )?;
+
+        if result.is_null() {
+            bail!("transaction {signature} not found");
+        }
+        Ok(result)
+    }
+
+    // The account and the slot it was read at
+    pub fn account(&self, address: &Pubkey) -> Result<(u64, Option<Account>)> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([address.to_string(), { "encoding": "base64", "commitment": "confirmed" }]),
+        )?;
+
+        Ok((context_slot(&result)?, parse_account(&result["value"])?))
+    }
+
+    // The cluster's Rent sysvar
+    pub fn rent(&self) -> Result<Rent> {
+        let (_, account) = self.account(&sysvar::rent::ID)?;
+        let data = account.ok_or_else(|| anyhow!("no rent sysvar"))?.data;
+        // bincode: lamports_per_byte_year, exemption_threshold, burn_percent
+        if data.len() < 17 {
+            bail!("unexpected rent sysvar of {} bytes", data.len());
+        }
+
+        Ok(Rent {
+            lamports_per_byte_year: u64::from_le_bytes(data[..8].try_into()?),
+            exemption_threshold: f64::from_le_bytes(data[8..16].try_into()?),
+            burn_percent: data[16],
+        })
+    }
+
+    // The accounts in the order asked for
+    pub fn accounts(&self, addresses: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(ACCOUNTS_LIMIT) {
+            let chunk: Vec<String> = chunk.iter().map(Pubkey::to_string).collect();
+            let result = self.call(
+                "getMultipleAccounts",
+                json!([chunk, { "encoding": "base64", "commitment": "confirmed" }]),
+            )?;
+
+            for value in result["value"]
+                .as_array()
+                .ok_or_else(|| anyhow!("unexpected getMultipleAccounts result"))?
+            {
+                accounts.push(parse_account(value)?);
+            }
+        }
+        Ok(accounts)
+    }
+
+    // Every account of `program` whose data starts with `prefix`, and the
+    // slot they were read at
+    pub fn program_accounts(
+        &self,
+        program: &Pubkey,
+        prefix: &[u8],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let result = self.call(
+            "getProgramAccounts",
+            json!([program.to_string(), {
+                "encoding": "base64",
+                "commitment": "confirmed",
+                "withContext": true,
+                "filters": [{ "memcmp": { "offset": 0, "bytes": bs58::encode(prefix).into_string() } }],
+            }]),
+        )?;
+
+        let accounts = result["value"]
+            .as_array()
+            .ok_or_else(|| anyhow!("unexpected getProgramAccounts result"))?
+            .iter()
+            .map(|entry| {
+                let address = entry["pubkey"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("account without a pubkey"))?;
+                let account = parse_account(&entry["account"])?
+                    .ok_or_else(|| anyhow!("{address} listed without its account"))?;
+                Ok((pubkey(address)?, account))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok((context_slot(&result)?, accounts))
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .with_context(|| format!("{method} request to {} failed", self.url))?
+            .into_json()
+            .with_context(|| format!("{method} returned an invalid response"))?;
+
+        let error = &response["error"];
+        if !error.is_null() {
+            bail!(
+                "{method} failed: {}",
+                error["message"].as_str().unwrap_or("rpc error")
+            );
+        }
+
+        Ok(response["result"].take())
+    }
+}
+
+fn context_slot(result: &Value) -> Result<u64> {
+    result["context"]["slot"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("result without a context slot"))
+}
+
+// An account in base64 encoding, null when there is none
+fn parse_account(value: &Value) -> Result<Option<Account>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    let lamports = value["lamports"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("account without lamports"))?;
+    let owner = value["owner"]
+        .as_str()
+        .ok_or_else(|| anyhow!("account without an owner"))?;
+    let data = value["data"][0]
+        .as_str()
+        .ok_or_else(|| anyhow!("account data is not base64"))?;
+
+    Ok(Some(Account {
+        lamports,
+        owner: pubkey(owner)?,
+        data: STANDARD.decode(data)?,
+    }))
+}
+
+fn pubkey(address: &str) -> Result<Pubkey> {
+    Pubkey::from_str(address).map_err(|err| anyhow!("invalid address {address}: {err}"))
+}
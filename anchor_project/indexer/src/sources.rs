@@ -0,0 +1,97 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Write},
+};
+
+use anyhow::{Context, Result};
+use escrow_client::{accounts::decode_escrow, ID};
+use serde_json::Value;
+
+use crate::{
+    rpc::{Rpc, SIGNATURES_LIMIT},
+    store::Store,
+    transaction::ProgramTransaction,
+};
+
+// Index every transaction of the program the node has that the store hasn't,
+// oldest first. With an empty store that goes back to the first one the
+// node still holds, otherwise to the cursor. Each transaction seen is also
+// written to `save`, one per line, as `replay` reads them back. Returns how
+// many were indexed
+pub fn backfill(rpc: &Rpc, store: &mut Store, mut save: Option<&mut dyn Write>) -> Result<usize> {
+    let until = store.cursor()?.map(|cursor| cursor.signature);
+
+    let mut signatures = Vec::new();
+    loop {
+        let page = rpc.signatures(&ID, signatures.last().map(String::as_str), until.as_deref())?;
+        let done = page.len() < SIGNATURES_LIMIT;
+        signatures.extend(page);
+        if done {
+            break;
+        }
+    }
+
+    let mut indexed = 0;
+    let mut touched = BTreeSet::new();
+    for signature in signatures.iter().rev() {
+        if store.contains(signature)? {
+            continue;
+        }
+
+        let value = rpc.transaction(signature)?;
+        if let Some(save) = save.as_mut() {
+            writeln!(save, "{value}")?;
+        }
+
+        let transaction = ProgramTransaction::parse(&value)?;
+        touched.extend(transaction.escrows());
+        store.record(&transaction)?;
+        indexed += 1;
+    }
+
+    // the events say what happened, the accounts fill in escrows that were
+    // created before there were any
+    for escrow in touched {
+        let (slot, Some(account)) = rpc.account(&escrow)? else {
+            continue;
+        };
+        if account.owner != ID {
+            continue;
+        }
+        // an escrow in an older layout is read again once it is migrated
+        if let Ok(decoded) = decode_escrow(&account.data) {
+            store.apply_account(&escrow, slot, &decoded)?;
+        }
+    }
+
+    Ok(indexed)
+}
+
+// Index a ledger file, one getTransaction result in json encoding per line in
+// slot order. Lines before the cursor slot are skipped, so are transactions
+// already indexed. Returns how many were indexed
+pub fn replay(reader: impl BufRead, store: &mut Store) -> Result<usize> {
+    let from = store.cursor()?.map(|cursor| cursor.slot);
+
+    let mut indexed = 0;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value: Value = serde_json::from_str(&line)
+            .with_context(|| format!("line {} is not JSON", number + 1))?;
+        let transaction = ProgramTransaction::parse(&value)
+            .with_context(|| format!("line {} is not a transaction", number + 1))?;
+        if from.is_some_and(|slot| transaction.slot < slot) {
+            continue;
+        }
+
+        if store.record(&transaction)? {
+            indexed += 1;
+        }
+    }
+
+    Ok(indexed)
+}
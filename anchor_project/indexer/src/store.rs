@@ -0,0 +1,614 @@
+use std::{path::Path, time::Duration};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Context, Result};
+use escrow_client::{events::*, Asset, Escrow, EscrowEvent, EscrowState};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use crate::transaction::{event_escrow, ProgramTransaction};
+
+// Amounts, slots and timestamps are INTEGER, addresses base58 TEXT and
+// states lower case names
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS cursor (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL
+);
+
+-- every transaction of the program, failed ones included
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    err TEXT
+);
+CREATE INDEX IF NOT EXISTS transactions_slot ON transactions (slot);
+
+-- an escrow as of its last transition. One first seen through a later event,
+-- one from before events existed, only has what that event carried until
+-- its account is read
+CREATE TABLE IF NOT EXISTS escrows (
+    address TEXT PRIMARY KEY,
+    escrow_id TEXT,
+    escrow_authority TEXT,
+    receiver TEXT,
+    -- mint and token program NULL for SOL
+    deposit_mint TEXT,
+    deposit_program TEXT,
+    deposit_amount INTEGER,
+    receive_mint TEXT,
+    receive_program TEXT,
+    receive_amount INTEGER,
+    bond_amount INTEGER,
+    committed_until INTEGER,
+    challenge_window INTEGER,
+    challenge_ends INTEGER,
+    counter_amount INTEGER,
+    counter_funded INTEGER,
+    has_split INTEGER,
+    referrer TEXT,
+    referral_bps INTEGER,
+    version INTEGER,
+    state TEXT,
+    created_slot INTEGER,
+    updated_slot INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS escrows_authority ON escrows (escrow_authority);
+CREATE INDEX IF NOT EXISTS escrows_receiver ON escrows (receiver);
+
+-- one row per event. `party` is the wallet it is about: the authority for
+-- created, counter_accepted and closed, the new authority for
+-- authority_transferred, the receiver otherwise. `amount` is the deposit for
+-- created, the bond for committed, the receive leg for funded and both
+-- counter events, what the receiver got for completed and the refunded
+-- deposit for closed
+CREATE TABLE IF NOT EXISTS transitions (
+    signature TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    escrow TEXT NOT NULL,
+    event TEXT NOT NULL,
+    from_state TEXT,
+    to_state TEXT,
+    party TEXT,
+    amount INTEGER,
+    slot INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (signature, position)
+);
+CREATE INDEX IF NOT EXISTS transitions_escrow ON transitions (escrow, slot);
+
+CREATE TABLE IF NOT EXISTS config_updates (
+    signature TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    admin TEXT NOT NULL,
+    max_referral_bps INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (signature, position)
+);
+";
+
+// Last transaction indexed, where a restart picks up
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub slot: u64,
+    pub signature: String,
+}
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn =
+            Connection::open(path).with_context(|| format!("could not open {}", path.display()))?;
+        // the api reads while the indexer writes, from another process
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        Self::new(conn)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)
+            .context("could not create the schema")?;
+        Ok(Self { conn })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn cursor(&self) -> Result<Option<Cursor>> {
+        Ok(self
+            .conn
+            .query_row("SELECT slot, signature FROM cursor", [], |row| {
+                Ok(Cursor {
+                    slot: row.get::<_, i64>(0)? as u64,
+                    signature: row.get(1)?,
+                })
+            })
+            .optional()?)
+    }
+
+    pub fn contains(&self, signature: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM transactions WHERE signature = ?1",
+                [signature],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    // The transaction, its events and the cursor in one go, false when it
+    // was indexed before
+    pub fn record(&mut self, transaction: &ProgramTransaction) -> Result<bool> {
+        if self.contains(&transaction.signature)? {
+            return Ok(false);
+        }
+
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO transactions (signature, slot, block_time, err) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                transaction.signature,
+                int(transaction.slot)?,
+                transaction.block_time,
+                transaction.err,
+            ],
+        )?;
+
+        for (position, event) in transaction.events.iter().enumerate() {
+            apply_event(&tx, transaction, position, event).with_context(|| {
+                format!(
+                    "could not index event {position} of {}",
+                    transaction.signature
+                )
+            })?;
+        }
+
+        // replays can go over older slots again, the cursor only moves on
+        tx.execute(
+            "INSERT INTO cursor (id, slot, signature) VALUES (0, ?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET slot = excluded.slot, signature = excluded.signature
+             WHERE excluded.slot >= cursor.slot",
+            params![int(transaction.slot)?, transaction.signature],
+        )?;
+
+        tx.commit()?;
+        Ok(true)
+    }
+
+    // Terms and state from the account itself, read at `slot`. Anything an
+    // event wrote since wins
+    pub fn apply_account(&mut self, address: &Pubkey, slot: u64, escrow: &Escrow) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO escrows (address, updated_slot) VALUES (?1, ?2)
+             ON CONFLICT (address) DO NOTHING",
+            params![address.to_string(), int(slot)?],
+        )?;
+
+        let (deposit_mint, deposit_program) = asset(&escrow.deposit);
+        let (receive_mint, receive_program) = asset(&escrow.receive);
+        self.conn.execute(
+            "UPDATE escrows SET escrow_id = ?2, escrow_authority = ?3, receiver = ?4,
+                deposit_mint = ?5, deposit_program = ?6, deposit_amount = ?7,
+                receive_mint = ?8, receive_program = ?9, receive_amount = ?10,
+                bond_amount = ?11, committed_until = ?12, challenge_window = ?13,
+                challenge_ends = ?14, counter_amount = ?15, counter_funded = ?16,
+                has_split = ?17, referrer = ?18, referral_bps = ?19, version = ?20,
+                state = ?21, updated_slot = ?22
+             WHERE address = ?1 AND updated_slot <= ?22",
+            params![
+                address.to_string(),
+                escrow.escrow_id,
+                escrow.escrow_authority.to_string(),
+                escrow.receiver.to_string(),
+                deposit_mint,
+                deposit_program,
+                int(escrow.deposit_amount)?,
+                receive_mint,
+                receive_program,
+                int(escrow.receive_amount)?,
+                int(escrow.bond_amount)?,
+                escrow.committed_until,
+                escrow.challenge_window,
+                escrow.challenge_ends,
+                int(escrow.counter_amount)?,
+                escrow.counter_funded,
+                escrow.has_split,
+                escrow.referrer().map(|referrer| referrer.to_string()),
+                escrow.referral_bps,
+                escrow.version,
+                state_name(escrow.state),
+                int(slot)?,
+            ],
+        )?;
+
+        Ok(())
+    }
+}
+
+fn apply_event(
+    tx: &Transaction,
+    transaction: &ProgramTransaction,
+    position: usize,
+    event: &EscrowEvent,
+) -> Result<()> {
+    let slot = int(transaction.slot)?;
+
+    let EscrowEvent::ConfigUpdated(event) = event else {
+        let escrow = event_escrow(event).expect("every other event has an escrow");
+        let from_state = escrow_state(tx, &escrow, slot)?;
+        let change = update_escrow(tx, &escrow, slot, event)?;
+
+        tx.execute(
+            "INSERT INTO transitions
+                (signature, position, escrow, event, from_state, to_state, party, amount, slot, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                transaction.signature,
+                position,
+                escrow.to_string(),
+                event_name(event),
+                from_state,
+                change.to_state.map(state_name).or(from_state.as_deref()),
+                change.party.map(|party| party.to_string()),
+                change.amount.map(int).transpose()?,
+                slot,
+                change.timestamp,
+            ],
+        )?;
+        return Ok(());
+    };
+
+    tx.execute(
+        "INSERT INTO config_updates (signature, position, admin, max_referral_bps, slot, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            transaction.signature,
+            position,
+            event.admin.to_string(),
+            event.max_referral_bps,
+            slot,
+            event.timestamp,
+        ],
+    )?;
+    Ok(())
+}
+
+// State of the escrow row before an event, its row made first if need be
+fn escrow_state(tx: &Transaction, escrow: &Pubkey, slot: i64) -> Result<Option<String>> {
+    tx.execute(
+        "INSERT INTO escrows (address, updated_slot) VALUES (?1, ?2)
+         ON CONFLICT (address) DO NOTHING",
+        params![escrow.to_string(), slot],
+    )?;
+
+    Ok(tx.query_row(
+        "SELECT state FROM escrows WHERE address = ?1",
+        [escrow.to_string()],
+        |row| row.get(0),
+    )?)
+}
+
+// What an event did to its escrow: the state it moved it to if it did, its
+// party and amount as the transitions table keeps them
+struct Change {
+    to_state: Option<EscrowState>,
+    party: Option<Pubkey>,
+    amount: Option<u64>,
+    timestamp: i64,
+}
+
+// Write what the event says about the escrow
+fn update_escrow(
+    tx: &Transaction,
+    escrow: &Pubkey,
+    slot: i64,
+    event: &EscrowEvent,
+) -> Result<Change> {
+    let address = escrow.to_string();
+    let set = |columns: &str, values: &[&dyn rusqlite::ToSql]| -> Result<()> {
+        let sql = format!("UPDATE escrows SET {columns}, updated_slot = ?1 WHERE address = ?2");
+        let params: Vec<&dyn rusqlite::ToSql> = [&slot as &dyn rusqlite::ToSql, &address]
+            .into_iter()
+            .chain(values.iter().copied())
+            .collect();
+        tx.execute(&sql, params.as_slice())?;
+        Ok(())
+    };
+
+    // every escrow event names the escrow's id, parties and legs, a row first
+    // seen through a later event has them too
+    let (escrow_id, escrow_authority, receiver, deposit, receive) = identity(event);
+    let (deposit_mint, deposit_program) = asset(deposit);
+    let (receive_mint, receive_program) = asset(receive);
+    set(
+        "escrow_id = ?3, escrow_authority = ?4, receiver = ?5, deposit_mint = ?6,
+         deposit_program = ?7, receive_mint = ?8, receive_program = ?9",
+        &[
+            escrow_id,
+            &escrow_authority.to_string(),
+            &receiver.to_string(),
+            &deposit_mint,
+            &deposit_program,
+            &receive_mint,
+            &receive_program,
+        ],
+    )?;
+
+    Ok(match event {
+        EscrowEvent::Created(EscrowCreated {
+            escrow_authority,
+            deposit_amount,
+            receive_amount,
+            bond_amount,
+            challenge_window,
+            has_split,
+            referrer,
+            referral_bps,
+            timestamp,
+            ..
+        }) => {
+            // an id used again after a close starts over on the same address
+            set(
+                "deposit_amount = ?3, receive_amount = ?4, bond_amount = ?5,
+                 committed_until = 0, challenge_window = ?6, challenge_ends = 0,
+                 counter_amount = 0, counter_funded = 0, has_split = ?7, referrer = ?8,
+                 referral_bps = ?9, version = NULL, state = 'active', created_slot = ?1",
+                &[
+                    &int(*deposit_amount)?,
+                    &int(*receive_amount)?,
+                    &int(*bond_amount)?,
+                    challenge_window,
+                    has_split,
+                    &referrer.map(|referrer| referrer.to_string()),
+                    referral_bps,
+                ],
+            )?;
+            Change {
+                to_state: Some(EscrowState::Active),
+                party: Some(*escrow_authority),
+                amount: Some(*deposit_amount),
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::Committed(EscrowCommitted {
+            receiver,
+            bond_amount,
+            committed_until,
+            timestamp,
+            ..
+        }) => {
+            set("committed_until = ?3", &[committed_until])?;
+            Change {
+                to_state: None,
+                party: Some(*receiver),
+                amount: Some(*bond_amount),
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::Funded(EscrowFunded {
+            receiver,
+            receive_amount,
+            challenge_ends,
+            timestamp,
+            ..
+        }) => {
+            set(
+                "receive_amount = ?3, challenge_ends = ?4, state = 'funded'",
+                &[&int(*receive_amount)?, challenge_ends],
+            )?;
+            Change {
+                to_state: Some(EscrowState::Funded),
+                party: Some(*receiver),
+                amount: Some(*receive_amount),
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::CounterOffered(EscrowCounterOffered {
+            receiver,
+            receive_amount,
+            funded,
+            timestamp,
+            ..
+        }) => {
+            set(
+                "counter_amount = ?3, counter_funded = ?4",
+                &[&int(*receive_amount)?, funded],
+            )?;
+            Change {
+                to_state: None,
+                party: Some(*receiver),
+                amount: Some(*receive_amount),
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::CounterAccepted(EscrowCounterAccepted {
+            escrow_authority,
+            receive_amount,
+            timestamp,
+            ..
+        }) => {
+            // a settled counter is followed by its own completed event
+            set(
+                "receive_amount = ?3, counter_amount = 0, counter_funded = 0",
+                &[&int(*receive_amount)?],
+            )?;
+            Change {
+                to_state: None,
+                party: Some(*escrow_authority),
+                amount: Some(*receive_amount),
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::AuthorityTransferred(EscrowAuthorityTransferred {
+            escrow_authority,
+            timestamp,
+            ..
+        }) => Change {
+            to_state: None,
+            party: Some(*escrow_authority),
+            amount: None,
+            timestamp: *timestamp,
+        },
+        EscrowEvent::Completed(EscrowCompleted {
+            receiver,
+            deposit_amount,
+            timestamp,
+            ..
+        }) => {
+            set("state = 'completed'", &[])?;
+            Change {
+                to_state: Some(EscrowState::Completed),
+                party: Some(*receiver),
+                amount: Some(*deposit_amount),
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::Closed(EscrowClosed {
+            escrow_authority,
+            refunded_deposit,
+            timestamp,
+            ..
+        }) => {
+            set("state = 'closed'", &[])?;
+            Change {
+                to_state: Some(EscrowState::Closed),
+                party: Some(*escrow_authority),
+                amount: Some(*refunded_deposit),
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::Migrated(EscrowMigrated {
+            to_version,
+            timestamp,
+            ..
+        }) => {
+            set("version = ?3", &[to_version])?;
+            Change {
+                to_state: None,
+                party: None,
+                amount: None,
+                timestamp: *timestamp,
+            }
+        }
+        EscrowEvent::ConfigUpdated(_) => unreachable!("config updates have no escrow"),
+    })
+}
+
+pub fn state_name(state: EscrowState) -> &'static str {
+    match state {
+        EscrowState::Active => "active",
+        EscrowState::Funded => "funded",
+        EscrowState::Completed => "completed",
+        EscrowState::Closed => "closed",
+    }
+}
+
+fn event_name(event: &EscrowEvent) -> &'static str {
+    match event {
+        EscrowEvent::Created(_) => "created",
+        EscrowEvent::Committed(_) => "committed",
+        EscrowEvent::Funded(_) => "funded",
+        EscrowEvent::CounterOffered(_) => "counter_offered",
+        EscrowEvent::CounterAccepted(_) => "counter_accepted",
+        EscrowEvent::AuthorityTransferred(_) => "authority_transferred",
+        EscrowEvent::Completed(_) => "completed",
+        EscrowEvent::Closed(_) => "closed",
+        EscrowEvent::Migrated(_) => "migrated",
+        EscrowEvent::ConfigUpdated(_) => "config_updated",
+    }
+}
+
+// Id, authority side, receiver and legs an escrow event names
+fn identity(event: &EscrowEvent) -> (&String, &Pubkey, &Pubkey, &Asset, &Asset) {
+    match event {
+        EscrowEvent::Created(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::Committed(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::Funded(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::CounterOffered(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::CounterAccepted(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::AuthorityTransferred(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::Completed(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::Closed(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::Migrated(e) => (
+            &e.escrow_id,
+            &e.escrow_authority,
+            &e.receiver,
+            &e.deposit,
+            &e.receive,
+        ),
+        EscrowEvent::ConfigUpdated(_) => unreachable!("config updates have no escrow"),
+    }
+}
+
+// Mint and token program of a leg, both NULL for SOL
+fn asset(asset: &Asset) -> (Option<String>, Option<String>) {
+    (
+        asset.mint().map(|mint| mint.to_string()),
+        asset.token_program().map(|program| program.to_string()),
+    )
+}
+
+// SQLite integers are signed
+fn int(value: u64) -> Result<i64> {
+    i64::try_from(value).map_err(|_| anyhow!("{value} does not fit an SQLite integer"))
+}
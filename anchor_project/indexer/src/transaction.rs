@@ -0,0 +1,129 @@
+use std::{collections::BTreeSet, str::FromStr};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Context, Result};
+use escrow_client::{decode_instructions, EscrowEvent};
+use serde_json::Value;
+use solana_message::compiled_instruction::CompiledInstruction;
+
+// One transaction that touched the program, as much of it as the index keeps
+#[derive(Debug)]
+pub struct ProgramTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    // None when it succeeded
+    pub err: Option<String>,
+    // empty for a failed transaction, its events never happened
+    pub events: Vec<EscrowEvent>,
+}
+
+impl ProgramTransaction {
+    // A getTransaction result in json encoding, the same shape the ledger
+    // file has on every line
+    pub fn parse(value: &Value) -> Result<Self> {
+        let signature = value["transaction"]["signatures"][0]
+            .as_str()
+            .ok_or_else(|| anyhow!("transaction without a signature"))?
+            .to_string();
+        let meta = &value["meta"];
+        let err = (!meta["err"].is_null()).then(|| meta["err"].to_string());
+
+        let events = match err {
+            Some(_) => Vec::new(),
+            None => {
+                let account_keys =
+                    account_keys(value).with_context(|| format!("account keys of {signature}"))?;
+                let inner = inner_instructions(meta)
+                    .with_context(|| format!("inner instructions of {signature}"))?;
+                decode_instructions(&account_keys, &inner)
+            }
+        };
+
+        Ok(Self {
+            slot: value["slot"]
+                .as_u64()
+                .ok_or_else(|| anyhow!("{signature} has no slot"))?,
+            block_time: value["blockTime"].as_i64(),
+            signature,
+            err,
+            events,
+        })
+    }
+
+    // Escrows any of the events is about
+    pub fn escrows(&self) -> BTreeSet<Pubkey> {
+        self.events.iter().filter_map(event_escrow).collect()
+    }
+}
+
+pub fn event_escrow(event: &EscrowEvent) -> Option<Pubkey> {
+    match event {
+        EscrowEvent::Created(event) => Some(event.escrow),
+        EscrowEvent::Committed(event) => Some(event.escrow),
+        EscrowEvent::Funded(event) => Some(event.escrow),
+        EscrowEvent::CounterOffered(event) => Some(event.escrow),
+        EscrowEvent::CounterAccepted(event) => Some(event.escrow),
+        EscrowEvent::AuthorityTransferred(event) => Some(event.escrow),
+        EscrowEvent::Completed(event) => Some(event.escrow),
+        EscrowEvent::Closed(event) => Some(event.escrow),
+        EscrowEvent::Migrated(event) => Some(event.escrow),
+        EscrowEvent::ConfigUpdated(_) => None,
+    }
+}
+
+// Static keys, then the ones loaded from lookup tables, writable first
+fn account_keys(value: &Value) -> Result<Vec<Pubkey>> {
+    let loaded = &value["meta"]["loadedAddresses"];
+
+    [
+        &value["transaction"]["message"]["accountKeys"],
+        &loaded["writable"],
+        &loaded["readonly"],
+    ]
+    .into_iter()
+    .filter_map(Value::as_array)
+    .flatten()
+    .map(|key| {
+        let key = key
+            .as_str()
+            .ok_or_else(|| anyhow!("expected an address, got {key}"))?;
+        Pubkey::from_str(key).map_err(|err| anyhow!("invalid address {key}: {err}"))
+    })
+    .collect()
+}
+
+fn inner_instructions(meta: &Value) -> Result<Vec<CompiledInstruction>> {
+    let Some(groups) = meta["innerInstructions"].as_array() else {
+        return Ok(Vec::new());
+    };
+
+    groups
+        .iter()
+        .filter_map(|group| group["instructions"].as_array())
+        .flatten()
+        .map(|instruction| {
+            let index = |value: &Value| {
+                value
+                    .as_u64()
+                    .and_then(|index| u8::try_from(index).ok())
+                    .ok_or_else(|| anyhow!("invalid account index {value}"))
+            };
+            let data = instruction["data"]
+                .as_str()
+                .ok_or_else(|| anyhow!("instruction without data"))?;
+
+            Ok(CompiledInstruction {
+                program_id_index: index(&instruction["programIdIndex"])?,
+                accounts: instruction["accounts"]
+                    .as_array()
+                    .map(|accounts| accounts.iter().map(index).collect())
+                    .transpose()?
+                    .unwrap_or_default(),
+                data: bs58::decode(data)
+                    .into_vec()
+                    .map_err(|err| anyhow!("instruction data is not base58: {err}"))?,
+            })
+        })
+        .collect()
+}
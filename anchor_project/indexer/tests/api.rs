@@ -0,0 +1,202 @@
+// Queries over a ledger of three escrows, each SOL for a token:
+//   a  completed
+//   b  a's authority with another receiver and mint, still active
+//   c  a's receiver and mint with another authority, closed
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use escrow_indexer::{api::handle, replay, Store};
+use serde_json::{json, Value};
+
+struct Fixture {
+    store: Store,
+    a: Parties,
+    b: Parties,
+    c: Parties,
+}
+
+fn fixture() -> Fixture {
+    let a = Parties::new();
+    let b = Parties {
+        escrow: Pubkey::new_unique(),
+        receiver: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        ..a
+    };
+    let c = Parties {
+        escrow: Pubkey::new_unique(),
+        authority: Pubkey::new_unique(),
+        ..a
+    };
+
+    let mut ledger = Ledger::new();
+    ledger.push(&[a.created(101)], None);
+    ledger.push(&[a.funded(102)], None);
+    ledger.push(&[a.completed(103)], None);
+    ledger.push(&[b.created(104)], None);
+    ledger.push(&[c.created(105)], None);
+    ledger.push(&[c.closed(106)], None);
+
+    let mut store = Store::in_memory().unwrap();
+    replay(ledger.text().as_bytes(), &mut store).unwrap();
+    Fixture { store, a, b, c }
+}
+
+fn get(store: &Store, url: &str) -> (u16, Value) {
+    let response = handle(store.connection(), url);
+    (response.status, response.body)
+}
+
+// Addresses of the escrows a listing returned, in order
+fn listed(store: &Store, url: &str) -> Vec<String> {
+    let (status, body) = get(store, url);
+    assert_eq!(status, 200, "{url}: {body}");
+    body["escrows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|escrow| escrow["address"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn addresses(parties: &[&Parties]) -> Vec<String> {
+    parties
+        .iter()
+        .map(|parties| parties.escrow.to_string())
+        .collect()
+}
+
+#[test]
+fn escrows_by_party() {
+    let Fixture { store, a, b, c } = fixture();
+
+    assert_eq!(
+        listed(&store, &format!("/escrows?authority={}", a.authority)),
+        addresses(&[&b, &a])
+    );
+    assert_eq!(
+        listed(&store, &format!("/escrows?receiver={}", a.receiver)),
+        addresses(&[&c, &a])
+    );
+    assert_eq!(listed(&store, "/escrows"), addresses(&[&c, &b, &a]));
+}
+
+#[test]
+fn escrows_by_mint_pair_and_state() {
+    let Fixture { store, a, b, c } = fixture();
+
+    assert_eq!(
+        listed(
+            &store,
+            &format!("/escrows?deposit_mint=sol&receive_mint={}", a.mint)
+        ),
+        addresses(&[&c, &a])
+    );
+    assert!(listed(&store, &format!("/escrows?deposit_mint={}", a.mint)).is_empty());
+    assert_eq!(listed(&store, "/escrows?state=active"), addresses(&[&b]));
+    assert_eq!(listed(&store, "/escrows?state=closed"), addresses(&[&c]));
+}
+
+#[test]
+fn escrows_by_creation_time() {
+    let Fixture { store, a, b, c } = fixture();
+
+    assert_eq!(
+        listed(&store, &format!("/escrows?from={}", time(104))),
+        addresses(&[&c, &b])
+    );
+    assert_eq!(
+        listed(
+            &store,
+            &format!("/escrows?from={}&to={}", time(101), time(104))
+        ),
+        addresses(&[&a])
+    );
+}
+
+#[test]
+fn escrows_in_pages() {
+    let Fixture { store, a, b, c } = fixture();
+
+    let (_, first) = get(&store, "/escrows?limit=2");
+    assert_eq!(first["escrows"].as_array().unwrap().len(), 2);
+    assert_eq!(first["next_offset"], 2);
+
+    assert_eq!(
+        listed(&store, "/escrows?limit=2&offset=2"),
+        addresses(&[&a])
+    );
+    let (_, last) = get(&store, "/escrows?limit=2&offset=2");
+    assert!(last["next_offset"].is_null());
+
+    // every escrow shows up on exactly one page
+    let mut all = listed(&store, "/escrows?limit=1");
+    all.extend(listed(&store, "/escrows?limit=1&offset=1"));
+    all.extend(listed(&store, "/escrows?limit=1&offset=2"));
+    assert_eq!(all, addresses(&[&c, &b, &a]));
+}
+
+#[test]
+fn one_escrow_with_its_history() {
+    let Fixture { store, a, .. } = fixture();
+
+    let (status, escrow) = get(&store, &format!("/escrows/{}", a.escrow));
+    assert_eq!(status, 200);
+    assert_eq!(escrow["state"], "completed");
+    assert_eq!(escrow["created_at"], time(101));
+    assert_eq!(
+        escrow["deposit"],
+        json!({ "mint": null, "program": null, "amount": 2 * SOL })
+    );
+    assert_eq!(escrow["receive"]["mint"], a.mint.to_string());
+
+    let events: Vec<&str> = escrow["transitions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|transition| transition["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(events, ["created", "funded", "completed"]);
+
+    assert_eq!(
+        get(&store, &format!("/escrows/{}", Pubkey::new_unique())).0,
+        404
+    );
+}
+
+#[test]
+fn volume_per_mint() {
+    let Fixture { store, a, .. } = fixture();
+
+    // only a completed, closing c moved nothing. SOL sorts first
+    let (status, body) = get(&store, "/volume");
+    assert_eq!(status, 200);
+    let sol = json!({ "mint": "sol", "volume": 2 * SOL, "escrows": 1 });
+    assert_eq!(
+        body["volume"],
+        json!([sol, { "mint": a.mint.to_string(), "volume": 500, "escrows": 1 }])
+    );
+
+    assert_eq!(get(&store, "/volume?mint=sol").1["volume"], json!([sol]));
+    let (_, later) = get(&store, &format!("/volume?from={}", time(104)));
+    assert_eq!(later["volume"], json!([]));
+}
+
+#[test]
+fn bad_requests() {
+    let Fixture { store, .. } = fixture();
+
+    for url in [
+        "/escrows?state=lost",
+        "/escrows?authority=nobody",
+        "/escrows?limit=0",
+        "/escrows?from=yesterday",
+        "/escrows/nobody",
+    ] {
+        let (status, body) = get(&store, url);
+        assert_eq!(status, 400, "{url}");
+        assert!(body["error"].is_string());
+    }
+    assert_eq!(get(&store, "/accounts").0, 404);
+}
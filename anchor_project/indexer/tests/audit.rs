@@ -0,0 +1,70 @@
+// The part of the audit that comes from the index: which accounts the
+// escrows it has seen kept their legs in
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use escrow_client::{
+    pda::{sol_vault_address, token_account},
+    Asset,
+};
+use escrow_indexer::{
+    audit::{indexed_custody, Finding},
+    replay, Store,
+};
+
+#[test]
+fn custody_of_indexed_escrows() {
+    let closed = Parties::new();
+    // first seen through its funding, which names the legs too
+    let funded = Parties::new();
+
+    let mut ledger = Ledger::new();
+    ledger.push(&[closed.created(101)], None);
+    ledger.push(&[closed.closed(102)], None);
+    ledger.push(&[funded.funded(103)], None);
+    let mut store = Store::in_memory().unwrap();
+    replay(ledger.text().as_bytes(), &mut store).unwrap();
+
+    let token_2022 = Asset::Token2022 { mint: closed.mint }
+        .token_program()
+        .unwrap();
+    let mut expected = vec![
+        (
+            closed.escrow,
+            vec![
+                sol_vault_address(&closed.escrow).0,
+                token_account(&closed.escrow, &closed.mint, &token_2022),
+            ],
+        ),
+        (
+            funded.escrow,
+            vec![
+                sol_vault_address(&funded.escrow).0,
+                token_account(&funded.escrow, &funded.mint, &token_2022),
+            ],
+        ),
+    ];
+    expected.sort_by_key(|(escrow, _)| escrow.to_string());
+    assert_eq!(indexed_custody(&store).unwrap(), expected);
+}
+
+#[test]
+fn findings_name_the_escrow_and_the_account() {
+    let escrow = Pubkey::new_unique();
+    let vault = sol_vault_address(&escrow).0;
+
+    assert_eq!(
+        Finding::Orphaned {
+            escrow,
+            account: vault,
+            lamports: 890_880,
+        }
+        .to_string(),
+        format!("orphan  {escrow}: {vault} is still there with 890880 lamports")
+    );
+    assert_eq!(
+        Finding::Unreadable { escrow }.to_string(),
+        format!("unknown {escrow}: layout not readable, nothing checked")
+    );
+}
@@ -0,0 +1,174 @@
+// Ledger files written by hand: getTransaction results whose inner
+// instructions carry the events the program would have emitted. Each test
+// binary uses part of it
+#![allow(dead_code)]
+
+use anchor_lang::{event::EVENT_IX_TAG_LE, prelude::Pubkey, Event};
+use escrow_client::{
+    events::{EscrowClosed, EscrowCompleted, EscrowCreated, EscrowFunded},
+    pda::event_authority,
+    Asset, EscrowState, ID,
+};
+use serde_json::{json, Value};
+
+pub const SOL: u64 = 1_000_000_000;
+
+// unix time of a slot, block times and event timestamps alike
+pub fn time(slot: u64) -> i64 {
+    1_700_000_000 + slot as i64
+}
+
+pub struct Ledger {
+    pub lines: Vec<String>,
+    pub slot: u64,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            slot: 100,
+        }
+    }
+
+    // A transaction in the next slot, the events as self-CPIs of the program
+    pub fn push(&mut self, events: &[Vec<u8>], err: Option<Value>) -> String {
+        self.slot += 1;
+        let signature = format!("sig{}", self.slot);
+        let instructions: Vec<Value> = events
+            .iter()
+            .map(|data| {
+                let data = [EVENT_IX_TAG_LE, data].concat();
+                json!({
+                    "programIdIndex": 1,
+                    "accounts": [2],
+                    "data": bs58::encode(data).into_string(),
+                    "stackHeight": 2,
+                })
+            })
+            .collect();
+
+        let transaction = json!({
+            "slot": self.slot,
+            "blockTime": time(self.slot),
+            "transaction": {
+                "signatures": [signature],
+                "message": {
+                    "accountKeys": [
+                        Pubkey::new_unique().to_string(),
+                        ID.to_string(),
+                        event_authority().to_string(),
+                    ],
+                },
+            },
+            "meta": {
+                "err": err,
+                "innerInstructions": [{ "index": 0, "instructions": instructions }],
+                "loadedAddresses": { "writable": [], "readonly": [] },
+            },
+        });
+        self.lines.push(transaction.to_string());
+        signature
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+pub struct Parties {
+    pub escrow: Pubkey,
+    pub authority: Pubkey,
+    pub receiver: Pubkey,
+    pub mint: Pubkey,
+}
+
+impl Parties {
+    pub fn new() -> Self {
+        Self {
+            escrow: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            receiver: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+        }
+    }
+
+    pub fn created(&self, slot: u64) -> Vec<u8> {
+        EscrowCreated {
+            escrow: self.escrow,
+            escrow_id: "deal".to_string(),
+            escrow_authority: self.authority,
+            receiver: self.receiver,
+            deposit: Asset::Native,
+            deposit_amount: 2 * SOL,
+            receive: Asset::Token2022 { mint: self.mint },
+            receive_amount: 500,
+            bond_amount: 0,
+            challenge_window: 600,
+            has_split: false,
+            referrer: None,
+            referral_bps: 0,
+            timestamp: time(slot),
+            slot,
+        }
+        .data()
+    }
+
+    pub fn funded(&self, slot: u64) -> Vec<u8> {
+        EscrowFunded {
+            escrow: self.escrow,
+            escrow_id: "deal".to_string(),
+            escrow_authority: self.authority,
+            receiver: self.receiver,
+            deposit: Asset::Native,
+            receive: Asset::Token2022 { mint: self.mint },
+            receive_amount: 500,
+            refunded_bond: 0,
+            challenge_ends: 600,
+            timestamp: time(slot),
+            slot,
+        }
+        .data()
+    }
+
+    pub fn completed(&self, slot: u64) -> Vec<u8> {
+        EscrowCompleted {
+            escrow: self.escrow,
+            escrow_id: "deal".to_string(),
+            escrow_authority: self.authority,
+            receiver: self.receiver,
+            previous_state: EscrowState::Funded,
+            deposit: Asset::Native,
+            deposit_amount: 2 * SOL,
+            receive: Asset::Token2022 { mint: self.mint },
+            receive_amount: 500,
+            referrer: None,
+            referral_fee: 0,
+            refunded_bond: 0,
+            forfeited_bond: 0,
+            reclaimed_lamports: 0,
+            timestamp: time(slot),
+            slot,
+        }
+        .data()
+    }
+
+    pub fn closed(&self, slot: u64) -> Vec<u8> {
+        EscrowClosed {
+            escrow: self.escrow,
+            escrow_id: "deal".to_string(),
+            escrow_authority: self.authority,
+            receiver: self.receiver,
+            previous_state: EscrowState::Active,
+            deposit: Asset::Native,
+            refunded_deposit: 2 * SOL,
+            receive: Asset::Token2022 { mint: self.mint },
+            refunded_receive: 0,
+            forfeited_bond: 0,
+            reclaimed_lamports: 0,
+            timestamp: time(slot),
+            slot,
+        }
+        .data()
+    }
+}
@@ -0,0 +1,177 @@
+// Replaying ledger files into a fresh store, and again over one that has
+// them already
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use escrow_indexer::{replay, Store};
+use serde_json::json;
+
+fn transitions(store: &Store, escrow: &Pubkey) -> Vec<(String, Option<String>, Option<String>)> {
+    let conn = store.connection();
+    let mut statement = conn
+        .prepare(
+            "SELECT event, from_state, to_state FROM transitions
+             WHERE escrow = ?1 ORDER BY slot, position",
+        )
+        .unwrap();
+    statement
+        .query_map([escrow.to_string()], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn escrow_row(store: &Store, escrow: &Pubkey) -> (String, String, i64, Option<String>, String) {
+    store
+        .connection()
+        .query_row(
+            "SELECT state, escrow_authority, deposit_amount, deposit_mint, receive_mint
+             FROM escrows WHERE address = ?1",
+            [escrow.to_string()],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .unwrap()
+}
+
+fn transition(
+    event: &str,
+    from: Option<&str>,
+    to: &str,
+) -> (String, Option<String>, Option<String>) {
+    (
+        event.to_string(),
+        from.map(str::to_string),
+        Some(to.to_string()),
+    )
+}
+
+#[test]
+fn replay_follows_an_escrow_to_completion() {
+    let parties = Parties::new();
+    let mut ledger = Ledger::new();
+    ledger.push(&[parties.created(101)], None);
+    ledger.push(&[parties.funded(102)], None);
+    let last = ledger.push(&[parties.completed(103)], None);
+
+    let mut store = Store::in_memory().unwrap();
+    assert_eq!(replay(ledger.text().as_bytes(), &mut store).unwrap(), 3);
+
+    assert_eq!(
+        transitions(&store, &parties.escrow),
+        vec![
+            transition("created", None, "active"),
+            transition("funded", Some("active"), "funded"),
+            transition("completed", Some("funded"), "completed"),
+        ]
+    );
+    assert_eq!(
+        escrow_row(&store, &parties.escrow),
+        (
+            "completed".to_string(),
+            parties.authority.to_string(),
+            (2 * SOL) as i64,
+            None,
+            parties.mint.to_string()
+        )
+    );
+
+    let cursor = store.cursor().unwrap().unwrap();
+    assert_eq!((cursor.slot, cursor.signature), (103, last));
+}
+
+#[test]
+fn replay_again_indexes_nothing_twice() {
+    let parties = Parties::new();
+    let mut ledger = Ledger::new();
+    ledger.push(&[parties.created(101)], None);
+    ledger.push(&[parties.funded(102)], None);
+
+    let mut store = Store::in_memory().unwrap();
+    assert_eq!(replay(ledger.text().as_bytes(), &mut store).unwrap(), 2);
+    assert_eq!(replay(ledger.text().as_bytes(), &mut store).unwrap(), 0);
+
+    // a longer file picks up after the cursor
+    ledger.push(&[parties.completed(103)], None);
+    assert_eq!(replay(ledger.text().as_bytes(), &mut store).unwrap(), 1);
+    assert_eq!(transitions(&store, &parties.escrow).len(), 3);
+}
+
+#[test]
+fn failed_transactions_have_no_transitions() {
+    let parties = Parties::new();
+    let mut ledger = Ledger::new();
+    ledger.push(&[parties.created(101)], None);
+    let failed = ledger.push(
+        &[parties.funded(102)],
+        Some(json!({ "InstructionError": [0, { "Custom": 6000 }] })),
+    );
+
+    let mut store = Store::in_memory().unwrap();
+    assert_eq!(replay(ledger.text().as_bytes(), &mut store).unwrap(), 2);
+
+    let err: Option<String> = store
+        .connection()
+        .query_row(
+            "SELECT err FROM transactions WHERE signature = ?1",
+            [&failed],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(err.unwrap().contains("6000"));
+    assert_eq!(transitions(&store, &parties.escrow).len(), 1);
+    assert_eq!(escrow_row(&store, &parties.escrow).0, "active");
+}
+
+#[test]
+fn id_used_again_after_close_starts_over() {
+    let parties = Parties::new();
+    let mut ledger = Ledger::new();
+    ledger.push(&[parties.created(101)], None);
+    ledger.push(&[parties.closed(102)], None);
+    ledger.push(&[parties.created(103)], None);
+
+    let mut store = Store::in_memory().unwrap();
+    replay(ledger.text().as_bytes(), &mut store).unwrap();
+
+    assert_eq!(
+        transitions(&store, &parties.escrow),
+        vec![
+            transition("created", None, "active"),
+            transition("closed", Some("active"), "closed"),
+            transition("created", Some("closed"), "active"),
+        ]
+    );
+    let created_slot: i64 = store
+        .connection()
+        .query_row(
+            "SELECT created_slot FROM escrows WHERE address = ?1",
+            [parties.escrow.to_string()],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(created_slot, 103);
+}
+
+#[test]
+fn events_not_signed_by_the_event_authority_are_ignored() {
+    let parties = Parties::new();
+    let mut ledger = Ledger::new();
+    ledger.push(&[parties.created(101)], None);
+    // anyone can call the program with event shaped data
+    ledger.lines[0] = ledger.lines[0].replace("\"accounts\":[2]", "\"accounts\":[0]");
+
+    let mut store = Store::in_memory().unwrap();
+    assert_eq!(replay(ledger.text().as_bytes(), &mut store).unwrap(), 1);
+    assert!(transitions(&store, &parties.escrow).is_empty());
+}
@@ -0,0 +1,237 @@
+// Compute units, rent and CPIs of init_escrow, accept and close for every
+// pairing of SOL, spl-token and token-2022 legs, written to benches/footprint.txt. Commit the
+// file with the change that moved it so the diff shows up in review.
+//
+//     cargo bench -p escrow --bench footprint
+//
+// The program runs in LiteSVM through tests/svm/runtime.rs, `anchor build`
+// first
+#[allow(dead_code)]
+#[path = "../tests/svm/runtime.rs"]
+mod runtime;
+#[allow(dead_code)]
+#[path = "../tests/svm/setup.rs"]
+mod setup;
+
+use std::{collections::HashMap, fmt::Write};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{associated_token, token, token_2022};
+use escrow_client::{
+    instructions::{self, InitEscrow},
+    Asset,
+};
+
+use crate::{runtime::Outcome, setup::*};
+
+const COLUMNS: [&str; 11] = [
+    "variant",
+    "instruction",
+    "compute units",
+    "system",
+    "token",
+    "token-2022",
+    "ata",
+    "created",
+    "rent created",
+    "closed",
+    "rent reclaimed",
+];
+
+// What one instruction did to the accounts around it
+struct Footprint {
+    // each instruction is sent alone, so the transaction's
+    compute_units: u64,
+    // CPIs into the system, token, token-2022 and ATA programs
+    cpis: [usize; 4],
+    created: usize,
+    rent_created: u64,
+    closed: usize,
+    rent_reclaimed: u64,
+}
+
+// Account sizes before an instruction, to tell what it created and closed
+fn sizes(env: &Env) -> HashMap<Pubkey, usize> {
+    env.svm
+        .accounts()
+        .map(|(address, account)| (*address, account.data.len()))
+        .collect()
+}
+
+fn footprint(env: &Env, before: &HashMap<Pubkey, usize>, outcome: &Outcome) -> Footprint {
+    let rent = env.svm.rent();
+    let after = sizes(env);
+
+    let mut cpis = [0; 4];
+    for instruction in &outcome.inner_instructions {
+        let program = [
+            anchor_lang::system_program::ID,
+            token::ID,
+            token_2022::ID,
+            associated_token::ID,
+        ]
+        .iter()
+        .position(|program| *program == instruction.program_id);
+        if let Some(program) = program {
+            cpis[program] += 1;
+        }
+    }
+
+    let created: Vec<usize> = after
+        .iter()
+        .filter(|(address, _)| !before.contains_key(address))
+        .map(|(_, len)| *len)
+        .collect();
+    let closed: Vec<usize> = before
+        .iter()
+        .filter(|(address, _)| !after.contains_key(address))
+        .map(|(_, len)| *len)
+        .collect();
+
+    Footprint {
+        compute_units: outcome.compute_units,
+        cpis,
+        created: created.len(),
+        rent_created: created.iter().map(|len| rent.minimum_balance(*len)).sum(),
+        closed: closed.len(),
+        rent_reclaimed: closed.iter().map(|len| rent.minimum_balance(*len)).sum(),
+    }
+}
+
+// Run `instruction` signed by `signer` and measure it
+fn measure(
+    env: &mut Env,
+    instruction: anchor_lang::solana_program::instruction::Instruction,
+    signer: &Pubkey,
+) -> Footprint {
+    let before = sizes(env);
+    let outcome = ok(env.send(instruction, &[signer]));
+    footprint(env, &before, &outcome)
+}
+
+// A second mint of either token program, both wallets funded
+fn second_mint(env: &mut Env, token_program: &Pubkey) -> Asset {
+    let mint = env.create_mint(token_program);
+    for wallet in [env.authority, env.receiver] {
+        env.mint_to(&wallet, &mint, 1000 * TOKEN);
+    }
+    match *token_program {
+        token::ID => Asset::SplToken {
+            mint,
+            program: token::ID,
+        },
+        _ => Asset::Token2022 { mint },
+    }
+}
+
+fn deal(env: &mut Env, variant: &str) -> Deal {
+    let (deposit, receive) = match variant {
+        "sol/token-2022" => (Asset::Native, env.token_2022()),
+        "sol/spl-token" => (Asset::Native, env.spl_token()),
+        "token-2022/sol" => (env.token_2022(), Asset::Native),
+        "spl-token/sol" => (env.spl_token(), Asset::Native),
+        "token-2022/token-2022" => (env.token_2022(), second_mint(env, &token_2022::ID)),
+        "spl-token/spl-token" => (env.spl_token(), second_mint(env, &token::ID)),
+        _ => unreachable!(),
+    };
+    let amount = |asset: &Asset| if asset.is_native() { SOL } else { 10 * TOKEN };
+    Deal::new(deposit, amount(&deposit), receive, amount(&receive))
+}
+
+fn init(env: &mut Env, escrow_id: &str, deal: Deal) -> Footprint {
+    let instruction = InitEscrow {
+        escrow_id: escrow_id.to_string(),
+        escrow_authority: env.authority,
+        receiver: env.receiver,
+        deposit: deal.deposit,
+        deposit_amount: deal.deposit_amount,
+        receive: deal.receive,
+        receive_amount: deal.receive_amount,
+        options: deal.options,
+    }
+    .instruction();
+    let authority = env.authority;
+    measure(env, instruction, &authority)
+}
+
+fn main() {
+    let mut rows = Vec::new();
+    for variant in [
+        "sol/token-2022",
+        "sol/spl-token",
+        "token-2022/sol",
+        "spl-token/sol",
+        "token-2022/token-2022",
+        "spl-token/spl-token",
+    ] {
+        let mut env = Env::new();
+        let deal = deal(&mut env, variant);
+        let (authority, receiver) = (env.authority, env.receiver);
+
+        rows.push((
+            variant,
+            "init_escrow",
+            init(&mut env, "accepted", deal.clone()),
+        ));
+        let escrow = env.escrow(&env.address("accepted"));
+        let instruction = instructions::accept(&escrow, &authority, false, None);
+        rows.push((variant, "accept", measure(&mut env, instruction, &receiver)));
+
+        init(&mut env, "closed", deal);
+        let escrow = env.escrow(&env.address("closed"));
+        let instruction = instructions::close(&escrow, &authority);
+        rows.push((variant, "close", measure(&mut env, instruction, &authority)));
+    }
+
+    let cells: Vec<[String; 11]> = rows
+        .iter()
+        .map(|(variant, instruction, footprint)| {
+            let [system, token, token_2022, ata] = footprint.cpis.map(|count| count.to_string());
+            [
+                variant.to_string(),
+                instruction.to_string(),
+                footprint.compute_units.to_string(),
+                system,
+                token,
+                token_2022,
+                ata,
+                footprint.created.to_string(),
+                footprint.rent_created.to_string(),
+                footprint.closed.to_string(),
+                footprint.rent_reclaimed.to_string(),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..COLUMNS.len())
+        .map(|column| {
+            cells
+                .iter()
+                .map(|row| row[column].len())
+                .chain([COLUMNS[column].len()])
+                .max()
+                .unwrap()
+        })
+        .collect();
+
+    let mut report = String::from(
+        "# Compute units, CPIs per program at any depth, accounts created and\n\
+         # closed, and their rent in lamports, from\n\
+         # `cargo bench -p escrow --bench footprint`\n",
+    );
+    for row in [COLUMNS.map(String::from)].iter().chain(&cells) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, width))| match column {
+                0 | 1 => format!("{cell:<width$}"),
+                _ => format!("{cell:>width$}"),
+            })
+            .collect();
+        writeln!(report, "{}", line.join("  ").trim_end()).unwrap();
+    }
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/footprint.txt");
+    std::fs::write(path, &report).unwrap();
+    print!("{report}");
+}
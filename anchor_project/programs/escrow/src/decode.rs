@@ -0,0 +1,93 @@
+// Off-chain decoding of the events the program records as self-CPIs. Each one
+// is an inner instruction to this program signed by the event authority, its
+// data the event tag, the event discriminator and the borsh encoded event
+use anchor_lang::{event::EVENT_IX_TAG_LE, prelude::*, Discriminator};
+use solana_message::{
+    compiled_instruction::CompiledInstruction, inner_instruction::InnerInstruction,
+};
+
+use crate::events::*;
+
+pub const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+
+pub fn event_authority() -> Pubkey {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED], &crate::ID).0
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EscrowEvent {
+    Created(EscrowCreated),
+    Committed(EscrowCommitted),
+    Funded(EscrowFunded),
+    CounterOffered(EscrowCounterOffered),
+    CounterAccepted(EscrowCounterAccepted),
+    AuthorityTransferred(EscrowAuthorityTransferred),
+    Completed(EscrowCompleted),
+    Closed(EscrowClosed),
+    Migrated(EscrowMigrated),
+    ConfigUpdated(ConfigUpdated),
+}
+
+impl EscrowEvent {
+    // Data of one event instruction, None for anything else
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let event = data.strip_prefix(EVENT_IX_TAG_LE)?;
+
+        macro_rules! decode_as {
+            ($($event:ident => $variant:ident),* $(,)?) => {
+                $(
+                    if let Some(mut body) = event.strip_prefix($event::DISCRIMINATOR) {
+                        return $event::deserialize(&mut body).ok().map(Self::$variant);
+                    }
+                )*
+            };
+        }
+
+        decode_as!(
+            EscrowCreated => Created,
+            EscrowCommitted => Committed,
+            EscrowFunded => Funded,
+            EscrowCounterOffered => CounterOffered,
+            EscrowCounterAccepted => CounterAccepted,
+            EscrowAuthorityTransferred => AuthorityTransferred,
+            EscrowCompleted => Completed,
+            EscrowClosed => Closed,
+            EscrowMigrated => Migrated,
+            ConfigUpdated => ConfigUpdated,
+        );
+
+        None
+    }
+}
+
+// Events of a successful transaction in emit order, from its account keys
+// (loaded addresses included) and the inner instructions of its metadata
+pub fn decode_events<'a>(
+    account_keys: &[Pubkey],
+    inner_instructions: impl IntoIterator<Item = &'a Vec<InnerInstruction>>,
+) -> Vec<EscrowEvent> {
+    decode_instructions(
+        account_keys,
+        inner_instructions
+            .into_iter()
+            .flatten()
+            .map(|inner| &inner.instruction),
+    )
+}
+
+// Only instructions to this program signed by the event authority count, no
+// one else can produce those
+pub fn decode_instructions<'a>(
+    account_keys: &[Pubkey],
+    instructions: impl IntoIterator<Item = &'a CompiledInstruction>,
+) -> Vec<EscrowEvent> {
+    let authority = event_authority();
+    let key = |index: u8| account_keys.get(index as usize);
+
+    instructions
+        .into_iter()
+        .filter(|ix| key(ix.program_id_index) == Some(&crate::ID))
+        .filter(|ix| ix.accounts.first().and_then(|&index| key(index)) == Some(&authority))
+        .filter_map(|ix| EscrowEvent::decode(&ix.data))
+        .collect()
+}
@@ -40,4 +40,82 @@ pub enum EscrowError {
 
     #[msg("Insufficient balance")]
     InsufficientBalance,
+
+    #[msg("Invalid token program")]
+    InvalidTokenProgram,
+
+    #[msg("Invalid token account")]
+    InvalidTokenAccount,
+
+    #[msg("Escrow account is already up to date")]
+    AlreadyMigrated,
+
+    #[msg("Unknown escrow account version")]
+    UnknownVersion,
+
+    #[msg("SOL vault account required")]
+    MissingSolVault,
+
+    #[msg("Invalid bond terms")]
+    InvalidBondTerms,
+
+    #[msg("Escrow does not take a commit bond")]
+    CommitNotOffered,
+
+    #[msg("Escrow is already committed")]
+    AlreadyCommitted,
+
+    #[msg("Escrow is committed to the receiver")]
+    OfferCommitted,
+
+    #[msg("Bond vault account required")]
+    MissingBondVault,
+
+    #[msg("Invalid challenge window")]
+    InvalidChallengeWindow,
+
+    #[msg("Challenge window is still open")]
+    ChallengeWindowOpen,
+
+    #[msg("Challenge window is over")]
+    ChallengeWindowOver,
+
+    #[msg("No counter-offer to accept")]
+    NoCounterOffer,
+
+    #[msg("Escrow has a funded counter-offer pending")]
+    CounterPending,
+
+    #[msg("Receipt accounts required")]
+    MissingReceipt,
+
+    #[msg("Not the receipt holder")]
+    NotReceiptHolder,
+
+    #[msg("Invalid payout split")]
+    InvalidSplit,
+
+    #[msg("Payout split accounts required")]
+    MissingSplit,
+
+    #[msg("Invalid referral")]
+    InvalidReferral,
+
+    #[msg("Referral fee above the configured cap")]
+    ReferralTooHigh,
+
+    #[msg("Referrer accounts required")]
+    MissingReferrer,
+
+    #[msg("Invalid config")]
+    InvalidConfig,
+
+    #[msg("Counter-offer is not the one expected")]
+    CounterChanged,
+
+    #[msg("Config account required")]
+    MissingConfig,
+
+    #[msg("Referral fee below rent exemption")]
+    ReferralBelowRent,
 }
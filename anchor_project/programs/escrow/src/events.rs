@@ -1,22 +1,183 @@
 use anchor_lang::prelude::*;
 
+use crate::state::{Asset, EscrowState};
+
+// Every event carries enough for an indexer to follow the escrow without
+// fetching it, the account may well be closed by the time it looks. Escrow
+// events all lead with the same fields: the escrow, its id, the authority
+// side, the receiver and both legs. `timestamp` and `slot` are the Clock at
+// emit time. They are recorded as self-CPIs, see decode.rs to read them back
+
 #[event]
+#[derive(Clone, Debug, PartialEq)]
 pub struct EscrowCreated {
     pub escrow: Pubkey,
+    pub escrow_id: String,
     pub escrow_authority: Pubkey,
     pub receiver: Pubkey,
+    pub deposit: Asset,
+    pub deposit_amount: u64,
+    pub receive: Asset,
+    pub receive_amount: u64,
+    // 0 when the offer can't be committed to
+    pub bond_amount: u64,
+    pub challenge_window: i64,
+    pub has_split: bool,
+    pub referrer: Option<Pubkey>,
+    pub referral_bps: u16,
+    pub timestamp: i64,
+    pub slot: u64,
 }
 
 #[event]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscrowCommitted {
+    pub escrow: Pubkey,
+    pub escrow_id: String,
+    pub escrow_authority: Pubkey,
+    pub receiver: Pubkey,
+    pub deposit: Asset,
+    pub receive: Asset,
+    pub bond_amount: u64,
+    pub committed_until: i64,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscrowFunded {
+    pub escrow: Pubkey,
+    pub escrow_id: String,
+    pub escrow_authority: Pubkey,
+    pub receiver: Pubkey,
+    pub deposit: Asset,
+    pub receive: Asset,
+    pub receive_amount: u64,
+    // a bond held from a commit goes back to the receiver on fund
+    pub refunded_bond: u64,
+    pub challenge_ends: i64,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscrowCounterOffered {
+    pub escrow: Pubkey,
+    pub escrow_id: String,
+    pub escrow_authority: Pubkey,
+    pub receiver: Pubkey,
+    pub deposit: Asset,
+    pub receive: Asset,
+    pub receive_amount: u64,
+    pub funded: bool,
+    // a funded counter this one replaced, paid back to the receiver
+    pub refunded_amount: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscrowCounterAccepted {
+    pub escrow: Pubkey,
+    pub escrow_id: String,
+    pub escrow_authority: Pubkey,
+    pub receiver: Pubkey,
+    pub deposit: Asset,
+    pub receive: Asset,
+    pub previous_receive_amount: u64,
+    pub receive_amount: u64,
+    // a funded counter settles right away, otherwise the receiver still accepts
+    pub settled: bool,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscrowAuthorityTransferred {
+    pub escrow: Pubkey,
+    pub escrow_id: String,
+    // the new authority side
+    pub escrow_authority: Pubkey,
+    pub receiver: Pubkey,
+    pub deposit: Asset,
+    pub receive: Asset,
+    pub previous_authority: Pubkey,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq)]
 pub struct EscrowCompleted {
     pub escrow: Pubkey,
+    pub escrow_id: String,
     pub escrow_authority: Pubkey,
     pub receiver: Pubkey,
+    pub previous_state: EscrowState,
+    pub deposit: Asset,
+    // what the receiver got, the deposit leg less the referral fee
+    pub deposit_amount: u64,
+    pub receive: Asset,
+    pub receive_amount: u64,
+    pub referrer: Option<Pubkey>,
+    pub referral_fee: u64,
+    // a commit bond handed back to the receiver on accept
+    pub refunded_bond: u64,
+    // a commit bond the receiver let run out, paid to the authority side
+    pub forfeited_bond: u64,
+    // vault, escrow ata and bond vault rent paid back on accept
+    pub reclaimed_lamports: u64,
+    pub timestamp: i64,
+    pub slot: u64,
 }
 
 #[event]
+#[derive(Clone, Debug, PartialEq)]
 pub struct EscrowClosed {
     pub escrow: Pubkey,
+    pub escrow_id: String,
+    pub escrow_authority: Pubkey,
+    pub receiver: Pubkey,
+    pub previous_state: EscrowState,
+    pub deposit: Asset,
+    // deposit leg back to the authority, 0 for an escrow that already ended
+    pub refunded_deposit: u64,
+    pub receive: Asset,
+    // a funded receive leg or counter back to the receiver
+    pub refunded_receive: u64,
+    // bond of a lapsed commit, kept by the authority
+    pub forfeited_bond: u64,
+    // lamports returned to the authority, escrow account included
+    pub reclaimed_lamports: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscrowMigrated {
+    pub escrow: Pubkey,
+    pub escrow_id: String,
     pub escrow_authority: Pubkey,
     pub receiver: Pubkey,
+    pub deposit: Asset,
+    pub receive: Asset,
+    // 0 for the legacy layout, it has no version byte
+    pub from_version: u8,
+    pub to_version: u8,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub max_referral_bps: u16,
+    pub timestamp: i64,
+    pub slot: u64,
 }
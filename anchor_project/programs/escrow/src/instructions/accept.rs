@@ -1,31 +1,37 @@
 use crate::{
     errors::EscrowError,
-    events::EscrowCompleted,
-    state::{Escrow, EscrowState, ESCROW_SEED, SOL_VAULT_SEED},
-};
-use anchor_lang::prelude::{
-    program::{invoke, invoke_signed},
-    system_instruction::transfer,
-    *,
+    receipt::{check_authority_side, Receipt},
+    release::{release, Payout, ReceiveLeg},
+    state::{
+        Escrow, EscrowState, PayoutSplit, SolCustody, ESCROW_SEED, RECEIPT_SEED, SOL_VAULT_SEED,
+        SPLIT_SEED,
+    },
+    transfer::{
+        close_token_vault, split_recipients, transfer_out, Custody, Party, TransferContext,
+    },
 };
+use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_2022::{self, Token2022, TransferChecked},
-    token_interface::{Mint, TokenAccount},
+    token_2022::Token2022,
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-pub fn _accept(ctx: Context<Accept>, escrow_id: String) -> Result<()> {
+pub fn _accept<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Accept<'info>>,
+    escrow_id: String,
+    wrapped: bool,
+) -> Result<()> {
     let escrow = &mut ctx.accounts.escrow;
-    let sol_vault = &mut ctx.accounts.sol_vault;
     let escrow_authority = &mut ctx.accounts.escrow_authority;
     let receiver = &mut ctx.accounts.receiver;
-    let system_program = &mut ctx.accounts.system_program;
-    let token_program = &mut ctx.accounts.token_program;
+    let system_program = &ctx.accounts.system_program;
+    let token_program = &ctx.accounts.token_program;
 
-    let vault_bump = ctx.bumps.sol_vault;
+    let vault_bump = ctx.bumps.sol_vault.unwrap_or_default();
 
     let escrow_id_bytes = escrow_id.as_bytes();
-    let escrow_authority_key = escrow_authority.key();
+    let seed_authority = escrow.seed_authority;
     let receiver_key = receiver.key();
     let escrow_key = escrow.key();
     let escrow_bump_ref = &[escrow.bump];
@@ -35,12 +41,22 @@ pub fn _accept(ctx: Context<Accept>, escrow_id: String) -> Result<()> {
         escrow_state == EscrowState::Active,
         EscrowError::InvalidState
     );
+    // the receiver has to take a funded counter back before the stored terms
+    require!(!escrow.counter_funded, EscrowError::CounterPending);
+
+    let receipt = Receipt {
+        mint: ctx.accounts.receipt_mint.as_deref(),
+        token_account: ctx.accounts.receipt_account.as_deref(),
+        token_program: ctx.accounts.receipt_token_program.as_ref(),
+    };
+    // proceeds go to the receipt holder when there is one
+    check_authority_side(escrow, &escrow_authority.key(), &receipt)?;
 
     // get signer seeds
     let escrow_seeds: &[&[u8]; 5] = &[
         ESCROW_SEED.as_bytes(),
         escrow_id_bytes,
-        escrow_authority_key.as_ref(),
+        seed_authority.as_ref(),
         receiver_key.as_ref(),
         escrow_bump_ref,
     ];
@@ -55,107 +71,157 @@ pub fn _accept(ctx: Context<Accept>, escrow_id: String) -> Result<()> {
     ];
     let vault_signer_seeds = &[&vault_seeds[..]];
 
-    let is_deposit_sol = escrow.deposit_mint == Pubkey::default();
-    let is_receive_sol = escrow.receive_mint == Pubkey::default();
-
-    // Transfer Token from receive mint to the escrow_authority and fund the receiver with deposited SOL
-    if is_deposit_sol {
-        // Transfer Receiver Token Out from the Receiver to the escrow Authority
-        let mint = &ctx.accounts.receive_mint;
-        let from_ata = &ctx.accounts.receiver_receive_mint_ata;
-        let to_ata = &ctx.accounts.authority_receive_mint_ata;
-
-        require!(
-            mint.key() == escrow.receive_mint,
-            EscrowError::InvalidReceiveMint
-        );
-        // Token tranfer
-        let cpi_accounts = TransferChecked {
-            from: from_ata.to_account_info(),
-            mint: mint.to_account_info(),
-            to: to_ata.to_account_info(),
-            authority: receiver.to_account_info(),
-        };
-
-        let transfer_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
-
-        token_2022::transfer_checked(transfer_ctx, escrow.receive_amount, mint.decimals)?;
-
-        // Transfer SOl (deposit amount) From the Escrow Vault to the Receiver
-        let transfer_ix = transfer(&sol_vault.key(), &receiver.key(), escrow.deposit_amount);
-
-        invoke_signed(
-            &transfer_ix,
-            &[
-                sol_vault.to_account_info(),
-                receiver.to_account_info(),
-                system_program.to_account_info(),
-            ],
-            vault_signer_seeds,
-        )?;
-    }
-
-    // Transfer SOl from the receiver to the escrow authority
-    // Transfer Deposit Mint from Escrow to the Receiver
-    if is_receive_sol {
-        // Transfer Deposit Token from the Escrow to the Receiver
-        let mint = &ctx.accounts.deposit_mint;
-        let from_ata = &ctx.accounts.escrow_deposit_mint_ata;
-        let to_ata = &ctx.accounts.receiver_deposit_mint_ata;
+    // only SOL deposits of legacy escrows are still in the vault
+    let sol_vault = match escrow.sol_custody {
+        SolCustody::Vault if escrow.deposit.is_native() => Some(
+            ctx.accounts
+                .sol_vault
+                .as_ref()
+                .ok_or(EscrowError::MissingSolVault)?
+                .to_account_info(),
+        ),
+        _ => None,
+    };
+
+    // mints are checked against the legs in the accounts context, a token
+    // leg just has to come with one
+    require!(
+        escrow.receive.is_native() || ctx.accounts.receive_mint.is_some(),
+        EscrowError::InvalidReceiveMint
+    );
+    require!(
+        escrow.deposit.is_native() || ctx.accounts.deposit_mint.is_some(),
+        EscrowError::InvalidDepositMint
+    );
 
+    // with a split the receive leg follows the table, its recipients lead the
+    // remaining accounts
+    let split = match escrow.has_split {
+        true => Some(
+            ctx.accounts
+                .payout_split
+                .as_deref()
+                .ok_or(EscrowError::MissingSplit)?,
+        ),
+        false => None,
+    };
+    let (recipients, hook_accounts) =
+        split_recipients(split.map(|split| &**split), ctx.remaining_accounts)?;
+
+    let receive_ctx = TransferContext {
+        mint: ctx.accounts.receive_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: hook_accounts,
+    };
+
+    // Hand a commit bond back first, a wSOL payer unwraps it along with the
+    // rest of the receive leg. Once the window is over it is forfeited to the
+    // authority side instead, like on close
+    let mut bond_rent = 0;
+    let mut refunded_bond = 0;
+    let mut forfeited_bond = 0;
+    if escrow.committed_until != 0 {
         require!(
-            mint.key() == escrow.deposit_mint,
-            EscrowError::InvalidDepositMint
+            escrow.receive.is_native() || ctx.accounts.bond_vault.is_some(),
+            EscrowError::MissingBondVault
         );
 
-        // Token tranfer
-        let cpi_accounts = TransferChecked {
-            from: from_ata.to_account_info(),
-            mint: mint.to_account_info(),
-            to: to_ata.to_account_info(),
-            authority: escrow.to_account_info(),
+        let bond_custody = Custody {
+            sol_vault: None,
+            vault_seeds: vault_signer_seeds,
+            escrow: escrow.to_account_info(),
+            escrow_seeds: signer_seeds,
+            token_account: ctx.accounts.bond_vault.as_ref(),
         };
 
-        let transfer_ctx = CpiContext::new_with_signer(
-            token_program.to_account_info(),
-            cpi_accounts,
-            signer_seeds,
-        );
-
-        token_2022::transfer_checked(transfer_ctx, escrow.deposit_amount, mint.decimals)?;
-        msg!("Transferred {} tokens to receiver", escrow.deposit_amount);
-
-        // SOL from the receiver to The escrow Authority
-        let transfer_ix = transfer(
-            &receiver.key(),
-            &escrow_authority.key(),
-            escrow.receive_amount,
-        );
-        invoke(
-            &transfer_ix,
-            &[
-                receiver.to_account_info(),
-                escrow_authority.to_account_info(),
-                system_program.to_account_info(),
-            ],
-        )?;
-        msg!(
-            "Transferred {} lamports SOL to authority",
-            escrow.receive_amount
-        );
+        if Clock::get()?.unix_timestamp < escrow.committed_until {
+            transfer_out(
+                &receive_ctx,
+                &escrow.receive,
+                escrow.bond_amount,
+                &bond_custody,
+                &Party {
+                    wallet: receiver.to_account_info(),
+                    token_account: ctx.accounts.receiver_receive_mint_ata.as_ref(),
+                    wrapped,
+                },
+            )?;
+            refunded_bond = escrow.bond_amount;
+        } else {
+            transfer_out(
+                &receive_ctx,
+                &escrow.receive,
+                escrow.bond_amount,
+                &bond_custody,
+                &Party {
+                    wallet: escrow_authority.to_account_info(),
+                    token_account: ctx.accounts.authority_receive_mint_ata.as_ref(),
+                    wrapped: escrow.authority_wrapped_sol,
+                },
+            )?;
+            forfeited_bond = escrow.bond_amount;
+        }
+        // the receiver paid for the bond vault
+        bond_rent = close_token_vault(&receive_ctx, &bond_custody, receiver.to_account_info())?;
     }
 
-    escrow.state = EscrowState::Completed;
-
-    emit!(EscrowCompleted {
-        escrow: escrow.key(),
-        escrow_authority: escrow_authority.key(),
-        receiver: receiver.key()
-    });
+    // Receiver pays the receive leg straight to the authority side or the
+    // split recipients, the deposit leg comes out of custody
+    let deposit_ctx = TransferContext {
+        mint: ctx.accounts.deposit_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: hook_accounts,
+    };
+    let deposit_custody = Custody {
+        sol_vault,
+        vault_seeds: vault_signer_seeds,
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.escrow_deposit_mint_ata.as_ref(),
+    };
+
+    let mut completed = release(
+        escrow,
+        &receive_ctx,
+        ReceiveLeg::Payer(Party {
+            wallet: receiver.to_account_info(),
+            token_account: ctx.accounts.receiver_receive_mint_ata.as_ref(),
+            wrapped,
+        }),
+        &deposit_ctx,
+        &deposit_custody,
+        Payout {
+            escrow_authority: escrow_authority.to_account_info(),
+            authority_receive_ata: ctx.accounts.authority_receive_mint_ata.as_ref(),
+            receiver: Party {
+                wallet: receiver.to_account_info(),
+                token_account: ctx.accounts.receiver_deposit_mint_ata.as_ref(),
+                wrapped,
+            },
+            referrer: ctx
+                .accounts
+                .referrer
+                .as_ref()
+                .map(|referrer| referrer.to_account_info()),
+            referrer_deposit_ata: ctx.accounts.referrer_deposit_mint_ata.as_deref(),
+            split,
+            recipients,
+            receipt,
+        },
+        EscrowState::Active,
+    )?;
+    completed.refunded_bond = refunded_bond;
+    completed.forfeited_bond = forfeited_bond;
+    completed.reclaimed_lamports += bond_rent;
+
+    emit_cpi!(completed);
 
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(escrow_id: String)]
 pub struct Accept<'info> {
@@ -168,29 +234,29 @@ pub struct Accept<'info> {
 
     #[account(
         mut,
-        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow_authority.key().as_ref(), receiver.key().as_ref()],
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), receiver.key().as_ref()],
         bump = escrow.bump,
         constraint = receiver.key() == escrow.receiver @ EscrowError::UnauthorizedSigner
     )]
     pub escrow: Account<'info, Escrow>,
 
-    /// CHECK: PDA holding SOL deposits
+    /// CHECK: PDA holding SOL deposits of legacy escrows
     #[account(mut, seeds = [SOL_VAULT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
-    pub sol_vault: UncheckedAccount<'info>,
+    pub sol_vault: Option<UncheckedAccount<'info>>,
 
-    /// CHECK: Mint of the token to transfer
-    #[account(mut)]
-    pub deposit_mint: InterfaceAccount<'info, Mint>,
+    // token accounts are only passed for the legs that move tokens, wSOL
+    // included; a plain SOL leg needs none of them
+    #[account(constraint = escrow.deposit.settles_with(&deposit_mint.key()) @ EscrowError::InvalidDepositMint)]
+    pub deposit_mint: Option<InterfaceAccount<'info, Mint>>,
 
-    //deposit mint token vault
+    // created at init, only for token deposits
     #[account(
-            init_if_needed,
-            payer = receiver,
+            mut,
             associated_token::mint = deposit_mint,
             associated_token::authority = escrow,
             associated_token::token_program = token_program
         )]
-    pub escrow_deposit_mint_ata: InterfaceAccount<'info, TokenAccount>,
+    pub escrow_deposit_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
             init_if_needed,
@@ -199,40 +265,73 @@ pub struct Accept<'info> {
             associated_token::authority = receiver,
             associated_token::token_program = token_program
         )]
-    pub receiver_deposit_mint_ata: InterfaceAccount<'info, TokenAccount>,
+    pub receiver_deposit_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(constraint = escrow.receive.settles_with(&receive_mint.key()) @ EscrowError::InvalidReceiveMint)]
+    pub receive_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    // the receiver pays from here, so it has to exist already
+    #[account(
+            mut,
+            associated_token::mint = receive_mint,
+            associated_token::authority = receiver,
+            associated_token::token_program = token_program
+        )]
+    pub receiver_receive_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
             init_if_needed,
             payer = receiver,
-            associated_token::mint = deposit_mint,
+            associated_token::mint = receive_mint,
             associated_token::authority = escrow_authority,
             associated_token::token_program = token_program
         )]
-    pub authority_deposit_mint_ata: InterfaceAccount<'info, TokenAccount>,
-
-    /// CHECK: Mint of the token to transfer
-    #[account(mut)]
-    pub receive_mint: InterfaceAccount<'info, Mint>,
+    pub authority_receive_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
 
+    // only for a committed escrow with a token bond
     #[account(
-            init_if_needed,
-            payer = receiver,
+            mut,
             associated_token::mint = receive_mint,
-            associated_token::authority = receiver,
+            associated_token::authority = escrow,
             associated_token::token_program = token_program
         )]
-    pub receiver_receive_mint_ata: InterfaceAccount<'info, TokenAccount>,
+    pub bond_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // only for escrows with a receipt: its mint and the holder's account
+    #[account(mut, seeds = [RECEIPT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    #[account(
+            mut,
+            token::mint = receipt_mint,
+            token::token_program = receipt_token_program
+        )]
+    pub receipt_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // only for escrows with a split receive leg, closed by release
+    #[account(
+            mut,
+            seeds = [SPLIT_SEED.as_bytes(), escrow.key().as_ref()],
+            bump = payout_split.bump
+        )]
+    pub payout_split: Option<Box<Account<'info, PayoutSplit>>>,
+
+    // only for escrows with a referral, the ata for a token deposit leg
+    /// CHECK: checked against the referrer stored at init
+    #[account(mut, address = escrow.referrer @ EscrowError::InvalidReferral)]
+    pub referrer: Option<UncheckedAccount<'info>>,
 
     #[account(
             init_if_needed,
             payer = receiver,
-            associated_token::mint = receive_mint,
-            associated_token::authority = escrow_authority,
+            associated_token::mint = deposit_mint,
+            associated_token::authority = referrer,
             associated_token::token_program = token_program
         )]
-    pub authority_receive_mint_ata: InterfaceAccount<'info, TokenAccount>,
+    pub referrer_deposit_mint_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-    pub token_program: Program<'info, Token2022>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
@@ -0,0 +1,312 @@
+use crate::{
+    errors::EscrowError,
+    events::EscrowCounterAccepted,
+    receipt::{check_authority_side, Receipt},
+    release::{release, Payout, ReceiveLeg},
+    state::{
+        Escrow, EscrowState, PayoutSplit, SolCustody, ESCROW_SEED, RECEIPT_SEED, SOL_VAULT_SEED,
+        SPLIT_SEED,
+    },
+    transfer::{split_recipients, Custody, Party, TransferContext},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_2022::Token2022,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+pub fn _accept_counter<'info>(
+    ctx: Context<'_, '_, 'info, 'info, AcceptCounter<'info>>,
+    escrow_id: String,
+    expected_amount: u64,
+) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    let escrow_authority = &ctx.accounts.escrow_authority;
+    let receiver = &ctx.accounts.receiver;
+    let system_program = &ctx.accounts.system_program;
+    let token_program = &ctx.accounts.token_program;
+
+    let vault_bump = ctx.bumps.sol_vault.unwrap_or_default();
+
+    let seed_authority = escrow.seed_authority;
+    let receiver_key = receiver.key();
+    let escrow_key = escrow.key();
+    let escrow_bump_ref = &[escrow.bump];
+
+    require!(
+        escrow.state == EscrowState::Active,
+        EscrowError::InvalidState
+    );
+    require!(escrow.counter_amount > 0, EscrowError::NoCounterOffer);
+    // the receiver can replace its counter at any time, only the one the
+    // authority side saw is accepted
+    require!(
+        escrow.counter_amount == expected_amount,
+        EscrowError::CounterChanged
+    );
+
+    let receipt = Receipt {
+        mint: ctx.accounts.receipt_mint.as_deref(),
+        token_account: ctx.accounts.receipt_account.as_deref(),
+        token_program: ctx.accounts.receipt_token_program.as_ref(),
+    };
+    check_authority_side(escrow, &escrow_authority.key(), &receipt)?;
+
+    // the counter terms become the escrow terms
+    let previous_receive_amount = escrow.receive_amount;
+    let clock = Clock::get()?;
+    escrow.receive_amount = escrow.counter_amount;
+    escrow.counter_amount = 0;
+
+    // An unfunded counter only changes the terms, the receiver accepts it
+    // like any other offer
+    if !escrow.counter_funded {
+        emit_cpi!(EscrowCounterAccepted {
+            escrow: escrow.key(),
+            escrow_id: escrow.escrow_id.clone(),
+            escrow_authority: escrow_authority.key(),
+            receiver: receiver.key(),
+            deposit: escrow.deposit,
+            receive: escrow.receive,
+            previous_receive_amount,
+            receive_amount: escrow.receive_amount,
+            settled: false,
+            timestamp: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        return Ok(());
+    }
+
+    escrow.counter_funded = false;
+
+    require!(
+        escrow.receive.is_native() || ctx.accounts.receive_mint.is_some(),
+        EscrowError::InvalidReceiveMint
+    );
+    require!(
+        escrow.deposit.is_native() || ctx.accounts.deposit_mint.is_some(),
+        EscrowError::InvalidDepositMint
+    );
+
+    // with a split the receive leg follows the table, its recipients lead the
+    // remaining accounts
+    let split = match escrow.has_split {
+        true => Some(
+            ctx.accounts
+                .payout_split
+                .as_deref()
+                .ok_or(EscrowError::MissingSplit)?,
+        ),
+        false => None,
+    };
+    let (recipients, hook_accounts) =
+        split_recipients(split.map(|split| &**split), ctx.remaining_accounts)?;
+
+    // get signer seeds
+    let escrow_seeds: &[&[u8]; 5] = &[
+        ESCROW_SEED.as_bytes(),
+        escrow_id.as_bytes(),
+        seed_authority.as_ref(),
+        receiver_key.as_ref(),
+        escrow_bump_ref,
+    ];
+    let signer_seeds = &[&escrow_seeds[..]];
+
+    // getvault signer seeds
+    let vault_seeds = &[
+        SOL_VAULT_SEED.as_bytes(),
+        escrow_key.as_ref(),
+        &[vault_bump],
+    ];
+    let vault_signer_seeds = &[&vault_seeds[..]];
+
+    // only SOL deposits of legacy escrows are still in the vault
+    let sol_vault = match escrow.sol_custody {
+        SolCustody::Vault if escrow.deposit.is_native() => Some(
+            ctx.accounts
+                .sol_vault
+                .as_ref()
+                .ok_or(EscrowError::MissingSolVault)?
+                .to_account_info(),
+        ),
+        _ => None,
+    };
+
+    // The receiver already put up the counter amount, both legs come out of
+    // custody
+    let receive_ctx = TransferContext {
+        mint: ctx.accounts.receive_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: hook_accounts,
+    };
+    let deposit_ctx = TransferContext {
+        mint: ctx.accounts.deposit_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: hook_accounts,
+    };
+    let deposit_custody = Custody {
+        sol_vault,
+        vault_seeds: vault_signer_seeds,
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.escrow_deposit_mint_ata.as_ref(),
+    };
+
+    let completed = release(
+        escrow,
+        &receive_ctx,
+        ReceiveLeg::Custody(Custody {
+            sol_vault: None,
+            vault_seeds: vault_signer_seeds,
+            escrow: escrow.to_account_info(),
+            escrow_seeds: signer_seeds,
+            token_account: ctx.accounts.receive_vault.as_ref(),
+        }),
+        &deposit_ctx,
+        &deposit_custody,
+        Payout {
+            escrow_authority: escrow_authority.to_account_info(),
+            authority_receive_ata: ctx.accounts.authority_receive_mint_ata.as_ref(),
+            receiver: Party {
+                wallet: receiver.to_account_info(),
+                token_account: ctx.accounts.receiver_deposit_mint_ata.as_ref(),
+                wrapped: escrow.receiver_wrapped_sol,
+            },
+            referrer: ctx
+                .accounts
+                .referrer
+                .as_ref()
+                .map(|referrer| referrer.to_account_info()),
+            referrer_deposit_ata: ctx.accounts.referrer_deposit_mint_ata.as_deref(),
+            split,
+            recipients,
+            receipt,
+        },
+        EscrowState::Active,
+    )?;
+
+    emit_cpi!(EscrowCounterAccepted {
+        escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
+        escrow_authority: escrow_authority.key(),
+        receiver: receiver.key(),
+        deposit: escrow.deposit,
+        receive: escrow.receive,
+        previous_receive_amount,
+        receive_amount: escrow.receive_amount,
+        settled: true,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+    emit_cpi!(completed);
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(escrow_id: String)]
+pub struct AcceptCounter<'info> {
+    #[account(mut)]
+    escrow_authority: Signer<'info>,
+
+    /// CHECK: account of the receiver
+    #[account(mut)]
+    receiver: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), receiver.key().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// CHECK: PDA holding SOL deposits of legacy escrows
+    #[account(mut, seeds = [SOL_VAULT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
+    pub sol_vault: Option<UncheckedAccount<'info>>,
+
+    // token accounts are only needed to settle a funded counter, and only for
+    // the legs that move tokens
+    #[account(constraint = escrow.deposit.settles_with(&deposit_mint.key()) @ EscrowError::InvalidDepositMint)]
+    pub deposit_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = deposit_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub escrow_deposit_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+            init_if_needed,
+            payer = escrow_authority,
+            associated_token::mint = deposit_mint,
+            associated_token::authority = receiver,
+            associated_token::token_program = token_program
+        )]
+    pub receiver_deposit_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(constraint = escrow.receive.settles_with(&receive_mint.key()) @ EscrowError::InvalidReceiveMint)]
+    pub receive_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub receive_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+            init_if_needed,
+            payer = escrow_authority,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow_authority,
+            associated_token::token_program = token_program
+        )]
+    pub authority_receive_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // only for escrows with a receipt: its mint and the holder's account
+    #[account(mut, seeds = [RECEIPT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    #[account(
+            mut,
+            token::mint = receipt_mint,
+            token::token_program = receipt_token_program
+        )]
+    pub receipt_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // only for escrows with a split receive leg, closed by release once a
+    // funded counter settles: an unfunded one leaves the escrow open
+    #[account(
+            mut,
+            seeds = [SPLIT_SEED.as_bytes(), escrow.key().as_ref()],
+            bump = payout_split.bump
+        )]
+    pub payout_split: Option<Box<Account<'info, PayoutSplit>>>,
+
+    // only for escrows with a referral, the ata for a token deposit leg
+    /// CHECK: checked against the referrer stored at init
+    #[account(mut, address = escrow.referrer @ EscrowError::InvalidReferral)]
+    pub referrer: Option<UncheckedAccount<'info>>,
+
+    #[account(
+            init_if_needed,
+            payer = escrow_authority,
+            associated_token::mint = deposit_mint,
+            associated_token::authority = referrer,
+            associated_token::token_program = token_program
+        )]
+    pub referrer_deposit_mint_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
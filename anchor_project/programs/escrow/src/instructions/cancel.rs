@@ -1,28 +1,35 @@
-use anchor_lang::prelude::{program::invoke_signed, system_instruction::transfer, *};
+use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_2022::{self, Token2022, TransferChecked},
-    token_interface::{Mint, TokenAccount},
+    token_2022::Token2022,
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
 use crate::{
     errors::EscrowError,
     events::EscrowClosed,
-    state::{Escrow, EscrowState, ESCROW_SEED, SOL_VAULT_SEED},
+    receipt::{burn_receipt, check_authority_side, Receipt},
+    state::{
+        Escrow, EscrowState, PayoutSplit, SolCustody, ESCROW_SEED, RECEIPT_SEED, SOL_VAULT_SEED,
+        SPLIT_SEED,
+    },
+    transfer::{close_token_vault, sweep_sol_vault, transfer_out, Custody, Party, TransferContext},
 };
 
-pub fn _close(ctx: Context<Close>, escrow_id: String) -> Result<()> {
+pub fn _close<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Close<'info>>,
+    escrow_id: String,
+) -> Result<()> {
     let escrow = &mut ctx.accounts.escrow;
-    let sol_vault = &mut ctx.accounts.sol_vault;
     let escrow_authority = &mut ctx.accounts.escrow_authority;
     let receiver = &mut ctx.accounts.receiver;
-    let system_program = &mut ctx.accounts.system_program;
-    let token_program = &mut ctx.accounts.token_program;
+    let system_program = &ctx.accounts.system_program;
+    let token_program = &ctx.accounts.token_program;
 
-    let vault_bump = ctx.bumps.sol_vault;
+    let vault_bump = ctx.bumps.sol_vault.unwrap_or_default();
 
     let escrow_id_bytes = escrow_id.as_bytes();
-    let escrow_authority_key = escrow_authority.key();
+    let seed_authority = escrow.seed_authority;
     let receiver_key = receiver.key();
     let escrow_key = escrow.key();
     let escrow_bump_ref = &[escrow.bump];
@@ -31,7 +38,7 @@ pub fn _close(ctx: Context<Close>, escrow_id: String) -> Result<()> {
     let escrow_seeds: &[&[u8]; 5] = &[
         ESCROW_SEED.as_bytes(),
         escrow_id_bytes,
-        escrow_authority_key.as_ref(),
+        seed_authority.as_ref(),
         receiver_key.as_ref(),
         escrow_bump_ref,
     ];
@@ -46,70 +53,198 @@ pub fn _close(ctx: Context<Close>, escrow_id: String) -> Result<()> {
     ];
     let vault_signer_seeds = &[&vault_seeds[..]];
 
-    let is_deposit_sol = escrow.deposit_mint == Pubkey::default();
-
-    match escrow.state {
-        EscrowState::Active => {
-            // Means its definitely funded
-            if is_deposit_sol {
-                // Transfer SOl (deposit amount) From the Escrow Vault to the Receiver
-                let transfer_ix = transfer(
-                    &sol_vault.key(),
-                    &escrow_authority.key(),
-                    escrow.deposit_amount,
-                );
+    let receipt = Receipt {
+        mint: ctx.accounts.receipt_mint.as_deref(),
+        token_account: ctx.accounts.receipt_account.as_deref(),
+        token_program: ctx.accounts.receipt_token_program.as_ref(),
+    };
+    // with a receipt its holder cancels and takes the refunds
+    check_authority_side(escrow, &escrow_authority.key(), &receipt)?;
+    // the split table closes along with the escrow
+    require!(
+        !escrow.has_split || ctx.accounts.payout_split.is_some(),
+        EscrowError::MissingSplit
+    );
+
+    // only SOL deposits of legacy escrows are still in the vault
+    let sol_vault = match escrow.sol_custody {
+        SolCustody::Vault if escrow.deposit.is_native() => Some(
+            ctx.accounts
+                .sol_vault
+                .as_ref()
+                .ok_or(EscrowError::MissingSolVault)?
+                .to_account_info(),
+        ),
+        _ => None,
+    };
+
+    let transfer_ctx = TransferContext {
+        mint: ctx.accounts.deposit_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    let custody = Custody {
+        sol_vault,
+        vault_seeds: vault_signer_seeds,
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.token_vault.as_ref(),
+    };
 
-                invoke_signed(
-                    &transfer_ix,
-                    &[
-                        sol_vault.to_account_info(),
-                        escrow_authority.to_account_info(),
-                        system_program.to_account_info(),
-                    ],
-                    vault_signer_seeds,
-                )?;
-            } else {
-                // Transfer Deposit Token Out from the Escrow Vault to the escrow Authority
-                let mint = ctx.accounts.deposit_mint.as_ref().unwrap();
-                let from_ata = ctx.accounts.token_vault.as_ref().unwrap();
-                let to_ata = ctx.accounts.authority_token_vault.as_ref().unwrap();
+    // escrow ata of the receive mint, holding a bond or a funded receive leg
+    let receive_ctx = TransferContext {
+        mint: ctx.accounts.receive_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    let receive_custody = Custody {
+        sol_vault: None,
+        vault_seeds: vault_signer_seeds,
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.receive_vault.as_ref(),
+    };
+
+    let previous_state = escrow.state;
+    let mut refunded_deposit = 0;
+    let mut refunded_receive = 0;
+    let mut forfeited_bond = 0;
+
+    if matches!(escrow.state, EscrowState::Active | EscrowState::Funded) {
+        if !escrow.deposit.is_native() {
+            require!(
+                ctx.accounts.deposit_mint.as_ref().map(|mint| mint.key()) == escrow.deposit.mint(),
+                EscrowError::InvalidDepositMint
+            );
+        }
 
+        // a funded counter-offer is held just like a funded receive leg
+        let held_receive_amount = match escrow.state {
+            EscrowState::Funded => escrow.receive_amount,
+            _ if escrow.counter_funded => escrow.counter_amount,
+            _ => 0,
+        };
+        let holds_receive_leg = held_receive_amount > 0;
+        let holds_bond = escrow.committed_until != 0;
+
+        if holds_receive_leg || holds_bond {
+            require!(
+                escrow.receive.is_native() || ctx.accounts.receive_vault.is_some(),
+                EscrowError::MissingBondVault
+            );
+        }
+
+        // The authority can still back out of a funded escrow during the
+        // challenge window or turn down a funded counter, the receiver gets
+        // their leg back. Not the other way round: funding is the receiver
+        // accepting the deal, the window is only for the authority to
+        // challenge it, so the receiver waits for settle
+        if holds_receive_leg {
+            if escrow.state == EscrowState::Funded {
                 require!(
-                    mint.key() == escrow.deposit_mint,
-                    EscrowError::InvalidDepositMint
+                    Clock::get()?.unix_timestamp < escrow.challenge_ends,
+                    EscrowError::ChallengeWindowOver
                 );
+            }
 
-                // Token tranfer
-                let cpi_accounts = TransferChecked {
-                    from: from_ata.to_account_info(),
-                    mint: mint.to_account_info(),
-                    to: to_ata.to_account_info(),
-                    authority: escrow.to_account_info(),
-                };
-
-                let transfer_ctx = CpiContext::new_with_signer(
-                    token_program.to_account_info(),
-                    cpi_accounts,
-                    signer_seeds,
-                );
+            transfer_out(
+                &receive_ctx,
+                &escrow.receive,
+                held_receive_amount,
+                &receive_custody,
+                &Party {
+                    wallet: receiver.to_account_info(),
+                    token_account: ctx.accounts.receiver_receive_token_vault.as_ref(),
+                    wrapped: escrow.receiver_wrapped_sol,
+                },
+            )?;
+            refunded_receive = held_receive_amount;
+        }
 
-                token_2022::transfer_checked(transfer_ctx, escrow.deposit_amount, mint.decimals)?;
-            }
+        // A committed offer stays locked until its window ends, after that the
+        // bond is forfeited to the authority
+        if holds_bond {
+            require!(
+                Clock::get()?.unix_timestamp >= escrow.committed_until,
+                EscrowError::OfferCommitted
+            );
+
+            transfer_out(
+                &receive_ctx,
+                &escrow.receive,
+                escrow.bond_amount,
+                &receive_custody,
+                &Party {
+                    wallet: escrow_authority.to_account_info(),
+                    token_account: ctx.accounts.authority_receive_token_vault.as_ref(),
+                    wrapped: escrow.authority_wrapped_sol,
+                },
+            )?;
+            forfeited_bond = escrow.bond_amount;
         }
-        _ => {}
+
+        // the receiver paid for the receive vault
+        if holds_receive_leg || holds_bond {
+            close_token_vault(&receive_ctx, &receive_custody, receiver.to_account_info())?;
+        }
+
+        // Refund the deposit leg to the escrow Authority the way it was deposited
+        transfer_out(
+            &transfer_ctx,
+            &escrow.deposit,
+            escrow.deposit_amount,
+            &custody,
+            &Party {
+                wallet: escrow_authority.to_account_info(),
+                token_account: ctx.accounts.authority_token_vault.as_ref(),
+                wrapped: escrow.authority_wrapped_sol,
+            },
+        )?;
+        refunded_deposit = escrow.deposit_amount;
     }
 
+    // the authority paid for every escrow account, hand all the rent back
+    let reclaimed_lamports =
+        sweep_sol_vault(&transfer_ctx, &custody, escrow_authority.to_account_info())?
+            + close_token_vault(&transfer_ctx, &custody, escrow_authority.to_account_info())?
+            + burn_receipt(
+                &receipt,
+                escrow,
+                signer_seeds,
+                escrow_authority.to_account_info(),
+            )?
+            + ctx
+                .accounts
+                .payout_split
+                .as_ref()
+                .map_or(0, |split| split.get_lamports())
+            + escrow.to_account_info().lamports();
+
     escrow.state = EscrowState::Closed;
 
-    emit!(EscrowClosed {
+    let clock = Clock::get()?;
+    emit_cpi!(EscrowClosed {
         escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
         escrow_authority: escrow_authority.key(),
-        receiver: receiver.key()
+        receiver: receiver.key(),
+        previous_state,
+        deposit: escrow.deposit,
+        refunded_deposit,
+        receive: escrow.receive,
+        refunded_receive,
+        forfeited_bond,
+        reclaimed_lamports,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
     });
 
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(escrow_id: String)]
 pub struct Close<'info> {
@@ -122,17 +257,16 @@ pub struct Close<'info> {
 
     #[account(
         mut,
-        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow_authority.key().as_ref(), receiver.key().as_ref()],
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), receiver.key().as_ref()],
         bump = escrow.bump,
-        constraint = escrow_authority.key() == escrow.escrow_authority @ EscrowError::UnauthorizedSigner,
         constraint = receiver.key() == escrow.receiver @ EscrowError::UnauthorizedSigner,
         close = escrow_authority
     )]
     pub escrow: Account<'info, Escrow>,
 
-    /// CHECK: PDA holding SOL deposits
+    /// CHECK: PDA holding SOL deposits of legacy escrows
     #[account(mut, seeds = [SOL_VAULT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
-    pub sol_vault: UncheckedAccount<'info>,
+    pub sol_vault: Option<UncheckedAccount<'info>>,
 
     /// CHECK: Mint of the token to transfer
     #[account(mut)]
@@ -157,7 +291,57 @@ pub struct Close<'info> {
         )]
     pub authority_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_program: Program<'info, Token2022>,
+    // only to settle a token bond or a funded token receive leg
+    #[account(constraint = escrow.receive.settles_with(&receive_mint.key()) @ EscrowError::InvalidReceiveMint)]
+    pub receive_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub receive_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+            init_if_needed,
+            payer = escrow_authority,
+            associated_token::mint = receive_mint,
+            associated_token::authority = receiver,
+            associated_token::token_program = token_program
+        )]
+    pub receiver_receive_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+            init_if_needed,
+            payer = escrow_authority,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow_authority,
+            associated_token::token_program = token_program
+        )]
+    pub authority_receive_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // only for escrows with a receipt: its mint and the holder's account
+    #[account(mut, seeds = [RECEIPT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    #[account(
+            mut,
+            token::mint = receipt_mint,
+            token::token_program = receipt_token_program
+        )]
+    pub receipt_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+            mut,
+            seeds = [SPLIT_SEED.as_bytes(), escrow.key().as_ref()],
+            bump = payout_split.bump,
+            close = escrow_authority
+        )]
+    pub payout_split: Option<Box<Account<'info, PayoutSplit>>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
@@ -0,0 +1,122 @@
+use crate::{
+    errors::EscrowError,
+    events::EscrowCommitted,
+    state::{Escrow, EscrowState, ESCROW_SEED},
+    transfer::{transfer_in, Party, TransferContext},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+pub fn _commit<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Commit<'info>>,
+    _escrow_id: String,
+    wrapped: bool,
+) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    let receiver = &ctx.accounts.receiver;
+
+    require!(
+        escrow.state == EscrowState::Active,
+        EscrowError::InvalidState
+    );
+    require!(escrow.bond_amount > 0, EscrowError::CommitNotOffered);
+    require!(escrow.committed_until == 0, EscrowError::AlreadyCommitted);
+    require!(!escrow.counter_funded, EscrowError::CounterPending);
+    require!(
+        escrow.receive.is_native() || ctx.accounts.receive_mint.is_some(),
+        EscrowError::InvalidReceiveMint
+    );
+
+    // the bond is paid in the receive asset and held by the escrow: as extra
+    // lamports for SOL, in the escrow ata of the receive mint for tokens
+    transfer_in(
+        &TransferContext {
+            mint: ctx.accounts.receive_mint.as_ref(),
+            token_program: &ctx.accounts.token_program,
+            system_program: &ctx.accounts.system_program,
+            remaining_accounts: ctx.remaining_accounts,
+        },
+        &escrow.receive,
+        escrow.bond_amount,
+        &Party {
+            wallet: receiver.to_account_info(),
+            token_account: ctx.accounts.receiver_receive_mint_ata.as_ref(),
+            wrapped,
+        },
+        &Party {
+            wallet: escrow.to_account_info(),
+            token_account: ctx.accounts.bond_vault.as_ref(),
+            wrapped: false,
+        },
+    )?;
+
+    let clock = Clock::get()?;
+    escrow.committed_until = clock
+        .unix_timestamp
+        .checked_add(escrow.commit_window)
+        .ok_or(EscrowError::OverFlow)?;
+
+    emit_cpi!(EscrowCommitted {
+        escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
+        escrow_authority: escrow.escrow_authority,
+        receiver: receiver.key(),
+        deposit: escrow.deposit,
+        receive: escrow.receive,
+        bond_amount: escrow.bond_amount,
+        committed_until: escrow.committed_until,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(escrow_id: String)]
+pub struct Commit<'info> {
+    #[account(mut)]
+    receiver: Signer<'info>,
+
+    /// CHECK: This is just the escrow authority account
+    escrow_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), receiver.key().as_ref()],
+        bump = escrow.bump,
+        constraint = escrow_authority.key() == escrow.escrow_authority @ EscrowError::UnauthorizedSigner,
+        constraint = receiver.key() == escrow.receiver @ EscrowError::UnauthorizedSigner
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // token accounts are only passed for a token or wSOL bond
+    #[account(constraint = escrow.receive.settles_with(&receive_mint.key()) @ EscrowError::InvalidReceiveMint)]
+    pub receive_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = receive_mint,
+            associated_token::authority = receiver,
+            associated_token::token_program = token_program
+        )]
+    pub receiver_receive_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // holds a token bond, its rent goes back to the receiver once settled
+    #[account(
+            init_if_needed,
+            payer = receiver,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub bond_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
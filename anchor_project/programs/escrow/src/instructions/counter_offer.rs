@@ -0,0 +1,171 @@
+use crate::{
+    errors::EscrowError,
+    events::EscrowCounterOffered,
+    state::{Escrow, EscrowState, ESCROW_SEED},
+    transfer::{close_token_vault, transfer_in, transfer_out, Custody, Party, TransferContext},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+pub fn _counter_offer<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CounterOffer<'info>>,
+    escrow_id: String,
+    receive_amount: u64,
+    fund: bool,
+    wrapped: bool,
+) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    let receiver = &ctx.accounts.receiver;
+
+    let seed_authority = escrow.seed_authority;
+    let receiver_key = receiver.key();
+    let escrow_bump_ref = &[escrow.bump];
+
+    require!(
+        escrow.state == EscrowState::Active,
+        EscrowError::InvalidState
+    );
+    require!(receive_amount > 0, EscrowError::ReceiveAmountLow);
+    // a bond already holds the stored terms for the receiver
+    require!(escrow.committed_until == 0, EscrowError::OfferCommitted);
+    if fund || escrow.counter_funded {
+        require!(
+            escrow.receive.is_native() || ctx.accounts.receive_mint.is_some(),
+            EscrowError::InvalidReceiveMint
+        );
+    }
+
+    // get signer seeds
+    let escrow_seeds: &[&[u8]; 5] = &[
+        ESCROW_SEED.as_bytes(),
+        escrow_id.as_bytes(),
+        seed_authority.as_ref(),
+        receiver_key.as_ref(),
+        escrow_bump_ref,
+    ];
+    let signer_seeds = &[&escrow_seeds[..]];
+
+    let receive_ctx = TransferContext {
+        mint: ctx.accounts.receive_mint.as_ref(),
+        token_program: &ctx.accounts.token_program,
+        system_program: &ctx.accounts.system_program,
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    let custody = Custody {
+        sol_vault: None,
+        vault_seeds: &[],
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.receive_vault.as_ref(),
+    };
+
+    // A new counter replaces the last one, give back whatever backed it
+    let mut refunded_amount = 0;
+    if escrow.counter_funded {
+        transfer_out(
+            &receive_ctx,
+            &escrow.receive,
+            escrow.counter_amount,
+            &custody,
+            &Party {
+                wallet: receiver.to_account_info(),
+                token_account: ctx.accounts.receiver_receive_mint_ata.as_ref(),
+                wrapped: escrow.receiver_wrapped_sol,
+            },
+        )?;
+        if !fund {
+            close_token_vault(&receive_ctx, &custody, receiver.to_account_info())?;
+        }
+        refunded_amount = escrow.counter_amount;
+    }
+
+    // Backing the counter with the receive leg lets the authority settle it
+    // in one go
+    if fund {
+        transfer_in(
+            &receive_ctx,
+            &escrow.receive,
+            receive_amount,
+            &Party {
+                wallet: receiver.to_account_info(),
+                token_account: ctx.accounts.receiver_receive_mint_ata.as_ref(),
+                wrapped,
+            },
+            &Party {
+                wallet: escrow.to_account_info(),
+                token_account: ctx.accounts.receive_vault.as_ref(),
+                wrapped: false,
+            },
+        )?;
+        escrow.receiver_wrapped_sol = wrapped;
+    }
+
+    escrow.counter_amount = receive_amount;
+    escrow.counter_funded = fund;
+
+    let clock = Clock::get()?;
+    emit_cpi!(EscrowCounterOffered {
+        escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
+        escrow_authority: escrow.escrow_authority,
+        receiver: receiver.key(),
+        deposit: escrow.deposit,
+        receive: escrow.receive,
+        receive_amount,
+        funded: fund,
+        refunded_amount,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(escrow_id: String)]
+pub struct CounterOffer<'info> {
+    #[account(mut)]
+    receiver: Signer<'info>,
+
+    /// CHECK: This is just the escrow authority account
+    escrow_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), receiver.key().as_ref()],
+        bump = escrow.bump,
+        constraint = escrow_authority.key() == escrow.escrow_authority @ EscrowError::UnauthorizedSigner,
+        constraint = receiver.key() == escrow.receiver @ EscrowError::UnauthorizedSigner
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // token accounts are only needed to fund a token or wSOL counter, or to
+    // get a funded one back
+    #[account(constraint = escrow.receive.settles_with(&receive_mint.key()) @ EscrowError::InvalidReceiveMint)]
+    pub receive_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = receive_mint,
+            associated_token::authority = receiver,
+            associated_token::token_program = token_program
+        )]
+    pub receiver_receive_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+            init_if_needed,
+            payer = receiver,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub receive_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
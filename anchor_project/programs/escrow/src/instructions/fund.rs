@@ -0,0 +1,165 @@
+use crate::{
+    errors::EscrowError,
+    events::EscrowFunded,
+    state::{Escrow, EscrowState, ESCROW_SEED},
+    transfer::{transfer_in, transfer_out, Custody, Party, TransferContext},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+pub fn _fund<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Fund<'info>>,
+    escrow_id: String,
+    wrapped: bool,
+) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    let receiver = &ctx.accounts.receiver;
+
+    let seed_authority = escrow.seed_authority;
+    let receiver_key = receiver.key();
+    let escrow_bump_ref = &[escrow.bump];
+
+    require!(
+        escrow.state == EscrowState::Active,
+        EscrowError::InvalidState
+    );
+    require!(!escrow.counter_funded, EscrowError::CounterPending);
+    require!(
+        escrow.receive.is_native() || ctx.accounts.receive_mint.is_some(),
+        EscrowError::InvalidReceiveMint
+    );
+
+    // get signer seeds
+    let escrow_seeds: &[&[u8]; 5] = &[
+        ESCROW_SEED.as_bytes(),
+        escrow_id.as_bytes(),
+        seed_authority.as_ref(),
+        receiver_key.as_ref(),
+        escrow_bump_ref,
+    ];
+    let signer_seeds = &[&escrow_seeds[..]];
+    let clock = Clock::get()?;
+
+    let receive_ctx = TransferContext {
+        mint: ctx.accounts.receive_mint.as_ref(),
+        token_program: &ctx.accounts.token_program,
+        system_program: &ctx.accounts.system_program,
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    let custody = Custody {
+        sol_vault: None,
+        vault_seeds: &[],
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.receive_vault.as_ref(),
+    };
+
+    // Funding within the window is as good as a commit, the bond goes back
+    // before the receive leg lands in the same vault. After it the bond stays
+    // in custody, forfeited to the authority side on settle or close
+    let mut refunded_bond = 0;
+    if escrow.committed_until != 0 && clock.unix_timestamp < escrow.committed_until {
+        transfer_out(
+            &receive_ctx,
+            &escrow.receive,
+            escrow.bond_amount,
+            &custody,
+            &Party {
+                wallet: receiver.to_account_info(),
+                token_account: ctx.accounts.receiver_receive_mint_ata.as_ref(),
+                wrapped,
+            },
+        )?;
+        escrow.committed_until = 0;
+        refunded_bond = escrow.bond_amount;
+    }
+
+    // Receive leg goes into custody until settle
+    transfer_in(
+        &receive_ctx,
+        &escrow.receive,
+        escrow.receive_amount,
+        &Party {
+            wallet: receiver.to_account_info(),
+            token_account: ctx.accounts.receiver_receive_mint_ata.as_ref(),
+            wrapped,
+        },
+        &Party {
+            wallet: escrow.to_account_info(),
+            token_account: ctx.accounts.receive_vault.as_ref(),
+            wrapped: false,
+        },
+    )?;
+
+    escrow.receiver_wrapped_sol = wrapped;
+    escrow.challenge_ends = clock
+        .unix_timestamp
+        .checked_add(escrow.challenge_window)
+        .ok_or(EscrowError::OverFlow)?;
+    escrow.state = EscrowState::Funded;
+
+    emit_cpi!(EscrowFunded {
+        escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
+        escrow_authority: escrow.escrow_authority,
+        receiver: receiver.key(),
+        deposit: escrow.deposit,
+        receive: escrow.receive,
+        receive_amount: escrow.receive_amount,
+        refunded_bond,
+        challenge_ends: escrow.challenge_ends,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(escrow_id: String)]
+pub struct Fund<'info> {
+    #[account(mut)]
+    receiver: Signer<'info>,
+
+    /// CHECK: This is just the escrow authority account
+    escrow_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), receiver.key().as_ref()],
+        bump = escrow.bump,
+        constraint = escrow_authority.key() == escrow.escrow_authority @ EscrowError::UnauthorizedSigner,
+        constraint = receiver.key() == escrow.receiver @ EscrowError::UnauthorizedSigner
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // token accounts are only passed for a token or wSOL receive leg
+    #[account(constraint = escrow.receive.settles_with(&receive_mint.key()) @ EscrowError::InvalidReceiveMint)]
+    pub receive_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = receive_mint,
+            associated_token::authority = receiver,
+            associated_token::token_program = token_program
+        )]
+    pub receiver_receive_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // escrow ata of the receive mint, shared with a commit bond
+    #[account(
+            init_if_needed,
+            payer = receiver,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub receive_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
@@ -0,0 +1,56 @@
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+
+use crate::{
+    errors::EscrowError,
+    events::ConfigUpdated,
+    state::{Config, BPS_DENOMINATOR, CONFIG_SEED},
+};
+
+pub fn _init_config(ctx: Context<InitConfig>, max_referral_bps: u16) -> Result<()> {
+    require!(
+        max_referral_bps as u64 <= BPS_DENOMINATOR,
+        EscrowError::InvalidConfig
+    );
+
+    let config = &mut ctx.accounts.config;
+    config.admin = ctx.accounts.admin.key();
+    config.max_referral_bps = max_referral_bps;
+    config.bump = ctx.bumps.config;
+
+    let clock = Clock::get()?;
+    emit_cpi!(ConfigUpdated {
+        admin: config.admin,
+        max_referral_bps,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    // only the upgrade authority gets to set the config up
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ EscrowError::UnauthorizedSigner
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
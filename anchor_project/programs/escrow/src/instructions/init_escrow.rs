@@ -1,37 +1,40 @@
-use anchor_lang::prelude::{program::invoke_signed, *};
+use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_2022::{transfer_checked, Token2022, TransferChecked},
-    token_interface::{Mint, TokenAccount},
+    token_2022::Token2022,
+    token_interface::{self, Mint, MintTo, TokenAccount, TokenInterface},
 };
 
 use crate::{
     errors::EscrowError,
     events::EscrowCreated,
-    state::{Escrow, EscrowState, ESCROW_ID_LENGTH, ESCROW_SEED, SOL_VAULT_SEED},
+    state::{
+        Asset, Config, Escrow, EscrowOptions, EscrowState, PayoutSplit, SolCustody, CONFIG_SEED,
+        DEFAULT_CHALLENGE_WINDOW, ESCROW_ID_LENGTH, ESCROW_SEED, ESCROW_VERSION, RECEIPT_SEED,
+        SPLIT_SEED,
+    },
+    transfer::{transfer_in, Party, TransferContext},
 };
 
-pub fn _init_escrow(
-    ctx: Context<InitializeEscrow>,
+pub fn _init_escrow<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitializeEscrow<'info>>,
     escrow_id: String,
-    deposit_mint: Pubkey,
+    deposit: Asset,
     deposit_amount: u64,
-    receive_mint: Pubkey,
+    receive: Asset,
     receive_amount: u64,
+    options: EscrowOptions,
 ) -> Result<()> {
     let escrow = &mut ctx.accounts.escrow;
-    let sol_vault = &mut ctx.accounts.sol_vault;
     let escrow_authority = &mut ctx.accounts.escrow_authority;
     let receiver = &mut ctx.accounts.receiver;
-    let system_program = &mut ctx.accounts.system_program;
-    let token_program = &mut ctx.accounts.token_program;
+    let token_program = &ctx.accounts.token_program;
 
     let mint = ctx.accounts.deposit_mint.as_ref();
     let authority_token_vault = ctx.accounts.authority_token_vault.as_ref();
     let escrow_token_vault = ctx.accounts.token_vault.as_ref();
 
     let bump = ctx.bumps.escrow;
-    let vault_bump = ctx.bumps.sol_vault;
 
     require!(
         escrow_authority.key() != receiver.key(),
@@ -39,105 +42,208 @@ pub fn _init_escrow(
     );
     require!(deposit_amount > 0, EscrowError::DepositAmountLow);
     require!(receive_amount > 0, EscrowError::ReceiveAmountLow);
-    require!(
-        deposit_mint != receive_mint,
-        EscrowError::SameTokenTransferNotAllowed
-    );
 
-    let is_deposit_sol = deposit_mint == Pubkey::default();
+    // native SOL and wSOL are the same asset, a wSOL mint means the authority
+    // deals in wSOL for its SOL leg
+    let (deposit, deposit_wrapped) = deposit.canonicalize();
+    let (receive, receive_wrapped) = receive.canonicalize();
+
+    require!(deposit != receive, EscrowError::SameTokenTransferNotAllowed);
+
+    for asset in [&deposit, &receive] {
+        if let Asset::SplToken { program, .. } = asset {
+            require!(
+                *program == anchor_spl::token::ID,
+                EscrowError::InvalidTokenProgram
+            );
+        }
+    }
+
+    // every later instruction takes a single token program for both legs, an
+    // escrow with one leg on each could never be accepted or closed
+    if let (Some(deposit_program), Some(receive_program)) =
+        (deposit.token_program(), receive.token_program())
+    {
+        require_keys_eq!(
+            deposit_program,
+            receive_program,
+            EscrowError::InvalidTokenProgram
+        );
+    }
 
     // Update the escrow State
+    escrow.version = ESCROW_VERSION;
     escrow.bump = bump;
     escrow.receiver = receiver.key();
+    escrow.deposit = deposit;
     escrow.deposit_amount = deposit_amount;
-    escrow.deposit_mint = deposit_mint;
+    escrow.receive = receive;
     escrow.receive_amount = receive_amount;
-    escrow.receive_mint = receive_mint;
+    escrow.authority_wrapped_sol = deposit_wrapped || receive_wrapped;
     escrow.escrow_authority = escrow_authority.key();
+    escrow.seed_authority = escrow_authority.key();
     escrow.escrow_id = escrow_id;
+    escrow.sol_custody = SolCustody::Escrow;
 
-    // DO SOl Transfer to the sol vault
-    // create vault only if the deposit is sol
-    if is_deposit_sol {
-        // create a sol vault
-        let rent = Rent::get()?;
-        let space = 0;
-        let lamports = rent
-            .minimum_balance(space)
-            .checked_add(deposit_amount)
-            .unwrap();
-
-        let create_ix = system_instruction::create_account(
-            &escrow_authority.key(),
-            &sol_vault.key(),
-            lamports,
-            space as u64,
-            &system_program.key(),
+    if let Some(bond) = options.bond {
+        require!(
+            bond.amount > 0 && bond.window > 0,
+            EscrowError::InvalidBondTerms
         );
 
-        invoke_signed(
-            &create_ix,
-            &[
-                escrow_authority.to_account_info(),
-                sol_vault.to_account_info(),
-                system_program.to_account_info(),
-            ],
-            &[&[
-                SOL_VAULT_SEED.as_bytes(),
-                escrow.key().as_ref(),
-                &[vault_bump],
-            ]],
-        )?;
-    } else {
-        // Transfer token to the escrow ata
-        // fund the escrow ata with token
-        // check if the mint address matches what was agreed on
-        let mint = mint.unwrap();
-        let from_ata = authority_token_vault.unwrap();
-        let to_ata = escrow_token_vault.unwrap();
+        escrow.bond_amount = bond.amount;
+        escrow.commit_window = bond.window;
+    }
+
+    escrow.challenge_window = options.challenge_window.unwrap_or(DEFAULT_CHALLENGE_WINDOW);
+    require!(
+        escrow.challenge_window >= 0,
+        EscrowError::InvalidChallengeWindow
+    );
+
+    if let Some(referral) = options.referral {
+        let config = ctx
+            .accounts
+            .config
+            .as_ref()
+            .ok_or(EscrowError::MissingConfig)?;
 
+        require!(referral.bps > 0, EscrowError::InvalidReferral);
         require!(
-            mint.key() == escrow.deposit_mint,
-            EscrowError::InvalidDepositMint
+            referral.bps <= config.max_referral_bps,
+            EscrowError::ReferralTooHigh
+        );
+        // the referrer takes its cut from the receiver's side of the deal,
+        // neither side can refer itself into it
+        require!(
+            referral.referrer != receiver.key(),
+            EscrowError::InvalidReferral
+        );
+        require!(
+            referral.referrer != escrow_authority.key(),
+            EscrowError::InvalidReferral
         );
 
-        // check if the buyer has sufficient balance
+        escrow.referrer = referral.referrer;
+        escrow.referral_bps = referral.bps;
+
+        // a native fee is paid as bare lamports, to a referrer without an
+        // account yet anything under the rent would fail every accept and
+        // settle
+        if escrow.deposit.is_native() {
+            require!(
+                escrow.referral_fee()? >= Rent::get()?.minimum_balance(0),
+                EscrowError::ReferralBelowRent
+            );
+        }
+    }
+
+    // the split table lives in its own account, sized for the most recipients
+    match (options.split, ctx.accounts.payout_split.as_mut()) {
+        (Some(shares), Some(payout_split)) => {
+            PayoutSplit::validate(&shares)?;
+
+            payout_split.escrow = escrow.key();
+            payout_split.shares = shares;
+            payout_split.bump = ctx.bumps.payout_split.unwrap_or_default();
+            escrow.has_split = true;
+        }
+        (Some(_), None) => return err!(EscrowError::MissingSplit),
+        (None, Some(_)) => return err!(EscrowError::InvalidSplit),
+        (None, None) => {}
+    }
+
+    // check if the mint address matches what was agreed on
+    if !deposit.is_native() {
         require!(
-            from_ata.amount > escrow.deposit_amount,
-            EscrowError::InsufficientBalance
+            mint.map(|mint| mint.key()) == deposit.mint(),
+            EscrowError::InvalidDepositMint
         );
+    }
 
+    if let Some(to_ata) = escrow_token_vault {
         // check for overflow
         to_ata
             .amount
-            .checked_add(escrow.deposit_amount)
+            .checked_add(deposit_amount)
             .ok_or(EscrowError::OverFlow)?;
+    }
 
-        let transfer_ctx = CpiContext::new(
-            token_program.to_account_info(),
-            TransferChecked {
-                authority: escrow_authority.to_account_info(),
-                mint: mint.to_account_info(),
-                to: to_ata.to_account_info(),
-                from: from_ata.to_account_info(),
-            },
-        );
+    transfer_in(
+        &TransferContext {
+            mint,
+            token_program,
+            system_program: &ctx.accounts.system_program,
+            remaining_accounts: ctx.remaining_accounts,
+        },
+        &deposit,
+        deposit_amount,
+        &Party {
+            wallet: escrow_authority.to_account_info(),
+            token_account: authority_token_vault,
+            wrapped: deposit_wrapped,
+        },
+        // SOL deposits sit on the escrow account on top of its rent, tokens
+        // in the escrow ata
+        &Party {
+            wallet: escrow.to_account_info(),
+            token_account: escrow_token_vault,
+            wrapped: false,
+        },
+    )?;
 
-        transfer_checked(transfer_ctx, escrow.deposit_amount, mint.decimals)?;
-    }
+    // Mint the receipt for the authority side, the escrow stays its only
+    // mint authority so supply never goes past 1
+    let escrow_authority_key = escrow_authority.key();
+    let receiver_key = receiver.key();
+    let escrow_seeds: &[&[u8]; 5] = &[
+        ESCROW_SEED.as_bytes(),
+        escrow.escrow_id.as_bytes(),
+        escrow_authority_key.as_ref(),
+        receiver_key.as_ref(),
+        &[bump],
+    ];
+
+    token_interface::mint_to(
+        CpiContext::new_with_signer(
+            ctx.accounts.receipt_token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.receipt_mint.to_account_info(),
+                to: ctx.accounts.authority_receipt_ata.to_account_info(),
+                authority: escrow.to_account_info(),
+            },
+            &[&escrow_seeds[..]],
+        ),
+        1,
+    )?;
+    escrow.has_receipt = true;
 
     // Update the escrow state
     escrow.state = EscrowState::Active;
 
-    emit!(EscrowCreated {
+    let clock = Clock::get()?;
+    emit_cpi!(EscrowCreated {
         escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
         escrow_authority: escrow_authority.key(),
         receiver: receiver.key(),
+        deposit: escrow.deposit,
+        deposit_amount,
+        receive: escrow.receive,
+        receive_amount,
+        bond_amount: escrow.bond_amount,
+        challenge_window: escrow.challenge_window,
+        has_split: escrow.has_split,
+        referrer: escrow.referrer(),
+        referral_bps: escrow.referral_bps,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
     });
 
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(escrow_id: String)]
 pub struct InitializeEscrow<'info> {
@@ -154,15 +260,11 @@ pub struct InitializeEscrow<'info> {
         space = 8 + Escrow::INIT_SPACE,
         seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow_authority.key().as_ref(), receiver.key().as_ref()],
         bump,
-        constraint = escrow_id.len() > 0 @ EscrowError::IdTooShort,
+        constraint = !escrow_id.is_empty() @ EscrowError::IdTooShort,
         constraint = escrow_id.len() <= ESCROW_ID_LENGTH @ EscrowError::IdTooLong
     )]
     pub escrow: Account<'info, Escrow>,
 
-    /// CHECK: PDA holding SOL deposits
-    #[account(mut, seeds = [SOL_VAULT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
-    pub sol_vault: UncheckedAccount<'info>,
-
     /// CHECK: Mint of the token to transfer
     #[account(mut)]
     pub deposit_mint: Option<InterfaceAccount<'info, Mint>>,
@@ -186,7 +288,49 @@ pub struct InitializeEscrow<'info> {
         )]
     pub authority_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_program: Program<'info, Token2022>,
+    // Non-fungible claim on the authority side. The escrow can burn it from
+    // any holder and close the mint once the escrow is done
+    #[account(
+            init,
+            payer = escrow_authority,
+            seeds = [RECEIPT_SEED.as_bytes(), escrow.key().as_ref()],
+            bump,
+            mint::decimals = 0,
+            mint::authority = escrow,
+            mint::token_program = receipt_token_program,
+            extensions::permanent_delegate::delegate = escrow,
+            extensions::close_authority::authority = escrow
+        )]
+    pub receipt_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // can be left over, empty, when the id was used before: the receipt is
+    // burnt when the escrow ends, but only a signing holder gets its account
+    // closed along with it
+    #[account(
+            init_if_needed,
+            payer = escrow_authority,
+            associated_token::mint = receipt_mint,
+            associated_token::authority = escrow_authority,
+            associated_token::token_program = receipt_token_program
+        )]
+    pub authority_receipt_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // only when the receive leg is split
+    #[account(
+            init,
+            payer = escrow_authority,
+            space = 8 + PayoutSplit::INIT_SPACE,
+            seeds = [SPLIT_SEED.as_bytes(), escrow.key().as_ref()],
+            bump
+        )]
+    pub payout_split: Option<Box<Account<'info, PayoutSplit>>>,
+
+    // only read to cap a referral
+    #[account(seeds = [CONFIG_SEED.as_bytes()], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub receipt_token_program: Program<'info, Token2022>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
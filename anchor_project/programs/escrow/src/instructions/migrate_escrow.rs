@@ -0,0 +1,103 @@
+use anchor_lang::{
+    prelude::{program::invoke, system_instruction::transfer, *},
+    Discriminator,
+};
+
+use crate::{
+    errors::EscrowError,
+    events::EscrowMigrated,
+    state::{legacy, Escrow, ESCROW_VERSION},
+};
+
+// The legacy layout is the only one to upgrade from. An account the size of
+// the current layout is either up to date or from a version this program
+// doesn't know
+fn upgrade(data: &[u8]) -> Result<Escrow> {
+    if data.len() == 8 + Escrow::INIT_SPACE {
+        return match data[8] {
+            ESCROW_VERSION => err!(EscrowError::AlreadyMigrated),
+            _ => err!(EscrowError::UnknownVersion),
+        };
+    }
+
+    require!(
+        data.len() == 8 + legacy::SentinelEscrow::INIT_SPACE,
+        EscrowError::UnknownVersion
+    );
+    legacy::decode(data)
+}
+
+pub fn _migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+    let escrow = &ctx.accounts.escrow;
+    let payer = &ctx.accounts.payer;
+    let system_program = &ctx.accounts.system_program;
+
+    let migrated = {
+        let data = escrow.try_borrow_data()?;
+
+        require!(
+            data.len() >= 8 && data[..8] == *Escrow::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+
+        upgrade(&data)?
+    };
+
+    // top up rent for the newest layout, the legacy account holds its SOL
+    // deposit in the sol vault and only rent for its own size
+    let space = 8 + Escrow::INIT_SPACE;
+    let rent = Rent::get()?;
+    let lamports = rent
+        .minimum_balance(space)
+        .saturating_sub(escrow.lamports());
+
+    if lamports > 0 {
+        invoke(
+            &transfer(&payer.key(), &escrow.key(), lamports),
+            &[
+                payer.to_account_info(),
+                escrow.to_account_info(),
+                system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    escrow.resize(space)?;
+
+    let mut data = escrow.try_borrow_mut_data()?;
+    data.fill(0);
+    migrated.try_serialize(&mut &mut data[..])?;
+
+    msg!("Migrated escrow {}", migrated.escrow_id);
+
+    let clock = Clock::get()?;
+    emit_cpi!(EscrowMigrated {
+        escrow: escrow.key(),
+        escrow_id: migrated.escrow_id.clone(),
+        escrow_authority: migrated.escrow_authority,
+        receiver: migrated.receiver,
+        deposit: migrated.deposit,
+        receive: migrated.receive,
+        // the legacy layout has no version byte
+        from_version: 0,
+        to_version: ESCROW_VERSION,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MigrateEscrow<'info> {
+    // anyone can pay to bring an escrow to the current layout
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: old layout, decoded by hand after the owner check
+    #[account(mut, owner = crate::ID)]
+    pub escrow: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -6,3 +6,30 @@ pub use cancel::*;
 
 pub mod accept;
 pub use accept::*;
+
+pub mod commit;
+pub use commit::*;
+
+pub mod fund;
+pub use fund::*;
+
+pub mod settle;
+pub use settle::*;
+
+pub mod counter_offer;
+pub use counter_offer::*;
+
+pub mod accept_counter;
+pub use accept_counter::*;
+
+pub mod transfer_authority;
+pub use transfer_authority::*;
+
+pub mod migrate_escrow;
+pub use migrate_escrow::*;
+
+pub mod init_config;
+pub use init_config::*;
+
+pub mod update_config;
+pub use update_config::*;
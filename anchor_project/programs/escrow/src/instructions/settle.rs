@@ -0,0 +1,293 @@
+use crate::{
+    errors::EscrowError,
+    receipt::{check_authority_side, Receipt},
+    release::{release, Payout, ReceiveLeg},
+    state::{
+        Escrow, EscrowState, PayoutSplit, SolCustody, ESCROW_SEED, RECEIPT_SEED, SOL_VAULT_SEED,
+        SPLIT_SEED,
+    },
+    transfer::{split_recipients, transfer_out, Custody, Party, TransferContext},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_2022::Token2022,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+pub fn _settle<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Settle<'info>>,
+    escrow_id: String,
+) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    let escrow_authority = &ctx.accounts.escrow_authority;
+    let receiver = &ctx.accounts.receiver;
+    let system_program = &ctx.accounts.system_program;
+    let token_program = &ctx.accounts.token_program;
+
+    let vault_bump = ctx.bumps.sol_vault.unwrap_or_default();
+
+    let seed_authority = escrow.seed_authority;
+    let receiver_key = receiver.key();
+    let escrow_key = escrow.key();
+    let escrow_bump_ref = &[escrow.bump];
+
+    require!(
+        escrow.state == EscrowState::Funded,
+        EscrowError::InvalidState
+    );
+    require!(
+        Clock::get()?.unix_timestamp >= escrow.challenge_ends,
+        EscrowError::ChallengeWindowOpen
+    );
+
+    let receipt = Receipt {
+        mint: ctx.accounts.receipt_mint.as_deref(),
+        token_account: ctx.accounts.receipt_account.as_deref(),
+        token_program: ctx.accounts.receipt_token_program.as_ref(),
+    };
+    check_authority_side(escrow, &escrow_authority.key(), &receipt)?;
+    require!(
+        escrow.receive.is_native() || ctx.accounts.receive_mint.is_some(),
+        EscrowError::InvalidReceiveMint
+    );
+    require!(
+        escrow.deposit.is_native() || ctx.accounts.deposit_mint.is_some(),
+        EscrowError::InvalidDepositMint
+    );
+
+    // with a split the receive leg follows the table, its recipients lead the
+    // remaining accounts
+    let split = match escrow.has_split {
+        true => Some(
+            ctx.accounts
+                .payout_split
+                .as_deref()
+                .ok_or(EscrowError::MissingSplit)?,
+        ),
+        false => None,
+    };
+    let (recipients, hook_accounts) =
+        split_recipients(split.map(|split| &**split), ctx.remaining_accounts)?;
+
+    // get signer seeds
+    let escrow_seeds: &[&[u8]; 5] = &[
+        ESCROW_SEED.as_bytes(),
+        escrow_id.as_bytes(),
+        seed_authority.as_ref(),
+        receiver_key.as_ref(),
+        escrow_bump_ref,
+    ];
+    let signer_seeds = &[&escrow_seeds[..]];
+
+    // getvault signer seeds
+    let vault_seeds = &[
+        SOL_VAULT_SEED.as_bytes(),
+        escrow_key.as_ref(),
+        &[vault_bump],
+    ];
+    let vault_signer_seeds = &[&vault_seeds[..]];
+
+    // only SOL deposits of legacy escrows are still in the vault
+    let sol_vault = match escrow.sol_custody {
+        SolCustody::Vault if escrow.deposit.is_native() => Some(
+            ctx.accounts
+                .sol_vault
+                .as_ref()
+                .ok_or(EscrowError::MissingSolVault)?
+                .to_account_info(),
+        ),
+        _ => None,
+    };
+
+    // Release both legs out of custody, the receive leg to the authority side
+    // or the split recipients
+    let receive_ctx = TransferContext {
+        mint: ctx.accounts.receive_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: hook_accounts,
+    };
+    let deposit_ctx = TransferContext {
+        mint: ctx.accounts.deposit_mint.as_ref(),
+        token_program,
+        system_program,
+        remaining_accounts: hook_accounts,
+    };
+    let deposit_custody = Custody {
+        sol_vault,
+        vault_seeds: vault_signer_seeds,
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.escrow_deposit_mint_ata.as_ref(),
+    };
+
+    let receive_custody = Custody {
+        sol_vault: None,
+        vault_seeds: vault_signer_seeds,
+        escrow: escrow.to_account_info(),
+        escrow_seeds: signer_seeds,
+        token_account: ctx.accounts.receive_vault.as_ref(),
+    };
+
+    // A bond still held was funded over after its window, it is forfeited to
+    // the authority side
+    let mut forfeited_bond = 0;
+    if escrow.committed_until != 0 {
+        transfer_out(
+            &receive_ctx,
+            &escrow.receive,
+            escrow.bond_amount,
+            &receive_custody,
+            &Party {
+                wallet: escrow_authority.to_account_info(),
+                token_account: ctx.accounts.authority_receive_mint_ata.as_ref(),
+                wrapped: escrow.authority_wrapped_sol,
+            },
+        )?;
+        escrow.committed_until = 0;
+        forfeited_bond = escrow.bond_amount;
+    }
+
+    let mut completed = release(
+        escrow,
+        &receive_ctx,
+        ReceiveLeg::Custody(receive_custody),
+        &deposit_ctx,
+        &deposit_custody,
+        Payout {
+            escrow_authority: escrow_authority.to_account_info(),
+            authority_receive_ata: ctx.accounts.authority_receive_mint_ata.as_ref(),
+            receiver: Party {
+                wallet: receiver.to_account_info(),
+                token_account: ctx.accounts.receiver_deposit_mint_ata.as_ref(),
+                wrapped: escrow.receiver_wrapped_sol,
+            },
+            referrer: ctx
+                .accounts
+                .referrer
+                .as_ref()
+                .map(|referrer| referrer.to_account_info()),
+            referrer_deposit_ata: ctx.accounts.referrer_deposit_mint_ata.as_deref(),
+            split,
+            recipients,
+            receipt,
+        },
+        EscrowState::Funded,
+    )?;
+    completed.forfeited_bond = forfeited_bond;
+
+    emit_cpi!(completed);
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(escrow_id: String)]
+pub struct Settle<'info> {
+    // either party can settle once the challenge window is over
+    #[account(mut)]
+    signer: Signer<'info>,
+
+    /// CHECK: authority side, the receipt holder or escrow.escrow_authority
+    /// without a receipt, checked by check_authority_side
+    #[account(mut)]
+    escrow_authority: UncheckedAccount<'info>,
+
+    /// CHECK: receiver, checked by the escrow seeds
+    #[account(mut)]
+    receiver: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), receiver.key().as_ref()],
+        bump = escrow.bump,
+        constraint = signer.key() == escrow_authority.key() || signer.key() == receiver.key() @ EscrowError::UnauthorizedSigner
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// CHECK: PDA holding SOL deposits of legacy escrows
+    #[account(mut, seeds = [SOL_VAULT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
+    pub sol_vault: Option<UncheckedAccount<'info>>,
+
+    // token accounts are only passed for the legs that move tokens, wSOL
+    // included; a plain SOL leg needs none of them
+    #[account(constraint = escrow.deposit.settles_with(&deposit_mint.key()) @ EscrowError::InvalidDepositMint)]
+    pub deposit_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = deposit_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub escrow_deposit_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+            init_if_needed,
+            payer = signer,
+            associated_token::mint = deposit_mint,
+            associated_token::authority = receiver,
+            associated_token::token_program = token_program
+        )]
+    pub receiver_deposit_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(constraint = escrow.receive.settles_with(&receive_mint.key()) @ EscrowError::InvalidReceiveMint)]
+    pub receive_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+            mut,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow,
+            associated_token::token_program = token_program
+        )]
+    pub receive_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+            init_if_needed,
+            payer = signer,
+            associated_token::mint = receive_mint,
+            associated_token::authority = escrow_authority,
+            associated_token::token_program = token_program
+        )]
+    pub authority_receive_mint_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // only for escrows with a receipt: its mint and the holder's account
+    #[account(mut, seeds = [RECEIPT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    #[account(
+            mut,
+            token::mint = receipt_mint,
+            token::token_program = receipt_token_program
+        )]
+    pub receipt_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // only for escrows with a split receive leg, closed by release
+    #[account(
+            mut,
+            seeds = [SPLIT_SEED.as_bytes(), escrow.key().as_ref()],
+            bump = payout_split.bump
+        )]
+    pub payout_split: Option<Box<Account<'info, PayoutSplit>>>,
+
+    // only for escrows with a referral, the ata for a token deposit leg
+    /// CHECK: checked against the referrer stored at init
+    #[account(mut, address = escrow.referrer @ EscrowError::InvalidReferral)]
+    pub referrer: Option<UncheckedAccount<'info>>,
+
+    #[account(
+            init_if_needed,
+            payer = signer,
+            associated_token::mint = deposit_mint,
+            associated_token::authority = referrer,
+            associated_token::token_program = token_program
+        )]
+    pub referrer_deposit_mint_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
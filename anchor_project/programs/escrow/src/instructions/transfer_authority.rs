@@ -0,0 +1,137 @@
+use crate::{
+    errors::EscrowError,
+    events::EscrowAuthorityTransferred,
+    receipt::{check_authority_side, Receipt},
+    state::{Escrow, EscrowState, ESCROW_SEED, RECEIPT_SEED},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_2022::Token2022,
+    token_interface::{self, CloseAccount, Mint, TokenAccount, TransferChecked},
+};
+
+pub fn _transfer_authority(ctx: Context<TransferAuthority>, _escrow_id: String) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    let escrow_authority = &ctx.accounts.escrow_authority;
+    let new_authority = &ctx.accounts.new_authority;
+
+    // only an open escrow has a side worth handing over
+    require!(
+        matches!(escrow.state, EscrowState::Active | EscrowState::Funded),
+        EscrowError::InvalidState
+    );
+    require!(
+        new_authority.key() != escrow.receiver,
+        EscrowError::SameBuyerSellerNotAllowed
+    );
+
+    let receipt = Receipt {
+        mint: ctx.accounts.receipt_mint.as_deref(),
+        token_account: ctx.accounts.receipt_account.as_deref(),
+        token_program: ctx.accounts.receipt_token_program.as_ref(),
+    };
+    check_authority_side(escrow, &escrow_authority.key(), &receipt)?;
+
+    // The receipt is the authority side, it moves along with it
+    if escrow.has_receipt {
+        let (Some(mint), Some(from), Some(to), Some(token_program)) = (
+            receipt.mint,
+            receipt.token_account,
+            ctx.accounts.new_authority_receipt_ata.as_deref(),
+            receipt.token_program,
+        ) else {
+            return err!(EscrowError::MissingReceipt);
+        };
+
+        token_interface::transfer_checked(
+            CpiContext::new(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: from.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: escrow_authority.to_account_info(),
+                },
+            ),
+            1,
+            0,
+        )?;
+
+        // the previous holder signs, its emptied account goes back to it
+        if from.key() != to.key() {
+            token_interface::close_account(CpiContext::new(
+                token_program.to_account_info(),
+                CloseAccount {
+                    account: from.to_account_info(),
+                    destination: escrow_authority.to_account_info(),
+                    authority: escrow_authority.to_account_info(),
+                },
+            ))?;
+        }
+    }
+
+    let previous_authority = escrow_authority.key();
+
+    // The new authority gets the proceeds, unless a PayoutSplit pays them, and
+    // the right to close. The escrow address keeps following seed_authority
+    escrow.escrow_authority = new_authority.key();
+
+    let clock = Clock::get()?;
+    emit_cpi!(EscrowAuthorityTransferred {
+        escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
+        escrow_authority: new_authority.key(),
+        receiver: escrow.receiver,
+        deposit: escrow.deposit,
+        receive: escrow.receive,
+        previous_authority,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(escrow_id: String)]
+pub struct TransferAuthority<'info> {
+    #[account(mut)]
+    escrow_authority: Signer<'info>,
+
+    /// CHECK: any wallet can take over the authority side
+    new_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED.as_bytes(), escrow_id.as_bytes(), escrow.seed_authority.as_ref(), escrow.receiver.as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // only for escrows with a receipt: its mint, the current holder's account
+    // and where it goes
+    #[account(mut, seeds = [RECEIPT_SEED.as_bytes(), escrow.key().as_ref()], bump)]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    #[account(
+            mut,
+            token::mint = receipt_mint,
+            token::token_program = receipt_token_program
+        )]
+    pub receipt_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+            init_if_needed,
+            payer = escrow_authority,
+            associated_token::mint = receipt_mint,
+            associated_token::authority = new_authority,
+            associated_token::token_program = receipt_token_program
+        )]
+    pub new_authority_receipt_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::EscrowError,
+    events::ConfigUpdated,
+    state::{Config, BPS_DENOMINATOR, CONFIG_SEED},
+};
+
+// A lower cap only applies to escrows created after it, existing ones keep
+// the referral they were made with
+pub fn _update_config(
+    ctx: Context<UpdateConfig>,
+    max_referral_bps: u16,
+    new_admin: Option<Pubkey>,
+) -> Result<()> {
+    require!(
+        max_referral_bps as u64 <= BPS_DENOMINATOR,
+        EscrowError::InvalidConfig
+    );
+
+    let config = &mut ctx.accounts.config;
+    config.max_referral_bps = max_referral_bps;
+
+    if let Some(new_admin) = new_admin {
+        config.admin = new_admin;
+    }
+
+    let clock = Clock::get()?;
+    emit_cpi!(ConfigUpdated {
+        admin: config.admin,
+        max_referral_bps,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    });
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED.as_bytes()],
+        bump = config.bump,
+        has_one = admin @ EscrowError::UnauthorizedSigner
+    )]
+    pub config: Account<'info, Config>,
+}
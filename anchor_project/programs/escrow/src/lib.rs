@@ -1,10 +1,16 @@
-mod errors;
-mod events;
+#[cfg(not(target_os = "solana"))]
+pub mod decode;
+pub mod errors;
+pub mod events;
 mod instructions;
-mod state;
+mod receipt;
+mod release;
+pub mod state;
+mod transfer;
 
 use anchor_lang::prelude::*;
 use instructions::*;
+use state::{Asset, EscrowOptions};
 
 declare_id!("nj9z1iSrdSBhFt3jmxmgHzhBLVqE6b2bh7MwLjiDWuq");
 
@@ -12,29 +18,99 @@ declare_id!("nj9z1iSrdSBhFt3jmxmgHzhBLVqE6b2bh7MwLjiDWuq");
 pub mod escrow {
     use super::*;
 
-    pub fn init_escrow(
-        ctx: Context<InitializeEscrow>,
+    pub fn init_escrow<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitializeEscrow<'info>>,
         escrow_id: String,
-        deposit_mint: Pubkey,
+        deposit: Asset,
         deposit_amount: u64,
-        receive_mint: Pubkey,
+        receive: Asset,
         receive_amount: u64,
+        options: EscrowOptions,
     ) -> Result<()> {
         _init_escrow(
             ctx,
             escrow_id,
-            deposit_mint,
+            deposit,
             deposit_amount,
-            receive_mint,
+            receive,
             receive_amount,
+            options,
         )
     }
 
-    pub fn accept(ctx: Context<Accept>, escrow_id: String) -> Result<()> {
-        _accept(ctx, escrow_id)
+    pub fn accept<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Accept<'info>>,
+        escrow_id: String,
+        wrapped: bool,
+    ) -> Result<()> {
+        _accept(ctx, escrow_id, wrapped)
+    }
+
+    pub fn commit<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Commit<'info>>,
+        escrow_id: String,
+        wrapped: bool,
+    ) -> Result<()> {
+        _commit(ctx, escrow_id, wrapped)
+    }
+
+    pub fn fund<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Fund<'info>>,
+        escrow_id: String,
+        wrapped: bool,
+    ) -> Result<()> {
+        _fund(ctx, escrow_id, wrapped)
+    }
+
+    pub fn settle<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Settle<'info>>,
+        escrow_id: String,
+    ) -> Result<()> {
+        _settle(ctx, escrow_id)
+    }
+
+    pub fn counter_offer<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CounterOffer<'info>>,
+        escrow_id: String,
+        receive_amount: u64,
+        fund: bool,
+        wrapped: bool,
+    ) -> Result<()> {
+        _counter_offer(ctx, escrow_id, receive_amount, fund, wrapped)
     }
 
-    pub fn close(ctx: Context<Close>, escrow_id: String) -> Result<()> {
+    pub fn accept_counter<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AcceptCounter<'info>>,
+        escrow_id: String,
+        expected_amount: u64,
+    ) -> Result<()> {
+        _accept_counter(ctx, escrow_id, expected_amount)
+    }
+
+    pub fn transfer_authority(ctx: Context<TransferAuthority>, escrow_id: String) -> Result<()> {
+        _transfer_authority(ctx, escrow_id)
+    }
+
+    pub fn close<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Close<'info>>,
+        escrow_id: String,
+    ) -> Result<()> {
         _close(ctx, escrow_id)
     }
+
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+        _migrate_escrow(ctx)
+    }
+
+    pub fn init_config(ctx: Context<InitConfig>, max_referral_bps: u16) -> Result<()> {
+        _init_config(ctx, max_referral_bps)
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        max_referral_bps: u16,
+        new_admin: Option<Pubkey>,
+    ) -> Result<()> {
+        _update_config(ctx, max_referral_bps, new_admin)
+    }
 }
@@ -0,0 +1,107 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::Token2022,
+    token_interface::{self, Burn, CloseAccount, Mint, TokenAccount},
+};
+
+use crate::{errors::EscrowError, state::Escrow};
+
+// Receipt accounts passed to the instructions that pay out the authority side
+pub struct Receipt<'a, 'info> {
+    pub mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    pub token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Option<&'a Program<'info, Token2022>>,
+}
+
+// With a receipt the authority side, proceeds, refunds and the right to close,
+// belongs to whoever holds it. Escrows without one stay with the recorded
+// authority. A split receive leg is the exception, see PayoutSplit
+pub fn check_authority_side(
+    escrow: &Escrow,
+    wallet: &Pubkey,
+    receipt: &Receipt<'_, '_>,
+) -> Result<()> {
+    if !escrow.has_receipt {
+        require_keys_eq!(
+            *wallet,
+            escrow.escrow_authority,
+            EscrowError::UnauthorizedSigner
+        );
+        return Ok(());
+    }
+
+    let (Some(_), Some(token_account), Some(_)) =
+        (receipt.mint, receipt.token_account, receipt.token_program)
+    else {
+        return err!(EscrowError::MissingReceipt);
+    };
+
+    require!(
+        token_account.owner == *wallet && token_account.amount == 1,
+        EscrowError::NotReceiptHolder
+    );
+
+    Ok(())
+}
+
+// Burn the receipt through the escrow's permanent delegate and close its mint.
+// Only its owner can close the emptied token account, so it goes too when the
+// holder signs; otherwise it stays with the holder, empty, to close with the
+// token program. Returns the rent paid to `holder`
+pub fn burn_receipt<'info>(
+    receipt: &Receipt<'_, 'info>,
+    escrow: &Account<'info, Escrow>,
+    escrow_seeds: &[&[&[u8]]],
+    holder: AccountInfo<'info>,
+) -> Result<u64> {
+    if !escrow.has_receipt {
+        return Ok(0);
+    }
+
+    let (Some(mint), Some(token_account), Some(token_program)) =
+        (receipt.mint, receipt.token_account, receipt.token_program)
+    else {
+        return err!(EscrowError::MissingReceipt);
+    };
+
+    token_interface::burn(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Burn {
+                mint: mint.to_account_info(),
+                from: token_account.to_account_info(),
+                authority: escrow.to_account_info(),
+            },
+            escrow_seeds,
+        ),
+        1,
+    )?;
+
+    let mut lamports = mint.to_account_info().lamports();
+
+    // token-2022 closes a mint like an account once its supply is back to 0
+    token_interface::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: mint.to_account_info(),
+            destination: holder.clone(),
+            authority: escrow.to_account_info(),
+        },
+        escrow_seeds,
+    ))?;
+
+    if holder.is_signer {
+        lamports += token_account.to_account_info().lamports();
+
+        token_interface::close_account(CpiContext::new(
+            token_program.to_account_info(),
+            CloseAccount {
+                account: token_account.to_account_info(),
+                destination: holder.clone(),
+                authority: holder,
+            },
+        ))?;
+    }
+
+    Ok(lamports)
+}
@@ -0,0 +1,176 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenAccount;
+
+use crate::{
+    errors::EscrowError,
+    events::EscrowCompleted,
+    receipt::{burn_receipt, Receipt},
+    state::{Escrow, EscrowState, PayoutSplit},
+    transfer::{
+        close_token_vault, sweep_sol_vault, transfer_in, transfer_out, transfer_split, Custody,
+        Party, TransferContext,
+    },
+};
+
+// Where the receive leg comes from when an escrow completes
+pub enum ReceiveLeg<'a, 'info> {
+    // the receiver pays it as it accepts
+    Payer(Party<'a, 'info>),
+    // the receiver funded it earlier, the vault rent goes back to it
+    Custody(Custody<'a, 'info>),
+}
+
+// Everyone a completing escrow pays out to
+pub struct Payout<'a, 'info> {
+    // the authority side, checked by check_authority_side
+    pub escrow_authority: AccountInfo<'info>,
+    pub authority_receive_ata: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    // gets the deposit leg less the referral fee
+    pub receiver: Party<'a, 'info>,
+    pub referrer: Option<AccountInfo<'info>>,
+    pub referrer_deposit_ata: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    // the table of an escrow with has_split and the recipients it pays
+    pub split: Option<&'a Account<'info, PayoutSplit>>,
+    pub recipients: &'info [AccountInfo<'info>],
+    pub receipt: Receipt<'a, 'info>,
+}
+
+// Pay out both legs, hand back the rent of the vaults, the receipt and the
+// split table, and complete the escrow. Returns the event to emit
+pub fn release<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    receive_ctx: &TransferContext<'_, 'info>,
+    mut receive_leg: ReceiveLeg<'_, 'info>,
+    deposit_ctx: &TransferContext<'_, 'info>,
+    deposit_custody: &Custody<'_, 'info>,
+    payout: Payout<'_, 'info>,
+    previous_state: EscrowState,
+) -> Result<EscrowCompleted> {
+    let receive = escrow.receive;
+    let receive_amount = escrow.receive_amount;
+
+    // Receive leg to each split recipient, or straight to the authority side
+    {
+        // the first payment unwraps all of a wSOL payer's balance, the rest
+        // is paid from its lamports
+        let mut pay = |to: &Party<'_, 'info>, amount: u64| match &mut receive_leg {
+            ReceiveLeg::Payer(payer) => {
+                transfer_in(receive_ctx, &receive, amount, payer, to)?;
+                payer.wrapped = false;
+                Ok(())
+            }
+            ReceiveLeg::Custody(custody) => {
+                transfer_out(receive_ctx, &receive, amount, custody, to)
+            }
+        };
+
+        match payout.split {
+            Some(split) => {
+                transfer_split(&receive, receive_amount, split, payout.recipients, &mut pay)?;
+                msg!("Transferred {} to split recipients", receive_amount);
+            }
+            None => {
+                pay(
+                    &Party {
+                        wallet: payout.escrow_authority.clone(),
+                        token_account: payout.authority_receive_ata,
+                        wrapped: escrow.authority_wrapped_sol,
+                    },
+                    receive_amount,
+                )?;
+                msg!("Transferred {} to authority", receive_amount);
+            }
+        }
+    }
+
+    // Release the deposit leg to the receiver, a referrer's cut comes off it
+    let referral_fee = escrow.referral_fee()?;
+    if referral_fee > 0 {
+        let referrer = payout
+            .referrer
+            .clone()
+            .ok_or(EscrowError::MissingReferrer)?;
+
+        transfer_out(
+            deposit_ctx,
+            &escrow.deposit,
+            referral_fee,
+            deposit_custody,
+            &Party {
+                wallet: referrer,
+                token_account: payout.referrer_deposit_ata,
+                wrapped: false,
+            },
+        )?;
+        msg!("Transferred {} to referrer", referral_fee);
+    }
+    let receiver_amount = escrow.deposit_amount - referral_fee;
+
+    transfer_out(
+        deposit_ctx,
+        &escrow.deposit,
+        receiver_amount,
+        deposit_custody,
+        &payout.receiver,
+    )?;
+    msg!("Transferred {} to receiver", receiver_amount);
+
+    // each side gets back the rent of the vaults it paid for
+    let receive_vault_rent = match &receive_leg {
+        ReceiveLeg::Custody(custody) => {
+            close_token_vault(receive_ctx, custody, payout.receiver.wallet.clone())?
+        }
+        ReceiveLeg::Payer(_) => 0,
+    };
+    let split_rent = match payout.split {
+        Some(split) => {
+            let lamports = split.get_lamports();
+            split.close(payout.escrow_authority.clone())?;
+            lamports
+        }
+        None => 0,
+    };
+    let reclaimed_lamports = sweep_sol_vault(
+        deposit_ctx,
+        deposit_custody,
+        payout.escrow_authority.clone(),
+    )? + close_token_vault(
+        deposit_ctx,
+        deposit_custody,
+        payout.escrow_authority.clone(),
+    )? + receive_vault_rent
+        + burn_receipt(
+            &payout.receipt,
+            escrow,
+            deposit_custody.escrow_seeds,
+            payout.escrow_authority.clone(),
+        )?
+        + split_rent;
+
+    // the receipt is gone, the authority side stays with its last holder
+    escrow.escrow_authority = payout.escrow_authority.key();
+    escrow.has_receipt = false;
+    escrow.has_split = false;
+
+    escrow.state = EscrowState::Completed;
+
+    let clock = Clock::get()?;
+    Ok(EscrowCompleted {
+        escrow: escrow.key(),
+        escrow_id: escrow.escrow_id.clone(),
+        escrow_authority: payout.escrow_authority.key(),
+        receiver: payout.receiver.wallet.key(),
+        previous_state,
+        deposit: escrow.deposit,
+        deposit_amount: receiver_amount,
+        receive: escrow.receive,
+        receive_amount,
+        referrer: escrow.referrer(),
+        referral_fee,
+        refunded_bond: 0,
+        forfeited_bond: 0,
+        reclaimed_lamports,
+        timestamp: clock.unix_timestamp,
+        slot: clock.slot,
+    })
+}
@@ -1,33 +1,351 @@
 use anchor_lang::prelude::*;
+use anchor_spl::{token, token_2022};
+
+use crate::errors::EscrowError;
 
 pub const ESCROW_ID_LENGTH: usize = 32;
 pub const ESCROW_SEED: &str = "ESCROW_SEED";
 pub const SOL_VAULT_SEED: &str = "sol_vault";
+pub const RECEIPT_SEED: &str = "receipt";
+pub const SPLIT_SEED: &str = "split";
+pub const CONFIG_SEED: &str = "config";
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, InitSpace)]
+// The first layout with a version byte, the legacy one before it had none.
+// Bump whenever a field is carved out of `reserved` or the layout changes,
+// and teach migrate_escrow to read the one it replaces
+pub const ESCROW_VERSION: u8 = 1;
+pub const ESCROW_RESERVED_SPACE: usize = 28;
+
+// Seconds between the receiver funding and settle, unless set at init
+pub const DEFAULT_CHALLENGE_WINDOW: i64 = 10 * 60;
+
+pub const MAX_SPLIT_RECIPIENTS: usize = 5;
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, InitSpace)]
 pub enum EscrowState {
     Active,
     Completed,
     Closed,
+    // both legs in custody, waiting for settle
+    Funded,
+}
+
+// Where a native SOL deposit is held. Migrated legacy escrows keep theirs in
+// the sol_vault PDA, newer ones hold it as extra lamports on the escrow itself
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, InitSpace)]
+pub enum SolCustody {
+    Vault,
+    Escrow,
+}
+
+// Bond the receiver posts in the receive asset to lock the offer to
+// themselves for `window` seconds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub struct BondTerms {
+    pub amount: u64,
+    pub window: i64,
+}
+
+// One recipient of the receive leg and its cut in basis points
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, InitSpace)]
+pub struct SplitShare {
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+// Frontend that brought the escrow in and its cut of the deposit leg
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub struct Referral {
+    pub referrer: Pubkey,
+    pub bps: u16,
+}
+
+// Optional deal terms picked by the authority at init
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct EscrowOptions {
+    pub bond: Option<BondTerms>,
+    // DEFAULT_CHALLENGE_WINDOW when not set
+    pub challenge_window: Option<i64>,
+    // pay the receive leg across several wallets instead of the authority side
+    pub split: Option<Vec<SplitShare>>,
+    // capped by the program Config
+    pub referral: Option<Referral>,
+}
+
+// One leg of an escrow. Native SOL and wSOL are the same asset, a wSOL mint
+// passed at init is stored as Native.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, InitSpace)]
+pub enum Asset {
+    Native,
+    SplToken { mint: Pubkey, program: Pubkey },
+    Token2022 { mint: Pubkey },
+}
+
+impl Asset {
+    pub fn mint(&self) -> Option<Pubkey> {
+        match self {
+            Asset::Native => None,
+            Asset::SplToken { mint, .. } | Asset::Token2022 { mint } => Some(*mint),
+        }
+    }
+
+    pub fn token_program(&self) -> Option<Pubkey> {
+        match self {
+            Asset::Native => None,
+            Asset::SplToken { program, .. } => Some(*program),
+            Asset::Token2022 { .. } => Some(token_2022::ID),
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        *self == Asset::Native
+    }
+
+    // Whether `mint` can settle this leg, native legs settle in either wSOL
+    pub fn settles_with(&self, mint: &Pubkey) -> bool {
+        match self.mint() {
+            Some(own) => own == *mint,
+            None => {
+                *mint == token::spl_token::native_mint::ID
+                    || *mint == token_2022::spl_token_2022::native_mint::ID
+            }
+        }
+    }
+
+    // Collapse wSOL into Native, the flag tells if the caller asked for wSOL
+    pub fn canonicalize(self) -> (Asset, bool) {
+        match self
+            .token_program()
+            .and_then(|program| native_mint_for(&program))
+        {
+            Some(native) if self.mint() == Some(native) => (Asset::Native, true),
+            _ => (self, false),
+        }
+    }
+}
+
+// wSOL mint of a token program
+pub fn native_mint_for(token_program: &Pubkey) -> Option<Pubkey> {
+    if *token_program == token::ID {
+        Some(token::spl_token::native_mint::ID)
+    } else if *token_program == token_2022::ID {
+        Some(token_2022::spl_token_2022::native_mint::ID)
+    } else {
+        None
+    }
 }
 
 #[account]
 #[derive(InitSpace)]
 pub struct Escrow {
+    pub version: u8,
+
     pub escrow_authority: Pubkey,
     pub receiver: Pubkey,
 
     // buyer token/sol details
-    pub deposit_mint: Pubkey,
+    pub deposit: Asset,
     pub deposit_amount: u64,
 
     // Seller token/sol details
-    pub receive_mint: Pubkey,
+    pub receive: Asset,
     pub receive_amount: u64,
 
+    // authority funds, gets refunded and gets paid its SOL leg as wSOL
+    pub authority_wrapped_sol: bool,
+
     pub state: EscrowState,
     pub bump: u8,
 
     #[max_len(ESCROW_ID_LENGTH)]
     pub escrow_id: String,
+
+    // Vault for escrows migrated from the legacy layout
+    pub sol_custody: SolCustody,
+
+    // commit bond, zero amount means the offer can't be committed to.
+    // committed_until is 0 until the receiver commits
+    pub bond_amount: u64,
+    pub commit_window: i64,
+    pub committed_until: i64,
+
+    // two-phase settle. challenge_ends is set when the receiver funds,
+    // receiver_wrapped_sol is how they want the deposit leg paid out
+    pub challenge_window: i64,
+    pub challenge_ends: i64,
+    pub receiver_wrapped_sol: bool,
+
+    // receive amount the receiver proposed instead, 0 when there is no
+    // counter-offer. A funded one sits in custody like a funded receive leg
+    pub counter_amount: u64,
+    pub counter_funded: bool,
+
+    // the authority the escrow address was derived from. escrow_authority
+    // can move on, this stays put for the signer seeds
+    pub seed_authority: Pubkey,
+
+    // a Token-2022 receipt was minted at init, its holder is the authority
+    // side. False for migrated legacy escrows
+    pub has_receipt: bool,
+
+    // the receive leg is paid by the escrow's PayoutSplit account
+    pub has_split: bool,
+
+    // referrer paid referral_bps of the deposit leg when the escrow
+    // completes, no referral while referral_bps is 0
+    pub referrer: Pubkey,
+    pub referral_bps: u16,
+
+    // room for new fields without a realloc, always zeroed
+    pub reserved: [u8; ESCROW_RESERVED_SPACE],
+}
+
+impl Escrow {
+    pub fn referrer(&self) -> Option<Pubkey> {
+        (self.referral_bps > 0).then_some(self.referrer)
+    }
+
+    // Referrer's cut of the deposit leg, rounded down
+    pub fn referral_fee(&self) -> Result<u64> {
+        u64::try_from(
+            self.deposit_amount as u128 * self.referral_bps as u128 / BPS_DENOMINATOR as u128,
+        )
+        .map_err(|_| error!(EscrowError::OverFlow))
+    }
+}
+
+// The layout deployed before the version byte: a mint per leg with
+// Pubkey::default() as native SOL, SOL deposits held in the sol vault
+pub mod legacy {
+    use super::*;
+
+    #[derive(AnchorDeserialize, InitSpace)]
+    pub struct SentinelEscrow {
+        pub escrow_authority: Pubkey,
+        pub receiver: Pubkey,
+        pub deposit_mint: Pubkey,
+        pub deposit_amount: u64,
+        pub receive_mint: Pubkey,
+        pub receive_amount: u64,
+        pub state: EscrowState,
+        pub bump: u8,
+        #[max_len(ESCROW_ID_LENGTH)]
+        pub escrow_id: String,
+    }
+
+    // Token legs could only be Token-2022 back then
+    fn asset(mint: Pubkey) -> Asset {
+        if mint == Pubkey::default() {
+            Asset::Native
+        } else {
+            Asset::Token2022 { mint }
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Escrow> {
+        let old = SentinelEscrow::deserialize(&mut &data[8..])?;
+
+        Ok(Escrow {
+            version: ESCROW_VERSION,
+            escrow_authority: old.escrow_authority,
+            receiver: old.receiver,
+            deposit: asset(old.deposit_mint),
+            deposit_amount: old.deposit_amount,
+            receive: asset(old.receive_mint),
+            receive_amount: old.receive_amount,
+            authority_wrapped_sol: false,
+            state: old.state,
+            bump: old.bump,
+            escrow_id: old.escrow_id,
+            sol_custody: SolCustody::Vault,
+            bond_amount: 0,
+            commit_window: 0,
+            committed_until: 0,
+            challenge_window: 0,
+            challenge_ends: 0,
+            receiver_wrapped_sol: false,
+            counter_amount: 0,
+            counter_funded: false,
+            seed_authority: old.escrow_authority,
+            has_receipt: false,
+            has_split: false,
+            referrer: Pubkey::default(),
+            referral_bps: 0,
+            reserved: [0; ESCROW_RESERVED_SPACE],
+        })
+    }
+}
+
+// Program wide settings, one account at ["config"] owned by the upgrade
+// authority
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub admin: Pubkey,
+    // highest referral_bps an escrow can be created with
+    pub max_referral_bps: u16,
+    pub bump: u8,
+}
+
+// Payout table of an escrow, lives next to it at ["split", escrow]. The
+// recipients are fixed at init: whoever holds the receipt, or takes over with
+// transfer_authority, gets the refunds, the rent and the right to close, but
+// never the receive leg of a split escrow
+#[account]
+#[derive(InitSpace)]
+pub struct PayoutSplit {
+    pub escrow: Pubkey,
+    #[max_len(MAX_SPLIT_RECIPIENTS)]
+    pub shares: Vec<SplitShare>,
+    pub bump: u8,
+}
+
+impl PayoutSplit {
+    pub fn validate(shares: &[SplitShare]) -> Result<()> {
+        require!(
+            !shares.is_empty() && shares.len() <= MAX_SPLIT_RECIPIENTS,
+            EscrowError::InvalidSplit
+        );
+        require!(
+            shares.iter().all(|share| share.bps > 0),
+            EscrowError::InvalidSplit
+        );
+        require!(
+            shares.iter().map(|share| share.bps as u64).sum::<u64>() == BPS_DENOMINATOR,
+            EscrowError::InvalidSplit
+        );
+
+        for (i, share) in shares.iter().enumerate() {
+            require!(
+                shares[..i]
+                    .iter()
+                    .all(|other| other.recipient != share.recipient),
+                EscrowError::InvalidSplit
+            );
+        }
+
+        Ok(())
+    }
+
+    // Cut of `amount` for each share, rounding dust goes to the first one
+    pub fn amounts(&self, amount: u64) -> Result<Vec<u64>> {
+        let mut amounts = self
+            .shares
+            .iter()
+            .map(|share| {
+                u64::try_from(amount as u128 * share.bps as u128 / BPS_DENOMINATOR as u128)
+                    .map_err(|_| error!(EscrowError::OverFlow))
+            })
+            .collect::<Result<Vec<u64>>>()?;
+
+        let paid: u64 = amounts.iter().sum();
+        if let Some(first) = amounts.first_mut() {
+            *first = first
+                .checked_add(amount - paid)
+                .ok_or(EscrowError::OverFlow)?;
+        }
+
+        Ok(amounts)
+    }
 }
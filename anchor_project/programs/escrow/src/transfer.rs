@@ -0,0 +1,389 @@
+use anchor_lang::prelude::{
+    program::{invoke, invoke_signed},
+    system_instruction::transfer,
+    *,
+};
+use anchor_spl::{
+    token_2022::spl_token_2022::{instruction::sync_native, onchain::invoke_transfer_checked},
+    token_interface::{self, CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    errors::EscrowError,
+    state::{native_mint_for, Asset, PayoutSplit},
+};
+
+// Accounts every leg transfer needs, the mint is only read for token legs
+pub struct TransferContext<'a, 'info> {
+    pub mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    pub system_program: &'a Program<'info, System>,
+    // transfer hook extra accounts, if any
+    pub remaining_accounts: &'a [AccountInfo<'info>],
+}
+
+// A wallet paying or getting paid. `wrapped` means it deals in wSOL through
+// `token_account` for native legs
+pub struct Party<'a, 'info> {
+    pub wallet: AccountInfo<'info>,
+    pub token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub wrapped: bool,
+}
+
+// Where the escrow keeps a deposit: the escrow itself for native legs, unless
+// it was migrated from the legacy layout and still has a sol_vault; the
+// escrow ATA for token legs
+pub struct Custody<'a, 'info> {
+    pub sol_vault: Option<AccountInfo<'info>>,
+    pub vault_seeds: &'a [&'a [&'a [u8]]],
+    pub escrow: AccountInfo<'info>,
+    pub escrow_seeds: &'a [&'a [&'a [u8]]],
+    pub token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+}
+
+// Pay a leg from a signer's wallet
+pub fn transfer_in<'info>(
+    ctx: &TransferContext<'_, 'info>,
+    asset: &Asset,
+    amount: u64,
+    from: &Party<'_, 'info>,
+    to: &Party<'_, 'info>,
+) -> Result<()> {
+    match asset {
+        Asset::Native => {
+            // wSOL is unwrapped first, the signer then pays in lamports
+            if from.wrapped {
+                let from_ata = wsol_account(ctx, from.token_account)?;
+
+                require!(from_ata.amount >= amount, EscrowError::InsufficientBalance);
+
+                unwrap_sol(
+                    from_ata.to_account_info(),
+                    from.wallet.clone(),
+                    ctx.token_program.to_account_info(),
+                )?;
+            }
+
+            pay_native(ctx, from.wallet.clone(), to, amount, &[])
+        }
+        _ => {
+            let from_ata = from.token_account.ok_or(EscrowError::InvalidTokenAccount)?;
+            let to_ata = to.token_account.ok_or(EscrowError::InvalidTokenAccount)?;
+
+            require!(from_ata.amount >= amount, EscrowError::InsufficientBalance);
+
+            pay_token(
+                ctx,
+                asset,
+                from_ata,
+                to_ata,
+                from.wallet.clone(),
+                amount,
+                &[],
+            )
+        }
+    }
+}
+
+// Pay a leg out of escrow custody
+pub fn transfer_out<'info>(
+    ctx: &TransferContext<'_, 'info>,
+    asset: &Asset,
+    amount: u64,
+    from: &Custody<'_, 'info>,
+    to: &Party<'_, 'info>,
+) -> Result<()> {
+    match asset {
+        Asset::Native => match &from.sol_vault {
+            Some(sol_vault) => pay_native(ctx, sol_vault.clone(), to, amount, from.vault_seeds),
+            None => pay_from_escrow(ctx, from.escrow.clone(), to, amount),
+        },
+        _ => {
+            let from_ata = from.token_account.ok_or(EscrowError::InvalidTokenAccount)?;
+            let to_ata = to.token_account.ok_or(EscrowError::InvalidTokenAccount)?;
+
+            pay_token(
+                ctx,
+                asset,
+                from_ata,
+                to_ata,
+                from.escrow.clone(),
+                amount,
+                from.escrow_seeds,
+            )
+        }
+    }
+}
+
+// Split recipients off the front of the remaining accounts, the rest are
+// transfer hook accounts
+pub fn split_recipients<'info>(
+    split: Option<&PayoutSplit>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+) -> Result<(&'info [AccountInfo<'info>], &'info [AccountInfo<'info>])> {
+    let count = split.map_or(0, |split| split.shares.len());
+
+    require!(remaining_accounts.len() >= count, EscrowError::MissingSplit);
+
+    Ok(remaining_accounts.split_at(count))
+}
+
+// Pay a leg across a split table through `pay`. The recipients lead the
+// remaining accounts in table order: their wallets for a native leg, their
+// token accounts of the leg's mint otherwise. Native shares are always paid
+// as plain SOL
+pub fn transfer_split<'info>(
+    asset: &Asset,
+    amount: u64,
+    split: &PayoutSplit,
+    recipients: &'info [AccountInfo<'info>],
+    mut pay: impl FnMut(&Party<'_, 'info>, u64) -> Result<()>,
+) -> Result<()> {
+    require!(
+        recipients.len() == split.shares.len(),
+        EscrowError::MissingSplit
+    );
+
+    let amounts = split.amounts(amount)?;
+
+    for ((share, recipient), amount) in split.shares.iter().zip(recipients).zip(amounts) {
+        if asset.is_native() {
+            require_keys_eq!(recipient.key(), share.recipient, EscrowError::InvalidSplit);
+
+            pay(
+                &Party {
+                    wallet: recipient.clone(),
+                    token_account: None,
+                    wrapped: false,
+                },
+                amount,
+            )?;
+        } else {
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(recipient)?;
+
+            require!(
+                token_account.owner == share.recipient && Some(token_account.mint) == asset.mint(),
+                EscrowError::InvalidSplit
+            );
+
+            pay(
+                &Party {
+                    wallet: recipient.clone(),
+                    token_account: Some(&token_account),
+                    wrapped: false,
+                },
+                amount,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn pay_native<'info>(
+    ctx: &TransferContext<'_, 'info>,
+    from: AccountInfo<'info>,
+    to: &Party<'_, 'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if to.wrapped {
+        let to_ata = wsol_account(ctx, to.token_account)?;
+
+        return wrap_sol(
+            from,
+            to_ata.to_account_info(),
+            ctx.system_program.to_account_info(),
+            ctx.token_program.to_account_info(),
+            amount,
+            signer_seeds,
+        );
+    }
+
+    let transfer_ix = transfer(from.key, to.wallet.key, amount);
+    invoke_signed(
+        &transfer_ix,
+        &[
+            from,
+            to.wallet.clone(),
+            ctx.system_program.to_account_info(),
+        ],
+        signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+// The escrow is program owned, so it pays by moving lamports directly
+fn pay_from_escrow<'info>(
+    ctx: &TransferContext<'_, 'info>,
+    escrow: AccountInfo<'info>,
+    to: &Party<'_, 'info>,
+    amount: u64,
+) -> Result<()> {
+    if !to.wrapped {
+        escrow.sub_lamports(amount)?;
+        to.wallet.add_lamports(amount)?;
+
+        return Ok(());
+    }
+
+    let to_ata = wsol_account(ctx, to.token_account)?.to_account_info();
+
+    escrow.sub_lamports(amount)?;
+    to_ata.add_lamports(amount)?;
+
+    // the escrow rides along in the sync so the runtime sees both sides of
+    // the lamport move, the token program only reads the first account
+    let mut sync_ix = sync_native(ctx.token_program.key, to_ata.key)?;
+    sync_ix.accounts.push(AccountMeta::new(escrow.key(), false));
+
+    invoke(
+        &sync_ix,
+        &[to_ata, escrow, ctx.token_program.to_account_info()],
+    )?;
+
+    Ok(())
+}
+
+fn pay_token<'info>(
+    ctx: &TransferContext<'_, 'info>,
+    asset: &Asset,
+    from_ata: &InterfaceAccount<'info, TokenAccount>,
+    to_ata: &InterfaceAccount<'info, TokenAccount>,
+    authority: AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let mint = ctx.mint.ok_or(EscrowError::InvalidTokenAccount)?;
+
+    require!(
+        asset.token_program() == Some(ctx.token_program.key()),
+        EscrowError::InvalidTokenProgram
+    );
+
+    invoke_transfer_checked(
+        &ctx.token_program.key(),
+        from_ata.to_account_info(),
+        mint.to_account_info(),
+        to_ata.to_account_info(),
+        authority,
+        ctx.remaining_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+// wSOL account of the token program in use
+fn wsol_account<'a, 'info>(
+    ctx: &TransferContext<'_, 'info>,
+    token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+) -> Result<&'a InterfaceAccount<'info, TokenAccount>> {
+    let token_account = token_account.ok_or(EscrowError::InvalidTokenAccount)?;
+
+    require!(
+        native_mint_for(&ctx.token_program.key()) == Some(token_account.mint),
+        EscrowError::InvalidTokenAccount
+    );
+
+    Ok(token_account)
+}
+
+// Pay lamports into a wSOL account and sync it so they count as tokens
+fn wrap_sol<'info>(
+    from: AccountInfo<'info>,
+    to_ata: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let transfer_ix = transfer(from.key, to_ata.key, amount);
+    invoke_signed(
+        &transfer_ix,
+        &[from, to_ata.clone(), system_program],
+        signer_seeds,
+    )?;
+
+    token_interface::sync_native(CpiContext::new(
+        token_program,
+        SyncNative { account: to_ata },
+    ))
+}
+
+// Unwrap a wSOL account by closing it, every lamport goes back to its owner
+fn unwrap_sol<'info>(
+    ata: AccountInfo<'info>,
+    owner: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+) -> Result<()> {
+    token_interface::close_account(CpiContext::new(
+        token_program,
+        CloseAccount {
+            account: ata,
+            destination: owner.clone(),
+            authority: owner,
+        },
+    ))
+}
+
+// Sweep every lamport out of the sol vault, rent exemption included
+pub fn sweep_sol_vault<'info>(
+    ctx: &TransferContext<'_, 'info>,
+    from: &Custody<'_, 'info>,
+    to: AccountInfo<'info>,
+) -> Result<u64> {
+    let Some(sol_vault) = &from.sol_vault else {
+        return Ok(0);
+    };
+
+    let lamports = sol_vault.lamports();
+
+    if lamports > 0 {
+        let transfer_ix = transfer(sol_vault.key, to.key, lamports);
+        invoke_signed(
+            &transfer_ix,
+            &[sol_vault.clone(), to, ctx.system_program.to_account_info()],
+            from.vault_seeds,
+        )?;
+    }
+
+    Ok(lamports)
+}
+
+// Close the (empty) escrow token account, its rent goes to `to`
+pub fn close_token_vault<'info>(
+    ctx: &TransferContext<'_, 'info>,
+    from: &Custody<'_, 'info>,
+    to: AccountInfo<'info>,
+) -> Result<u64> {
+    let Some(token_account) = from.token_account else {
+        return Ok(0);
+    };
+
+    let info = token_account.to_account_info();
+
+    // the cached amount is stale after a transfer out, read it back; stray
+    // tokens sent to the vault keep it open rather than failing the close
+    let amount = TokenAccount::try_deserialize(&mut &info.try_borrow_data()?[..])?.amount;
+    if amount > 0 {
+        return Ok(0);
+    }
+
+    let lamports = info.lamports();
+
+    token_interface::close_account(CpiContext::new_with_signer(
+        ctx.token_program.to_account_info(),
+        CloseAccount {
+            account: info,
+            destination: to,
+            authority: from.escrow.clone(),
+        },
+        from.escrow_seeds,
+    ))?;
+
+    Ok(lamports)
+}
@@ -0,0 +1,290 @@
+use anchor_spl::{token, token_2022};
+use escrow::{decode::EscrowEvent, errors::EscrowError};
+use escrow_client::{
+    instructions,
+    pda::{receipt_mint_address, token_account},
+    Asset, EscrowState,
+};
+
+use crate::setup::*;
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_sol_for_tokens() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "sol_for_tokens",
+        Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN),
+    ));
+    assert_state(&env, &escrow, EscrowState::Active);
+    let receiver_lamports = env.lamports(&env.receiver.clone());
+
+    let outcome = ok(env.accept(&escrow, false));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1010 * TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        990 * TOKEN
+    );
+    assert!(env.lamports(&env.receiver.clone()) > receiver_lamports + SOL / 2);
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::Completed(_)]
+    ));
+
+    // the receipt is burnt and its mint closed
+    assert!(env.svm.account(&receipt_mint_address(&escrow).0).is_none());
+    assert!(!env.escrow(&escrow).has_receipt);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_tokens_for_tokens() {
+    let mut env = Env::new();
+    let other_mint = env.create_mint(&token::ID);
+    env.mint_to(&env.receiver.clone(), &other_mint, 10 * TOKEN);
+    let other = Asset::SplToken {
+        mint: other_mint,
+        program: token::ID,
+    };
+    let escrow = created(env.init(
+        "tokens_for_tokens",
+        Deal::new(env.spl_token(), 3 * TOKEN, other, 7 * TOKEN),
+    ));
+
+    ok(env.accept(&escrow, false));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.spl_mint.clone()),
+        1003 * TOKEN
+    );
+    assert_eq!(env.balance(&env.authority.clone(), &other_mint), 7 * TOKEN);
+    // the emptied deposit vault is closed
+    assert!(env
+        .svm
+        .account(&token_account(&escrow, &env.spl_mint, &token::ID))
+        .is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_paying_and_paid_in_wsol() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "wsol_receiver",
+        Deal::new(Asset::Native, 2 * SOL, env.token_2022(), TOKEN),
+    ));
+    let wsol = token_2022::spl_token_2022::native_mint::ID;
+    env.wrap_sol(&env.receiver.clone(), &token_2022::ID, SOL);
+
+    ok(env.accept(&escrow, true));
+    assert_eq!(
+        env.token_balance(&token_account(&env.receiver, &wsol, &token_2022::ID)),
+        3 * SOL
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_twice_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "twice",
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+    ));
+    ok(env.accept(&escrow, false));
+
+    assert_error(env.accept(&escrow, false), EscrowError::InvalidState);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_without_the_receive_tokens_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "too_expensive",
+        Deal::new(Asset::Native, SOL, env.token_2022(), 1001 * TOKEN),
+    ));
+
+    assert_error(env.accept(&escrow, false), EscrowError::InsufficientBalance);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_by_someone_else_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "stranger",
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+    ));
+    let stranger = env.wallet();
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+
+    // the escrow address is derived from its receiver
+    assert_anchor_error(
+        env.send(
+            swap_account(instruction, &env.receiver.clone(), &stranger),
+            &[&stranger],
+        ),
+        anchor_lang::error::ErrorCode::ConstraintSeeds,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_with_the_wrong_deposit_mint_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "wrong_deposit_mint",
+        Deal::new(env.spl_token(), TOKEN, Asset::Native, SOL),
+    ));
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    // an escrow vault of the other mint, anchor wants it to exist
+    let other_mint = env.create_mint(&token::ID);
+    env.mint_to(&escrow, &other_mint, 0);
+
+    assert_error(
+        env.send(
+            swap_mint(
+                instruction,
+                &env.spl_mint.clone(),
+                &other_mint,
+                &token::ID,
+                &[escrow, env.receiver],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidDepositMint,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_with_the_wrong_receive_mint_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "wrong_receive_mint",
+        Deal::new(Asset::Native, SOL, env.spl_token(), TOKEN),
+    ));
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    let other_mint = env.create_mint(&token::ID);
+    env.mint_to(&env.receiver.clone(), &other_mint, TOKEN);
+
+    assert_error(
+        env.send(
+            swap_mint(
+                instruction,
+                &env.spl_mint.clone(),
+                &other_mint,
+                &token::ID,
+                &[env.receiver, env.authority],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidReceiveMint,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_without_a_token_mint_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "no_receive_mint",
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+    ));
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+
+    assert_error(
+        env.send(
+            without_mint(
+                instruction,
+                &env.mint.clone(),
+                &token_2022::ID,
+                &[env.receiver, env.authority],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidReceiveMint,
+    );
+
+    let escrow = created(env.init(
+        "no_deposit_mint",
+        Deal::new(env.token_2022(), TOKEN, Asset::Native, SOL),
+    ));
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+
+    assert_error(
+        env.send(
+            without_mint(
+                instruction,
+                &env.mint.clone(),
+                &token_2022::ID,
+                &[escrow, env.receiver],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidDepositMint,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_without_the_receiver_token_account_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "no_receiver_ata",
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+    ));
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    let receiver_ata = token_account(&env.receiver, &env.mint, &token_2022::ID);
+
+    assert_error(
+        env.send(
+            without(instruction, &receiver_ata),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidTokenAccount,
+    );
+}
+
+// Same ceiling as the TS compute benchmark, raise it only with the numbers
+// in benches/footprint.txt that justify it
+const ACCEPT_COMPUTE_UNITS: u64 = 80_000;
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_stays_under_its_compute_budget() {
+    let mut env = Env::new();
+    for (escrow_id, deposit, deposit_amount, receive, receive_amount) in [
+        (
+            "sol_for_tokens",
+            Asset::Native,
+            SOL,
+            env.token_2022(),
+            TOKEN,
+        ),
+        (
+            "tokens_for_sol",
+            env.token_2022(),
+            TOKEN,
+            Asset::Native,
+            SOL,
+        ),
+    ] {
+        let escrow = created(env.init(
+            escrow_id,
+            Deal::new(deposit, deposit_amount, receive, receive_amount),
+        ));
+
+        let outcome = ok(env.accept(&escrow, false));
+        assert!(
+            outcome.compute_units < ACCEPT_COMPUTE_UNITS,
+            "{escrow_id} took {} compute units",
+            outcome.compute_units
+        );
+    }
+}
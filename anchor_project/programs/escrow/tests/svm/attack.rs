@@ -0,0 +1,357 @@
+// An attacker against escrows of other people. They hold a wallet, an escrow
+// of their own and a deal where they are the receiver, and send accept, close
+// and settle with any account in any slot and any data. fuzz.rs and the
+// libFuzzer target in fuzz/ both drive this
+use std::collections::BTreeMap;
+
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::instruction::Instruction,
+};
+use anchor_spl::{associated_token, token, token_2022};
+use arbitrary::Arbitrary;
+use escrow::decode::event_authority;
+use escrow_client::{
+    instructions,
+    pda::{receipt_mint_address, sol_vault_address, token_account},
+    Asset, Escrow, EscrowOptions, EscrowState,
+};
+
+use crate::{runtime::Account, setup::*};
+
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub enum Kind {
+    Accept,
+    Close,
+    Settle,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Data {
+    Keep,
+    // (index, byte) pairs written over the real data
+    Patch(Vec<(u8, u8)>),
+    // the real data of another instruction
+    Of(Kind),
+    Raw(Vec<u8>),
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Substitution {
+    pub slot: u8,
+    // index into the account pool
+    pub account: u8,
+    // a signature only counts for the attacker's own wallet
+    pub signer: bool,
+    pub writable: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Attack {
+    pub kind: Kind,
+    // index into the escrows, victims' and the attacker's
+    pub target: u8,
+    pub substitutions: Vec<Substitution>,
+    // pool accounts appended after the named ones, where split recipients go
+    pub extra: Vec<u8>,
+    pub data: Data,
+    // minutes the clock moves first, to get past challenge windows
+    pub warp: u8,
+}
+
+// Escrows in the order `Attack::target` picks them
+pub const VICTIM_SOL: u8 = 0;
+pub const VICTIM_FUNDED: u8 = 1;
+pub const VICTIM_LEGACY: u8 = 2;
+pub const ATTACKER_RECEIVES: u8 = 3;
+pub const ATTACKER_OWN: u8 = 4;
+
+// the attacker pays this for the deal where they are the receiver
+const PRICE: u64 = 5 * TOKEN;
+
+pub struct Harness {
+    pub env: Env,
+    pub attacker: Pubkey,
+    escrows: Vec<Pubkey>,
+    // as last read, the attacker may close their own
+    last: Vec<Escrow>,
+    // every account an attack can put in a slot
+    pool: Vec<Pubkey>,
+    // accounts of the escrows the attacker is no authority of, none of which
+    // may change unless the attacker pays for their deal
+    protected: Vec<Vec<(Pubkey, Option<Account>)>>,
+    // lamports and test tokens of the victims, which may only go up
+    victims: Vec<Pubkey>,
+    wealth: BTreeMap<(Pubkey, Pubkey), u64>,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let mut env = Env::new();
+        let attacker = env.wallet();
+        env.mint_to(&attacker, &env.mint.clone(), 1000 * TOKEN);
+        let (authority, receiver) = (env.authority, env.receiver);
+
+        let victim_sol = created(env.init(
+            "victim_sol",
+            Deal::new(Asset::Native, 2 * SOL, env.token_2022(), 10 * TOKEN),
+        ));
+        let victim_funded = created(env.init(
+            "victim_funded",
+            Deal::new(env.token_2022(), 10 * TOKEN, Asset::Native, SOL).with(EscrowOptions {
+                challenge_window: Some(60 * 60),
+                ..no_options()
+            }),
+        ));
+        ok(env.fund(&victim_funded, false));
+
+        // its deposit sits in the sol vault
+        let victim_legacy = legacy_escrow(&mut env);
+        ok(env.send(
+            instructions::migrate_escrow(&attacker, &victim_legacy),
+            &[&attacker],
+        ));
+
+        let attacker_receives = created(env.init_by(
+            &authority,
+            &attacker,
+            "attacker_receives",
+            Deal::new(Asset::Native, 2 * SOL, env.token_2022(), PRICE),
+        ));
+        let attacker_own = created(env.init_by(
+            &attacker,
+            &receiver,
+            "attacker_own",
+            Deal::new(env.token_2022(), 10 * TOKEN, Asset::Native, SOL),
+        ));
+
+        let escrows = vec![
+            victim_sol,
+            victim_funded,
+            victim_legacy,
+            attacker_receives,
+            attacker_own,
+        ];
+        let last = escrows.iter().map(|escrow| env.escrow(escrow)).collect();
+        let victims = vec![
+            authority,
+            receiver,
+            legacy_authority(),
+            env.escrow(&victim_legacy).receiver,
+        ];
+
+        let wallets: Vec<Pubkey> = [attacker].into_iter().chain(victims.clone()).collect();
+        let mut pool = wallets.clone();
+        for address in &escrows {
+            let (receipt_mint, _) = receipt_mint_address(address);
+            let holder = env.escrow(address).escrow_authority;
+            pool.extend([
+                *address,
+                sol_vault_address(address).0,
+                token_account(address, &env.mint, &token_2022::ID),
+                receipt_mint,
+                token_account(&holder, &receipt_mint, &token_2022::ID),
+            ]);
+        }
+        for wallet in &wallets {
+            pool.extend([
+                token_account(wallet, &env.mint, &token_2022::ID),
+                token_account(wallet, &env.spl_mint, &token::ID),
+            ]);
+        }
+        pool.extend([
+            env.mint,
+            env.spl_mint,
+            escrow::ID,
+            token::ID,
+            token_2022::ID,
+            associated_token::ID,
+            anchor_lang::system_program::ID,
+            event_authority(),
+        ]);
+
+        let mut harness = Self {
+            env,
+            attacker,
+            escrows,
+            last,
+            pool,
+            protected: Vec::new(),
+            victims,
+            wealth: BTreeMap::new(),
+        };
+        harness.protected = (0..ATTACKER_OWN)
+            .map(|target| harness.protected_accounts(target))
+            .collect();
+        harness.wealth = harness.victim_wealth();
+        harness
+    }
+
+    pub fn escrow(&self, target: u8) -> Pubkey {
+        self.escrows[target as usize]
+    }
+
+    // Index of `key` in the pool, for attacks written by hand
+    pub fn account(&self, key: &Pubkey) -> u8 {
+        self.pool
+            .iter()
+            .position(|account| account == key)
+            .expect("account in the pool") as u8
+    }
+
+    pub fn run(&mut self, attacks: &[Attack]) {
+        for attack in attacks {
+            self.attack(attack);
+        }
+    }
+
+    pub fn attack(&mut self, attack: &Attack) {
+        let target = attack.target % self.escrows.len() as u8;
+        let price_paid_to = self.env.balance(&self.env.authority, &self.env.mint);
+        self.env.svm.warp(attack.warp as i64 * 60);
+
+        let instruction = self.instruction(attack, target);
+        let attacker = self.attacker;
+        let _ = self.env.svm.send(&[instruction], &[&attacker]);
+
+        // the one way into a victim's escrow is to pay for it
+        let address = self.escrow(ATTACKER_RECEIVES);
+        if self.protected[ATTACKER_RECEIVES as usize][0].1 != self.env.svm.account(&address) {
+            assert_eq!(
+                self.env.escrow(&address).state,
+                EscrowState::Completed,
+                "{attack:?} changed the attacker's deal without completing it"
+            );
+            assert!(
+                self.env.balance(&self.env.authority, &self.env.mint) >= price_paid_to + PRICE,
+                "{attack:?} completed the attacker's deal without paying"
+            );
+            self.protected[ATTACKER_RECEIVES as usize] = self.protected_accounts(ATTACKER_RECEIVES);
+        }
+
+        self.check(attack);
+    }
+
+    // The legitimate instruction for `target` with the attacker in every
+    // signing slot, then the attack applied to it
+    fn instruction(&mut self, attack: &Attack, target: u8) -> Instruction {
+        let mut instruction = self.template(attack.kind, target);
+        for meta in &mut instruction.accounts {
+            if meta.is_signer {
+                meta.pubkey = self.attacker;
+            }
+        }
+
+        let slots = instruction.accounts.len();
+        for substitution in &attack.substitutions {
+            let account = self.pool[substitution.account as usize % self.pool.len()];
+            let meta = &mut instruction.accounts[substitution.slot as usize % slots];
+            meta.pubkey = account;
+            meta.is_signer = substitution.signer && account == self.attacker;
+            meta.is_writable = substitution.writable;
+        }
+        // the runtime caps accounts per instruction well below this
+        for account in attack.extra.iter().take(16) {
+            let account = self.pool[*account as usize % self.pool.len()];
+            instruction.accounts.push(AccountMeta::new(account, false));
+        }
+
+        match &attack.data {
+            Data::Keep => {}
+            Data::Patch(patches) => {
+                for (index, byte) in patches {
+                    let len = instruction.data.len();
+                    instruction.data[*index as usize % len] = *byte;
+                }
+            }
+            Data::Of(kind) => instruction.data = self.template(*kind, target).data,
+            Data::Raw(data) => instruction.data = data.clone(),
+        }
+
+        instruction
+    }
+
+    fn template(&mut self, kind: Kind, target: u8) -> Instruction {
+        let address = self.escrow(target);
+        if self.env.svm.account(&address).is_some() {
+            self.last[target as usize] = self.env.escrow(&address);
+        }
+        let escrow = &self.last[target as usize];
+        let holder = if self.env.svm.account(&address).is_some() {
+            self.env.authority_side(&address)
+        } else {
+            escrow.escrow_authority
+        };
+
+        match kind {
+            Kind::Accept => instructions::accept(escrow, &holder, false, None),
+            Kind::Close => instructions::close(escrow, &holder),
+            Kind::Settle => instructions::settle(escrow, &self.attacker, &holder, None),
+        }
+    }
+
+    // The escrow, everything that holds its legs and its receipt
+    fn protected_accounts(&self, target: u8) -> Vec<(Pubkey, Option<Account>)> {
+        let address = self.escrow(target);
+        let (receipt_mint, _) = receipt_mint_address(&address);
+        let holder = self.last[target as usize].escrow_authority;
+
+        [
+            address,
+            sol_vault_address(&address).0,
+            token_account(&address, &self.env.mint, &token_2022::ID),
+            receipt_mint,
+            token_account(&holder, &receipt_mint, &token_2022::ID),
+        ]
+        .into_iter()
+        .map(|key| (key, self.env.svm.account(&key)))
+        .collect()
+    }
+
+    // Lamports and test mint balances of each victim, keyed by (wallet, mint)
+    // with the default key for lamports
+    fn victim_wealth(&self) -> BTreeMap<(Pubkey, Pubkey), u64> {
+        let mut wealth = BTreeMap::new();
+        for victim in &self.victims {
+            wealth.insert((*victim, Pubkey::default()), self.env.lamports(victim));
+        }
+
+        for (_, account) in self.env.svm.accounts() {
+            if account.data.len() < 165 || ![token::ID, token_2022::ID].contains(&account.owner) {
+                continue;
+            }
+            let mint = Pubkey::try_from(&account.data[..32]).unwrap();
+            let owner = Pubkey::try_from(&account.data[32..64]).unwrap();
+            if self.victims.contains(&owner) && [self.env.mint, self.env.spl_mint].contains(&mint) {
+                *wealth.entry((owner, mint)).or_default() +=
+                    u64::from_le_bytes(account.data[64..72].try_into().unwrap());
+            }
+        }
+
+        wealth
+    }
+
+    fn check(&mut self, attack: &Attack) {
+        for accounts in &self.protected {
+            for (key, before) in accounts {
+                assert!(
+                    self.env.svm.account(key) == *before,
+                    "{attack:?} changed {key}"
+                );
+            }
+        }
+
+        let wealth = self.victim_wealth();
+        for (key, before) in &self.wealth {
+            let after = wealth.get(key).copied().unwrap_or_default();
+            assert!(
+                after >= *before,
+                "{attack:?} took {} of {} from {}",
+                before - after,
+                key.1,
+                key.0
+            );
+        }
+        self.wealth = wealth;
+    }
+}
@@ -0,0 +1,248 @@
+use anchor_spl::token_2022;
+use escrow::{decode::EscrowEvent, errors::EscrowError};
+use escrow_client::{
+    instructions,
+    pda::{receipt_mint_address, token_account},
+    Asset, EscrowState,
+};
+
+use crate::setup::*;
+
+fn sol_for_tokens(env: &Env) -> Deal {
+    Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN)
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn transfer_authority_moves_the_receipt() {
+    let mut env = Env::new();
+    let escrow = created(env.init("transfer", sol_for_tokens(&env)));
+    let new_authority = env.wallet();
+
+    let outcome = ok(env.transfer_authority(&escrow, &new_authority));
+    let account = env.escrow(&escrow);
+    assert_eq!(account.escrow_authority, new_authority);
+    // the address stays derived from the first authority
+    assert_eq!(account.seed_authority, env.authority);
+    let (receipt_mint, _) = receipt_mint_address(&escrow);
+    assert_eq!(
+        env.token_balance(&token_account(
+            &new_authority,
+            &receipt_mint,
+            &token_2022::ID
+        )),
+        1
+    );
+    let [EscrowEvent::AuthorityTransferred(transferred)] = outcome.events.as_slice() else {
+        panic!(
+            "expected EscrowAuthorityTransferred, got {:?}",
+            outcome.events
+        );
+    };
+    assert_eq!(transferred.escrow_authority, new_authority);
+    assert_eq!(transferred.previous_authority, env.authority);
+    assert_eq!(transferred.escrow_id, "transfer");
+    // the previous holder signed, its emptied receipt account is closed
+    assert_eq!(
+        env.lamports(&token_account(
+            &env.authority,
+            &receipt_mint,
+            &token_2022::ID
+        )),
+        0
+    );
+
+    // proceeds follow the receipt
+    ok(env.accept(&escrow, false));
+    assert_eq!(env.balance(&new_authority, &env.mint.clone()), 10 * TOKEN);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn only_the_receipt_holder_closes() {
+    let mut env = Env::new();
+    let escrow = created(env.init("holder_closes", sol_for_tokens(&env)));
+    let new_authority = env.wallet();
+    ok(env.transfer_authority(&escrow, &new_authority));
+    let account = env.escrow(&escrow);
+
+    let authority = env.authority;
+    env.empty_receipt_account(&escrow, &authority);
+    assert_error(
+        env.close_as(&authority, &account),
+        EscrowError::NotReceiptHolder,
+    );
+
+    let before = env.lamports(&new_authority);
+    ok(env.close_as(&new_authority, &account));
+    assert!(env.lamports(&new_authority) > before + SOL);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn transfer_authority_to_the_receiver_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("to_receiver", sol_for_tokens(&env)));
+
+    assert_error(
+        env.transfer_authority(&escrow, &env.receiver.clone()),
+        EscrowError::SameBuyerSellerNotAllowed,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn transfer_authority_of_a_funded_escrow() {
+    let mut env = Env::new();
+    let escrow = created(env.init("transfer_funded", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+    let new_authority = env.wallet();
+
+    ok(env.transfer_authority(&escrow, &new_authority));
+    assert_state(&env, &escrow, EscrowState::Funded);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn transfer_authority_of_a_completed_escrow_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("transfer_completed", sol_for_tokens(&env)));
+    let account = env.escrow(&escrow);
+    ok(env.accept(&escrow, false));
+    let new_authority = env.wallet();
+
+    // built from the escrow as it was, the receipt is gone now
+    let instruction = without_mint(
+        instructions::transfer_authority(&account, &env.authority.clone(), &new_authority),
+        &receipt_mint_address(&escrow).0,
+        &token_2022::ID,
+        &[env.authority, new_authority],
+    );
+    assert_error(
+        env.send(instruction, &[&env.authority.clone()]),
+        EscrowError::InvalidState,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn transfer_authority_without_the_receipt_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("transfer_no_receipt", sol_for_tokens(&env)));
+    let new_authority = env.wallet();
+    let instruction = instructions::transfer_authority(
+        &env.escrow(&escrow),
+        &env.authority.clone(),
+        &new_authority,
+    );
+
+    assert_error(
+        env.send(
+            without_mint(
+                instruction,
+                &receipt_mint_address(&escrow).0,
+                &token_2022::ID,
+                &[env.authority, new_authority],
+            ),
+            &[&env.authority.clone()],
+        ),
+        EscrowError::MissingReceipt,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_pays_the_receipt_holder_only() {
+    let mut env = Env::new();
+    let escrow = created(env.init("pay_holder", sol_for_tokens(&env)));
+    let new_authority = env.wallet();
+    ok(env.transfer_authority(&escrow, &new_authority));
+
+    // the old authority is still named, but no longer holds the receipt
+    env.empty_receipt_account(&escrow, &env.authority.clone());
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    assert_error(
+        env.send(instruction, &[&env.receiver.clone()]),
+        EscrowError::NotReceiptHolder,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn closing_reclaims_the_receipt_account() {
+    let mut env = Env::new();
+    let escrow = created(env.init("close_receipt", sol_for_tokens(&env)));
+    let receipt_account = token_account(
+        &env.authority,
+        &receipt_mint_address(&escrow).0,
+        &token_2022::ID,
+    );
+    let rent = env.lamports(&receipt_account);
+    assert!(rent > 0);
+
+    let outcome = ok(env.close(&escrow));
+    assert_eq!(env.lamports(&receipt_account), 0);
+    let [.., EscrowEvent::Closed(closed)] = outcome.events.as_slice() else {
+        panic!("expected EscrowClosed, got {:?}", outcome.events);
+    };
+    assert!(closed.reclaimed_lamports >= rent);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn the_holder_reclaims_its_receipt_account_after_accept() {
+    let mut env = Env::new();
+    let escrow = created(env.init("accept_receipt", sol_for_tokens(&env)));
+    let authority = env.authority;
+    let receipt_account = token_account(
+        &authority,
+        &receipt_mint_address(&escrow).0,
+        &token_2022::ID,
+    );
+
+    // the receiver signs accept, the holder's account can only be burnt empty
+    ok(env.accept(&escrow, false));
+    assert_eq!(env.token_balance(&receipt_account), 0);
+
+    // and its owner closes it with the token program
+    let rent = env.lamports(&receipt_account);
+    let before = env.lamports(&authority);
+    let close = spl_token_2022::instruction::close_account(
+        &token_2022::ID,
+        &receipt_account,
+        &authority,
+        &authority,
+        &[],
+    )
+    .unwrap();
+    ok(env.send(close, &[&authority]));
+    assert_eq!(env.lamports(&receipt_account), 0);
+    assert_eq!(env.lamports(&authority), before + rent);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_without_the_receipt_fails() {
+    let mut env = Env::new();
+    let deal = Deal::new(Asset::Native, SOL, env.spl_token(), 10 * TOKEN);
+    let escrow = created(env.init("no_receipt_program", deal));
+    let instruction =
+        instructions::accept(&env.escrow(&escrow), &env.authority.clone(), false, None);
+
+    // every receipt account left out, the escrow would complete with the
+    // receipt still out there
+    let instruction = without(
+        without_mint(
+            instruction,
+            &receipt_mint_address(&escrow).0,
+            &token_2022::ID,
+            &[env.authority],
+        ),
+        &token_2022::ID,
+    );
+    assert_error(
+        env.send(instruction, &[&env.receiver.clone()]),
+        EscrowError::MissingReceipt,
+    );
+    assert_state(&env, &escrow, EscrowState::Active);
+}
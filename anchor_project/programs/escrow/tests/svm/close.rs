@@ -0,0 +1,231 @@
+use anchor_spl::token_2022;
+use escrow::{decode::EscrowEvent, errors::EscrowError, state::DEFAULT_CHALLENGE_WINDOW};
+use escrow_client::{
+    instructions,
+    pda::{receipt_mint_address, token_account},
+    Asset, BondTerms, EscrowOptions, EscrowState,
+};
+
+use crate::setup::*;
+
+fn sol_for_tokens(env: &Env) -> Deal {
+    Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN)
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_refunds_the_deposit() {
+    let mut env = Env::new();
+    let before = env.lamports(&env.authority.clone());
+    let escrow = created(env.init("close", sol_for_tokens(&env)));
+
+    let outcome = ok(env.close(&escrow));
+    // every lamport the authority put in comes back, but for the emptied
+    // receipt account which is the holder's to close
+    let (receipt_mint, _) = receipt_mint_address(&escrow);
+    let receipt_account = token_account(&env.authority, &receipt_mint, &token_2022::ID);
+    assert_eq!(env.token_balance(&receipt_account), 0);
+    assert_eq!(
+        env.lamports(&env.authority.clone()) + env.lamports(&receipt_account),
+        before
+    );
+    assert!(env.svm.account(&escrow).is_none());
+    assert!(env.svm.account(&receipt_mint).is_none());
+    match outcome.events.as_slice() {
+        [EscrowEvent::Closed(event)] => {
+            assert_eq!(event.previous_state, EscrowState::Active);
+            assert_eq!(event.refunded_deposit, SOL);
+        }
+        events => panic!("unexpected events {events:?}"),
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_refunds_a_token_deposit() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "close_tokens",
+        Deal::new(env.token_2022(), 5 * TOKEN, Asset::Native, SOL),
+    ));
+
+    ok(env.close(&escrow));
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1000 * TOKEN
+    );
+    assert!(env
+        .svm
+        .account(&token_account(&escrow, &env.mint, &token_2022::ID))
+        .is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_a_completed_escrow_reclaims_its_rent() {
+    let mut env = Env::new();
+    let escrow = created(env.init("close_completed", sol_for_tokens(&env)));
+    ok(env.accept(&escrow, false));
+    let before = env.lamports(&env.authority.clone());
+    let rent = env.lamports(&escrow);
+
+    ok(env.close(&escrow));
+    assert_eq!(env.lamports(&env.authority.clone()), before + rent);
+    assert!(env.svm.account(&escrow).is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_funded_refunds_both_legs() {
+    let mut env = Env::new();
+    let escrow = created(env.init("close_funded", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+
+    ok(env.close(&escrow));
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        1000 * TOKEN
+    );
+    assert!(env.svm.account(&escrow).is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_funded_after_the_challenge_window_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("close_late", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+    env.svm.warp(DEFAULT_CHALLENGE_WINDOW);
+
+    assert_error(env.close(&escrow), EscrowError::ChallengeWindowOver);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn receiver_cannot_back_out_of_a_funded_escrow() {
+    let mut env = Env::new();
+    let escrow = created(env.init("receiver_backs_out", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+    let receiver = env.receiver;
+
+    // funding is the receiver accepting, the window is the authority's alone
+    assert_error(
+        env.close_as(&receiver, &env.escrow(&escrow)),
+        EscrowError::NotReceiptHolder,
+    );
+    assert_state(&env, &escrow, EscrowState::Funded);
+    assert_eq!(env.balance(&receiver, &env.mint.clone()), 990 * TOKEN);
+
+    env.svm.warp(DEFAULT_CHALLENGE_WINDOW);
+    ok(env.settle(&escrow, &receiver));
+    assert_state(&env, &escrow, EscrowState::Completed);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_funded_without_the_receive_vault_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("close_no_vault", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+    let instruction = instructions::close(&env.escrow(&escrow), &env.authority);
+    let receive_vault = token_account(&escrow, &env.mint, &token_2022::ID);
+
+    assert_error(
+        env.send(
+            without(instruction, &receive_vault),
+            &[&env.authority.clone()],
+        ),
+        EscrowError::MissingBondVault,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_refunds_a_funded_counter() {
+    let mut env = Env::new();
+    let escrow = created(env.init("close_countered", sol_for_tokens(&env)));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, true, false));
+
+    ok(env.close(&escrow));
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        1000 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn committed_offer_closes_once_the_commit_is_over() {
+    let mut env = Env::new();
+    let deal = sol_for_tokens(&env).with(EscrowOptions {
+        bond: Some(BondTerms {
+            amount: TOKEN,
+            window: 60,
+        }),
+        ..no_options()
+    });
+    let escrow = created(env.init("close_committed", deal));
+    ok(env.commit(&escrow, false));
+
+    assert_error(env.close(&escrow), EscrowError::OfferCommitted);
+
+    // the bond is forfeited to the authority
+    env.svm.warp(60);
+    ok(env.close(&escrow));
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1001 * TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        999 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_with_the_wrong_deposit_mint_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "close_wrong_mint",
+        Deal::new(env.token_2022(), TOKEN, Asset::Native, SOL),
+    ));
+    let other_mint = env.create_mint(&token_2022::ID);
+    let instruction = instructions::close(&env.escrow(&escrow), &env.authority);
+
+    assert_error(
+        env.send(
+            swap_mint(
+                instruction,
+                &env.mint.clone(),
+                &other_mint,
+                &token_2022::ID,
+                &[escrow, env.authority],
+            ),
+            &[&env.authority.clone()],
+        ),
+        EscrowError::InvalidDepositMint,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_without_the_receipt_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("close_no_receipt", sol_for_tokens(&env)));
+    let instruction = instructions::close(&env.escrow(&escrow), &env.authority);
+    let (receipt_mint, _) = receipt_mint_address(&escrow);
+
+    assert_error(
+        env.send(
+            without_mint(
+                instruction,
+                &receipt_mint,
+                &token_2022::ID,
+                &[env.authority],
+            ),
+            &[&env.authority.clone()],
+        ),
+        EscrowError::MissingReceipt,
+    );
+}
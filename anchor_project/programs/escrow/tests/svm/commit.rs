@@ -0,0 +1,221 @@
+use anchor_spl::token_2022;
+use escrow::{decode::EscrowEvent, errors::EscrowError};
+use escrow_client::{
+    instructions, pda::token_account, Asset, BondTerms, EscrowOptions, EscrowState,
+};
+
+use crate::setup::*;
+
+const WINDOW: i64 = 60 * 60;
+
+// SOL for 10 tokens, committing takes a 1 token bond for an hour
+fn bonded(env: &Env) -> Deal {
+    Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN).with(EscrowOptions {
+        bond: Some(BondTerms {
+            amount: TOKEN,
+            window: WINDOW,
+        }),
+        ..no_options()
+    })
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_holds_the_bond() {
+    let mut env = Env::new();
+    let escrow = created(env.init("commit", bonded(&env)));
+
+    let outcome = ok(env.commit(&escrow, false));
+    let account = env.escrow(&escrow);
+    assert_eq!(account.state, EscrowState::Active);
+    assert_eq!(
+        account.committed_until,
+        env.svm.clock().unix_timestamp + WINDOW
+    );
+    assert_eq!(
+        env.token_balance(&token_account(&escrow, &env.mint, &token_2022::ID)),
+        TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        999 * TOKEN
+    );
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::Committed(_)]
+    ));
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_holds_a_sol_bond_on_the_escrow() {
+    let mut env = Env::new();
+    let deal = Deal::new(env.token_2022(), TOKEN, Asset::Native, 2 * SOL).with(EscrowOptions {
+        bond: Some(BondTerms {
+            amount: SOL / 10,
+            window: WINDOW,
+        }),
+        ..no_options()
+    });
+    let escrow = created(env.init("sol_bond", deal));
+    let before = env.lamports(&escrow);
+
+    ok(env.commit(&escrow, false));
+    assert_eq!(env.lamports(&escrow), before + SOL / 10);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_refunds_the_bond() {
+    let mut env = Env::new();
+    let escrow = created(env.init("accept_committed", bonded(&env)));
+    ok(env.commit(&escrow, false));
+
+    ok(env.accept(&escrow, false));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        990 * TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1010 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_after_the_window_forfeits_the_bond() {
+    let mut env = Env::new();
+    let escrow = created(env.init("accept_lapsed", bonded(&env)));
+    ok(env.commit(&escrow, false));
+    env.svm.warp(WINDOW);
+
+    let outcome = ok(env.accept(&escrow, false));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        989 * TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1011 * TOKEN
+    );
+    let [EscrowEvent::Completed(completed)] = outcome.events.as_slice() else {
+        panic!("expected EscrowCompleted, got {:?}", outcome.events);
+    };
+    assert_eq!(completed.refunded_bond, 0);
+    assert_eq!(completed.forfeited_bond, TOKEN);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_without_the_bond_vault_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("no_bond_vault", bonded(&env)));
+    ok(env.commit(&escrow, false));
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    let bond_vault = token_account(&escrow, &env.mint, &token_2022::ID);
+
+    // the bond vault is the only account of the escrow for that mint
+    assert_error(
+        env.send(without(instruction, &bond_vault), &[&env.receiver.clone()]),
+        EscrowError::MissingBondVault,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_without_a_bond_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "no_bond",
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+    ));
+
+    assert_error(env.commit(&escrow, false), EscrowError::CommitNotOffered);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_twice_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("commit_twice", bonded(&env)));
+    ok(env.commit(&escrow, false));
+
+    assert_error(env.commit(&escrow, false), EscrowError::AlreadyCommitted);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_to_a_completed_escrow_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("commit_completed", bonded(&env)));
+    ok(env.accept(&escrow, false));
+
+    assert_error(env.commit(&escrow, false), EscrowError::InvalidState);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_over_a_funded_counter_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("commit_countered", bonded(&env)));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, true, false));
+
+    assert_error(env.commit(&escrow, false), EscrowError::CounterPending);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_window_past_the_clock_overflows() {
+    let mut env = Env::new();
+    let deal = Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN).with(EscrowOptions {
+        bond: Some(BondTerms {
+            amount: TOKEN,
+            window: i64::MAX,
+        }),
+        ..no_options()
+    });
+    let escrow = created(env.init("commit_overflow", deal));
+
+    assert_error(env.commit(&escrow, false), EscrowError::OverFlow);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_without_the_receive_mint_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("commit_no_mint", bonded(&env)));
+    let instruction = instructions::commit(&env.escrow(&escrow), false);
+
+    assert_error(
+        env.send(
+            without_mint(
+                instruction,
+                &env.mint.clone(),
+                &token_2022::ID,
+                &[escrow, env.receiver],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidReceiveMint,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn commit_against_another_authority_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("commit_stranger", bonded(&env)));
+    let stranger = env.wallet();
+    let instruction = instructions::commit(&env.escrow(&escrow), false);
+
+    assert_error(
+        env.send(
+            swap_account(instruction, &env.authority.clone(), &stranger),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::UnauthorizedSigner,
+    );
+}
@@ -0,0 +1,191 @@
+use anchor_spl::token_2022;
+use escrow::{decode::EscrowEvent, errors::EscrowError};
+use escrow_client::{
+    instructions, pda::token_account, Asset, BondTerms, EscrowOptions, EscrowState,
+};
+
+use crate::setup::*;
+
+fn sol_for_tokens(env: &Env) -> Deal {
+    Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN)
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accepted_counter_becomes_the_terms() {
+    let mut env = Env::new();
+    let escrow = created(env.init("counter", sol_for_tokens(&env)));
+
+    let outcome = ok(env.counter_offer(&escrow, 8 * TOKEN, false, false));
+    assert_eq!(env.escrow(&escrow).counter_amount, 8 * TOKEN);
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::CounterOffered(_)]
+    ));
+
+    let outcome = ok(env.accept_counter(&escrow));
+    let account = env.escrow(&escrow);
+    assert_eq!(account.state, EscrowState::Active);
+    assert_eq!(account.receive_amount, 8 * TOKEN);
+    assert_eq!(account.counter_amount, 0);
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::CounterAccepted(_)]
+    ));
+
+    ok(env.accept(&escrow, false));
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1008 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accepted_funded_counter_settles() {
+    let mut env = Env::new();
+    let escrow = created(env.init("funded_counter", sol_for_tokens(&env)));
+    let receiver_lamports = env.lamports(&env.receiver.clone());
+
+    ok(env.counter_offer(&escrow, 8 * TOKEN, true, false));
+    assert!(env.escrow(&escrow).counter_funded);
+    assert_eq!(
+        env.token_balance(&token_account(&escrow, &env.mint, &token_2022::ID)),
+        8 * TOKEN
+    );
+
+    let outcome = ok(env.accept_counter(&escrow));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1008 * TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        992 * TOKEN
+    );
+    assert!(env.lamports(&env.receiver.clone()) > receiver_lamports + SOL / 2);
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::CounterAccepted(_), EscrowEvent::Completed(_)] | [EscrowEvent::Completed(_)]
+    ));
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn new_counter_refunds_the_funded_one() {
+    let mut env = Env::new();
+    let escrow = created(env.init("recounter", sol_for_tokens(&env)));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, true, false));
+
+    ok(env.counter_offer(&escrow, 6 * TOKEN, false, false));
+    let account = env.escrow(&escrow);
+    assert_eq!(account.counter_amount, 6 * TOKEN);
+    assert!(!account.counter_funded);
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        1000 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_counter_after_the_receiver_replaced_it_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("replaced_counter", sol_for_tokens(&env)));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, false, false));
+
+    // the receiver gets a worse counter in ahead of the authority's accept
+    ok(env.counter_offer(&escrow, 6 * TOKEN, false, false));
+
+    assert_error(
+        env.accept_counter_of(&escrow, 8 * TOKEN),
+        EscrowError::CounterChanged,
+    );
+    let account = env.escrow(&escrow);
+    assert_eq!(account.receive_amount, 10 * TOKEN);
+    assert_eq!(account.counter_amount, 6 * TOKEN);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_counter_without_one_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("no_counter", sol_for_tokens(&env)));
+
+    assert_error(env.accept_counter(&escrow), EscrowError::NoCounterOffer);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_counter_on_a_completed_escrow_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("counter_completed", sol_for_tokens(&env)));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, false, false));
+    ok(env.accept(&escrow, false));
+
+    assert_error(env.accept_counter(&escrow), EscrowError::InvalidState);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn zero_counter_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("zero_counter", sol_for_tokens(&env)));
+
+    assert_error(
+        env.counter_offer(&escrow, 0, false, false),
+        EscrowError::ReceiveAmountLow,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn counter_on_a_committed_offer_fails() {
+    let mut env = Env::new();
+    let deal = sol_for_tokens(&env).with(EscrowOptions {
+        bond: Some(BondTerms {
+            amount: TOKEN,
+            window: 60,
+        }),
+        ..no_options()
+    });
+    let escrow = created(env.init("counter_committed", deal));
+    ok(env.commit(&escrow, false));
+
+    assert_error(
+        env.counter_offer(&escrow, 8 * TOKEN, false, false),
+        EscrowError::OfferCommitted,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_over_a_funded_counter_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("accept_countered", sol_for_tokens(&env)));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, true, false));
+
+    assert_error(env.accept(&escrow, false), EscrowError::CounterPending);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn funded_counter_without_the_receive_mint_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("counter_no_mint", sol_for_tokens(&env)));
+    let instruction = instructions::counter_offer(&env.escrow(&escrow), 8 * TOKEN, true, false);
+
+    assert_error(
+        env.send(
+            without_mint(
+                instruction,
+                &env.mint.clone(),
+                &token_2022::ID,
+                &[escrow, env.receiver],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidReceiveMint,
+    );
+}
@@ -0,0 +1,107 @@
+// Account substitution against accept, close and settle, see attack.rs. The
+// random runs here are a smoke test, fuzz/ searches properly
+use arbitrary::{Arbitrary, Unstructured};
+use escrow_client::EscrowState;
+use proptest::prelude::*;
+
+use crate::{attack::*, setup::*};
+
+// Slots by their position in the accounts structs
+const ACCEPT_ESCROW_AUTHORITY: u8 = 1;
+const CLOSE_SOL_VAULT: u8 = 3;
+const CLOSE_TOKEN_VAULT: u8 = 5;
+
+fn attack(kind: Kind, target: u8) -> Attack {
+    Attack {
+        kind,
+        target,
+        substitutions: Vec::new(),
+        extra: Vec::new(),
+        data: Data::Keep,
+        warp: 0,
+    }
+}
+
+fn substitute(slot: u8, account: u8) -> Substitution {
+    Substitution {
+        slot,
+        account,
+        signer: false,
+        writable: true,
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn paying_for_the_own_deal_goes_through() {
+    let mut harness = Harness::new();
+    let escrow = harness.escrow(ATTACKER_RECEIVES);
+
+    harness.attack(&attack(Kind::Accept, ATTACKER_RECEIVES));
+    assert_state(&harness.env, &escrow, EscrowState::Completed);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn victims_escrows_refuse_the_attacker() {
+    let mut harness = Harness::new();
+    for target in [VICTIM_SOL, VICTIM_FUNDED, VICTIM_LEGACY] {
+        for kind in [Kind::Accept, Kind::Close, Kind::Settle] {
+            harness.attack(&Attack {
+                // past every challenge window
+                warp: u8::MAX,
+                ..attack(kind, target)
+            });
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn closing_the_own_escrow_onto_victims_vaults_fails() {
+    let mut harness = Harness::new();
+    let legacy_vault =
+        harness.account(&escrow_client::pda::sol_vault_address(&harness.escrow(VICTIM_LEGACY)).0);
+    let funded_vault = harness.account(&escrow_client::pda::token_account(
+        &harness.escrow(VICTIM_FUNDED),
+        &harness.env.mint,
+        &anchor_spl::token_2022::ID,
+    ));
+
+    harness.attack(&Attack {
+        substitutions: vec![substitute(CLOSE_SOL_VAULT, legacy_vault)],
+        ..attack(Kind::Close, ATTACKER_OWN)
+    });
+    harness.attack(&Attack {
+        substitutions: vec![substitute(CLOSE_TOKEN_VAULT, funded_vault)],
+        ..attack(Kind::Close, ATTACKER_OWN)
+    });
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn redirecting_the_proceeds_of_the_own_deal_fails() {
+    let mut harness = Harness::new();
+    let attacker = harness.account(&harness.attacker);
+
+    harness.attack(&Attack {
+        substitutions: vec![substitute(ACCEPT_ESCROW_AUTHORITY, attacker)],
+        ..attack(Kind::Accept, ATTACKER_RECEIVES)
+    });
+    assert_state(
+        &harness.env,
+        &harness.escrow(ATTACKER_RECEIVES),
+        EscrowState::Active,
+    );
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    #[ignore = "needs target/deploy/escrow.so"]
+    fn random_attacks_move_no_victim_funds(bytes in prop::collection::vec(any::<u8>(), 0..2048)) {
+        let attacks = Vec::<Attack>::arbitrary_take_rest(Unstructured::new(&bytes)).unwrap_or_default();
+        Harness::new().run(&attacks);
+    }
+}
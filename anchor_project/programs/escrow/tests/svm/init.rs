@@ -0,0 +1,383 @@
+use anchor_lang::prelude::Pubkey;
+use escrow::{decode::EscrowEvent, errors::EscrowError};
+use escrow_client::{
+    instructions::InitEscrow,
+    pda::{receipt_mint_address, token_account},
+    Asset, BondTerms, EscrowState,
+};
+
+use crate::setup::*;
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_holds_sol_deposit_on_the_escrow() {
+    let mut env = Env::new();
+    let before = env.lamports(&env.authority.clone());
+
+    let escrow = created(env.init(
+        "sol_deposit",
+        Deal::new(Asset::Native, 2 * SOL, env.token_2022(), 10 * TOKEN),
+    ));
+
+    let account = env.escrow(&escrow);
+    assert_eq!(account.state, EscrowState::Active);
+    assert_eq!(account.version, escrow::state::ESCROW_VERSION);
+    assert_eq!(account.escrow_authority, env.authority);
+    assert_eq!(account.seed_authority, env.authority);
+    assert!(account.has_receipt);
+    assert!(env.lamports(&escrow) > 2 * SOL);
+    assert!(env.lamports(&env.authority.clone()) < before - 2 * SOL);
+
+    // the authority holds the receipt
+    let (receipt_mint, _) = receipt_mint_address(&escrow);
+    assert_eq!(
+        env.token_balance(&token_account(
+            &env.authority.clone(),
+            &receipt_mint,
+            &anchor_spl::token_2022::ID
+        )),
+        1
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_holds_token_deposit_in_the_escrow_ata() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "token_deposit",
+        Deal::new(env.spl_token(), 5 * TOKEN, Asset::Native, SOL),
+    ));
+
+    let vault = token_account(&escrow, &env.spl_mint, &anchor_spl::token::ID);
+    assert_eq!(env.token_balance(&vault), 5 * TOKEN);
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.spl_mint.clone()),
+        995 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_emits_created() {
+    let mut env = Env::new();
+    let instruction = InitEscrow {
+        escrow_id: "event".to_string(),
+        escrow_authority: env.authority,
+        receiver: env.receiver,
+        deposit: Asset::Native,
+        deposit_amount: SOL,
+        receive: env.token_2022(),
+        receive_amount: TOKEN,
+        options: no_options(),
+    }
+    .instruction();
+
+    let outcome = ok(env.send(instruction, &[&env.authority.clone()]));
+    assert_eq!(
+        outcome.logs.first(),
+        Some(&format!("Program {} invoke [1]", escrow::ID))
+    );
+    assert_eq!(
+        outcome.logs.last(),
+        Some(&format!("Program {} success", escrow::ID))
+    );
+    // after every transfer, as the last CPI
+    assert_eq!(
+        outcome.inner_instructions.last().map(|ix| ix.program_id),
+        Some(escrow::ID)
+    );
+    match outcome.events.as_slice() {
+        [EscrowEvent::Created(event)] => {
+            assert_eq!(event.escrow_id, "event");
+            assert_eq!(event.deposit_amount, SOL);
+        }
+        events => panic!("unexpected events {events:?}"),
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_stores_wsol_as_native() {
+    let mut env = Env::new();
+    let wsol = Asset::Token2022 {
+        mint: anchor_spl::token_2022::spl_token_2022::native_mint::ID,
+    };
+    env.wrap_sol(&env.authority.clone(), &anchor_spl::token_2022::ID, 3 * SOL);
+
+    let escrow = created(env.init(
+        "wsol_deposit",
+        Deal::new(wsol, 2 * SOL, env.token_2022(), TOKEN),
+    ));
+
+    let account = env.escrow(&escrow);
+    assert_eq!(account.deposit, Asset::Native);
+    assert!(account.authority_wrapped_sol);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_same_authority_and_receiver() {
+    let mut env = Env::new();
+    let authority = env.authority;
+    assert_error(
+        env.init_by(
+            &authority,
+            &authority,
+            "same_party",
+            Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        ),
+        EscrowError::SameBuyerSellerNotAllowed,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_zero_amounts() {
+    let mut env = Env::new();
+    assert_error(
+        env.init(
+            "no_deposit",
+            Deal::new(Asset::Native, 0, env.token_2022(), TOKEN),
+        ),
+        EscrowError::DepositAmountLow,
+    );
+    assert_error(
+        env.init(
+            "no_receive",
+            Deal::new(Asset::Native, SOL, env.token_2022(), 0),
+        ),
+        EscrowError::ReceiveAmountLow,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_same_asset_on_both_legs() {
+    let mut env = Env::new();
+    assert_error(
+        env.init(
+            "same_token",
+            Deal::new(env.token_2022(), TOKEN, env.token_2022(), TOKEN),
+        ),
+        EscrowError::SameTokenTransferNotAllowed,
+    );
+
+    // wSOL is native SOL
+    let wsol = Asset::SplToken {
+        mint: anchor_spl::token::spl_token::native_mint::ID,
+        program: anchor_spl::token::ID,
+    };
+    assert_error(
+        env.init("sol_for_wsol", Deal::new(Asset::Native, SOL, wsol, SOL)),
+        EscrowError::SameTokenTransferNotAllowed,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_empty_id() {
+    let mut env = Env::new();
+    assert_error(
+        env.init("", Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN)),
+        EscrowError::IdTooShort,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_spl_leg_under_token_2022() {
+    let mut env = Env::new();
+    let asset = Asset::SplToken {
+        mint: env.mint,
+        program: anchor_spl::token_2022::ID,
+    };
+    assert_error(
+        env.init("wrong_program", Deal::new(asset, TOKEN, Asset::Native, SOL)),
+        EscrowError::InvalidTokenProgram,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_legs_on_different_token_programs() {
+    let mut env = Env::new();
+    assert_error(
+        env.init(
+            "spl_for_2022",
+            Deal::new(env.spl_token(), TOKEN, env.token_2022(), TOKEN),
+        ),
+        EscrowError::InvalidTokenProgram,
+    );
+    assert_error(
+        env.init(
+            "2022_for_spl",
+            Deal::new(env.token_2022(), TOKEN, env.spl_token(), TOKEN),
+        ),
+        EscrowError::InvalidTokenProgram,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_deposit_above_balance() {
+    let mut env = Env::new();
+    assert_error(
+        env.init(
+            "too_much",
+            Deal::new(env.token_2022(), 1001 * TOKEN, Asset::Native, SOL),
+        ),
+        EscrowError::InsufficientBalance,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_token_deposit_without_token_account() {
+    let mut env = Env::new();
+    let instruction = InitEscrow {
+        escrow_id: "no_ata".to_string(),
+        escrow_authority: env.authority,
+        receiver: env.receiver,
+        deposit: env.token_2022(),
+        deposit_amount: TOKEN,
+        receive: Asset::Native,
+        receive_amount: SOL,
+        options: no_options(),
+    }
+    .instruction();
+    let authority_ata = token_account(&env.authority, &env.mint, &anchor_spl::token_2022::ID);
+
+    assert_error(
+        env.send(
+            without(instruction, &authority_ata),
+            &[&env.authority.clone()],
+        ),
+        EscrowError::InvalidTokenAccount,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_deposit_mint_of_another_leg() {
+    let mut env = Env::new();
+    let other_mint = env.create_mint(&anchor_spl::token_2022::ID);
+    let authority = env.authority;
+    let instruction = InitEscrow {
+        escrow_id: "wrong_mint".to_string(),
+        escrow_authority: authority,
+        receiver: env.receiver,
+        deposit: env.token_2022(),
+        deposit_amount: TOKEN,
+        receive: Asset::Native,
+        receive_amount: SOL,
+        options: no_options(),
+    }
+    .instruction();
+
+    // the mint and both its token accounts point at a different mint
+    let escrow = env.address("wrong_mint");
+    let token_2022 = anchor_spl::token_2022::ID;
+    let instruction = swap_account(instruction, &env.mint, &other_mint);
+    let instruction = swap_account(
+        instruction,
+        &token_account(&escrow, &env.mint, &token_2022),
+        &token_account(&escrow, &other_mint, &token_2022),
+    );
+    let instruction = swap_account(
+        instruction,
+        &token_account(&authority, &env.mint, &token_2022),
+        &token_account(&authority, &other_mint, &token_2022),
+    );
+
+    assert_error(
+        env.send(instruction, &[&authority]),
+        EscrowError::InvalidDepositMint,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_bad_bond_terms() {
+    let mut env = Env::new();
+    for (id, bond) in [
+        (
+            "no_bond",
+            BondTerms {
+                amount: 0,
+                window: 60,
+            },
+        ),
+        (
+            "no_window",
+            BondTerms {
+                amount: TOKEN,
+                window: 0,
+            },
+        ),
+    ] {
+        let deal = Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN).with(
+            escrow_client::EscrowOptions {
+                bond: Some(bond),
+                ..no_options()
+            },
+        );
+        assert_error(env.init(id, deal), EscrowError::InvalidBondTerms);
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_rejects_negative_challenge_window() {
+    let mut env = Env::new();
+    let deal =
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN).with(escrow_client::EscrowOptions {
+            challenge_window: Some(-1),
+            ..no_options()
+        });
+    assert_error(
+        env.init("negative_window", deal),
+        EscrowError::InvalidChallengeWindow,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_twice_fails() {
+    let mut env = Env::new();
+    let deal = Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN);
+    created(env.init("twice", deal.clone()));
+
+    // the system program refuses to create the escrow account again
+    assert!(env.init("twice", deal).is_err());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_again_after_close() {
+    let mut env = Env::new();
+    let deal = Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN);
+    let escrow = created(env.init("again", deal.clone()));
+    ok(env.close(&escrow));
+
+    // the authority still has the empty receipt account from the first round
+    assert_eq!(created(env.init("again", deal)), escrow);
+    assert_state(&env, &escrow, EscrowState::Active);
+}
+
+// The escrow address is derived from the id, anything longer than a seed can
+// be never gets as far as the IdTooLong check
+#[test]
+fn id_longer_than_a_seed_has_no_address() {
+    let id = "x".repeat(escrow::state::ESCROW_ID_LENGTH + 1);
+    assert!(Pubkey::try_find_program_address(
+        &[
+            escrow::state::ESCROW_SEED.as_bytes(),
+            id.as_bytes(),
+            Pubkey::new_unique().as_ref(),
+            Pubkey::new_unique().as_ref(),
+        ],
+        &escrow::ID,
+    )
+    .is_none());
+}
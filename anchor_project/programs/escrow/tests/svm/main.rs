@@ -0,0 +1,26 @@
+// Program tests that need no validator: the SBF build runs in LiteSVM
+// together with the token and ATA programs it calls, see runtime.rs.
+//
+// A plain `cargo test` skips every test that needs that build, run them with
+// `anchor build && cargo test -- --ignored`
+//
+// Every EscrowError is raised by some test here except EscrowNotActive,
+// which nothing raises any more, and IdTooLong: an id past 32 bytes can't be
+// an escrow seed, so deriving the address fails before the check
+mod runtime;
+mod setup;
+
+mod accept;
+mod attack;
+mod authority;
+mod close;
+mod commit;
+mod counter;
+mod fuzz;
+mod init;
+mod migrate;
+mod model;
+mod referral;
+mod settle;
+mod solvency;
+mod split;
@@ -0,0 +1,139 @@
+// Upgrades of escrows in the legacy layout
+use escrow::{
+    decode::EscrowEvent,
+    errors::EscrowError,
+    state::{SolCustody, ESCROW_VERSION},
+};
+use escrow_client::{instructions, pda::sol_vault_address, Asset, EscrowState};
+
+use crate::setup::*;
+
+// The sentinel layout escrow and its sol vault, migrated, with a funded
+// authority to sign for it
+fn migrated_legacy_escrow(env: &mut Env) -> anchor_lang::prelude::Pubkey {
+    let escrow = legacy_escrow(env);
+    env.svm.airdrop(&legacy_authority(), 10 * SOL);
+
+    let payer = env.wallet();
+    ok(env.send(instructions::migrate_escrow(&payer, &escrow), &[&payer]));
+    escrow
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn migrate_the_sentinel_layout() {
+    let mut env = Env::new();
+    let escrow = legacy_escrow(&mut env);
+    let payer = env.wallet();
+
+    let outcome = ok(env.send(instructions::migrate_escrow(&payer, &escrow), &[&payer]));
+    let account = env.escrow(&escrow);
+    assert_eq!(account.version, ESCROW_VERSION);
+    assert_eq!(account.escrow_id, "legacy_01");
+    assert_eq!(account.escrow_authority, legacy_authority());
+    assert_eq!(account.seed_authority, legacy_authority());
+    assert_eq!(account.deposit, Asset::Native);
+    assert_eq!(account.deposit_amount, 200_000_000);
+    assert!(account.sol_custody == SolCustody::Vault);
+    assert_eq!(account.state, EscrowState::Active);
+    assert!(!account.has_receipt);
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::Migrated(_)]
+    ));
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn migrate_twice_fails() {
+    let mut env = Env::new();
+    let escrow = migrated_legacy_escrow(&mut env);
+    let payer = env.wallet();
+
+    assert_error(
+        env.send(instructions::migrate_escrow(&payer, &escrow), &[&payer]),
+        EscrowError::AlreadyMigrated,
+    );
+
+    // new escrows are up to date from the start
+    let escrow = created(env.init(
+        "current",
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+    ));
+    assert_error(
+        env.send(instructions::migrate_escrow(&payer, &escrow), &[&payer]),
+        EscrowError::AlreadyMigrated,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn migrate_an_unknown_version_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "from_the_future",
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+    ));
+    let mut account = env.svm.account(&escrow).unwrap();
+    account.data[8] = ESCROW_VERSION + 1;
+    env.set_program_account(&escrow, account.data.clone(), account.lamports);
+    let payer = env.wallet();
+
+    assert_error(
+        env.send(instructions::migrate_escrow(&payer, &escrow), &[&payer]),
+        EscrowError::UnknownVersion,
+    );
+
+    // nor is any size but the legacy one and the current one
+    account.data.truncate(257);
+    env.set_program_account(&escrow, account.data, account.lamports);
+    assert_error(
+        env.send(instructions::migrate_escrow(&payer, &escrow), &[&payer]),
+        EscrowError::UnknownVersion,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_refunds_a_migrated_escrow_from_its_sol_vault() {
+    let mut env = Env::new();
+    let escrow = migrated_legacy_escrow(&mut env);
+    let authority = legacy_authority();
+    let before = env.lamports(&authority);
+    // the deposit and the vault's rent, the escrow only holds its own rent
+    let held = env.lamports(&sol_vault_address(&escrow).0) + env.lamports(&escrow);
+
+    ok(env.close_as(&authority, &env.escrow(&escrow)));
+    assert_eq!(env.lamports(&authority), before + held);
+    assert!(env.svm.account(&sol_vault_address(&escrow).0).is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_a_migrated_escrow_without_its_sol_vault_fails() {
+    let mut env = Env::new();
+    let escrow = migrated_legacy_escrow(&mut env);
+    let authority = legacy_authority();
+    let instruction = instructions::close(&env.escrow(&escrow), &authority);
+
+    assert_error(
+        env.send(
+            without(instruction, &sol_vault_address(&escrow).0),
+            &[&authority],
+        ),
+        EscrowError::MissingSolVault,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn escrow_without_a_receipt_stays_with_its_authority() {
+    let mut env = Env::new();
+    let escrow = migrated_legacy_escrow(&mut env);
+    let stranger = env.wallet();
+
+    assert_error(
+        env.close_as(&stranger, &env.escrow(&escrow)),
+        EscrowError::UnauthorizedSigner,
+    );
+}
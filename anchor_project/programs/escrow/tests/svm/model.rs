@@ -0,0 +1,333 @@
+// Random runs of init, accept and close, mixed with attempts that have to
+// fail, checked step by step against a model of what the program should hold
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token_2022;
+use escrow_client::{
+    instructions,
+    pda::{sol_vault_address, token_account},
+    Asset, Escrow, EscrowState,
+};
+use proptest::prelude::*;
+
+use crate::setup::*;
+
+// few enough ids that runs keep landing on the same escrow
+const IDS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Leg {
+    Sol,
+    Token,
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Init {
+        id: usize,
+        deposit: Leg,
+        deposit_amount: u64,
+        receive: Leg,
+        receive_amount: u64,
+    },
+    Accept(usize),
+    Close(usize),
+    // signed by a wallet that has no part in the escrow
+    AcceptByStranger(usize),
+    CloseByStranger(usize),
+    // the token leg pointed at another mint
+    AcceptWithWrongMint(usize),
+}
+
+fn leg() -> impl Strategy<Value = Leg> {
+    prop_oneof![Just(Leg::Sol), Just(Leg::Token)]
+}
+
+// lamports or token units, zero now and then
+fn amount() -> impl Strategy<Value = u64> {
+    prop_oneof![1 => Just(0), 9 => 1..=20 * TOKEN]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        4 => (0..IDS, leg(), amount(), leg(), amount()).prop_map(
+            |(id, deposit, deposit_amount, receive, receive_amount)| Step::Init {
+                id,
+                deposit,
+                deposit_amount,
+                receive,
+                receive_amount,
+            }
+        ),
+        3 => (0..IDS).prop_map(Step::Accept),
+        2 => (0..IDS).prop_map(Step::Close),
+        1 => (0..IDS).prop_map(Step::AcceptByStranger),
+        1 => (0..IDS).prop_map(Step::CloseByStranger),
+        1 => (0..IDS).prop_map(Step::AcceptWithWrongMint),
+    ]
+}
+
+#[derive(Default)]
+struct Slot {
+    // as last read, kept after a close so later steps can still aim at it
+    escrow: Option<Escrow>,
+    // None before the first init and after a close
+    state: Option<EscrowState>,
+}
+
+struct Harness {
+    env: Env,
+    stranger: Pubkey,
+    decoy_mint: Pubkey,
+    slots: [Slot; IDS],
+    // the test mint held by the authority and the receiver
+    authority_tokens: u64,
+    receiver_tokens: u64,
+    // totals no step may change
+    lamports: u64,
+    tokens: u64,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let mut env = Env::new();
+        let stranger = env.wallet();
+        let decoy_mint = env.create_mint(&token_2022::ID);
+        let (lamports, tokens) = totals(&env);
+
+        Self {
+            env,
+            stranger,
+            decoy_mint,
+            slots: Default::default(),
+            authority_tokens: 1000 * TOKEN,
+            receiver_tokens: 1000 * TOKEN,
+            lamports,
+            tokens,
+        }
+    }
+
+    fn asset(&self, leg: Leg) -> Asset {
+        match leg {
+            Leg::Sol => Asset::Native,
+            Leg::Token => self.env.token_2022(),
+        }
+    }
+
+    fn run(&mut self, step: &Step) {
+        match *step {
+            Step::Init {
+                id,
+                deposit,
+                deposit_amount,
+                receive,
+                receive_amount,
+            } => {
+                let valid = deposit_amount > 0
+                    && receive_amount > 0
+                    && deposit != receive
+                    && self.slots[id].state.is_none();
+                let deal = Deal::new(
+                    self.asset(deposit),
+                    deposit_amount,
+                    self.asset(receive),
+                    receive_amount,
+                );
+
+                let result = self.env.init(&escrow_id(id), deal);
+                expect(step, result.is_ok(), valid);
+                if let Ok(address) = result {
+                    if deposit == Leg::Token {
+                        self.authority_tokens -= deposit_amount;
+                    }
+                    self.slots[id] = Slot {
+                        escrow: Some(self.env.escrow(&address)),
+                        state: Some(EscrowState::Active),
+                    };
+                }
+            }
+            Step::Accept(id) => {
+                let Some(escrow) = self.slots[id].escrow.clone() else {
+                    return;
+                };
+                let instruction = instructions::accept(&escrow, &self.env.authority, false, None);
+                let receiver = self.env.receiver;
+
+                let result = self.env.send(instruction, &[&receiver]);
+                expect(
+                    step,
+                    result.is_ok(),
+                    self.slots[id].state == Some(EscrowState::Active),
+                );
+                if result.is_ok() {
+                    if escrow.deposit.mint().is_some() {
+                        self.receiver_tokens += escrow.deposit_amount;
+                    }
+                    if escrow.receive.mint().is_some() {
+                        self.receiver_tokens -= escrow.receive_amount;
+                        self.authority_tokens += escrow.receive_amount;
+                    }
+                    self.slots[id] = Slot {
+                        escrow: Some(self.env.escrow(&self.env.address(&escrow_id(id)))),
+                        state: Some(EscrowState::Completed),
+                    };
+                }
+            }
+            Step::Close(id) => {
+                let Some(escrow) = self.slots[id].escrow.clone() else {
+                    return;
+                };
+                let authority = self.env.authority;
+
+                let result = self.env.close_as(&authority, &escrow);
+                expect(step, result.is_ok(), self.slots[id].state.is_some());
+                if result.is_ok() {
+                    if escrow.state == EscrowState::Active && escrow.deposit.mint().is_some() {
+                        self.authority_tokens += escrow.deposit_amount;
+                    }
+                    self.slots[id].state = None;
+                }
+            }
+            Step::AcceptByStranger(id) => {
+                let Some(escrow) = &self.slots[id].escrow else {
+                    return;
+                };
+                let instruction = swap_account(
+                    instructions::accept(escrow, &self.env.authority, false, None),
+                    &self.env.receiver,
+                    &self.stranger,
+                );
+                let stranger = self.stranger;
+
+                let result = self.env.send(instruction, &[&stranger]);
+                expect(step, result.is_ok(), false);
+            }
+            Step::CloseByStranger(id) => {
+                let Some(escrow) = self.slots[id].escrow.clone() else {
+                    return;
+                };
+                let stranger = self.stranger;
+
+                let result = self.env.close_as(&stranger, &escrow);
+                expect(step, result.is_ok(), false);
+            }
+            Step::AcceptWithWrongMint(id) => {
+                let Some(escrow) = &self.slots[id].escrow else {
+                    return;
+                };
+                let instruction = swap_account(
+                    instructions::accept(escrow, &self.env.authority, false, None),
+                    &self.env.mint,
+                    &self.decoy_mint,
+                );
+                let receiver = self.env.receiver;
+
+                let result = self.env.send(instruction, &[&receiver]);
+                expect(step, result.is_ok(), false);
+            }
+        }
+    }
+
+    fn check(&self, previous: &[Option<EscrowState>; IDS]) -> [Option<EscrowState>; IDS] {
+        let env = &self.env;
+        assert_eq!(totals(env), (self.lamports, self.tokens), "value conserved");
+        assert_eq!(
+            env.balance(&env.authority, &env.mint),
+            self.authority_tokens
+        );
+        assert_eq!(env.balance(&env.receiver, &env.mint), self.receiver_tokens);
+
+        std::array::from_fn(|id| {
+            let address = env.address(&escrow_id(id));
+            let state = env
+                .svm
+                .account(&address)
+                .map(|_| env.escrow(&address).state);
+            assert_eq!(state, self.slots[id].state, "state of {}", escrow_id(id));
+            assert!(
+                transition_allowed(previous[id], state),
+                "{} went from {:?} to {state:?}",
+                escrow_id(id),
+                previous[id]
+            );
+
+            // an open escrow holds its deposit and nothing else, a settled or
+            // closed one holds nothing past its own rent
+            let (held_lamports, held_tokens) = match (&self.slots[id].escrow, state) {
+                (Some(escrow), Some(EscrowState::Active)) if escrow.deposit.mint().is_some() => {
+                    (0, escrow.deposit_amount)
+                }
+                (Some(escrow), Some(EscrowState::Active)) => (escrow.deposit_amount, 0),
+                _ => (0, 0),
+            };
+            let rent = env.svm.account(&address).map_or(0, |account| {
+                env.svm.rent().minimum_balance(account.data.len())
+            });
+            assert_eq!(env.lamports(&address), rent + held_lamports);
+            assert_eq!(
+                env.token_balance(&token_account(&address, &env.mint, &token_2022::ID)),
+                held_tokens
+            );
+            assert_eq!(env.lamports(&sol_vault_address(&address).0), 0);
+
+            state
+        })
+    }
+}
+
+fn escrow_id(id: usize) -> String {
+    format!("model_{id}")
+}
+
+fn expect(step: &Step, ok: bool, expected: bool) {
+    assert_eq!(
+        ok,
+        expected,
+        "{step:?} should have {}",
+        if expected { "passed" } else { "failed" }
+    );
+}
+
+// Escrows only move forward: Active to Completed, and either of them to
+// Closed, which leaves no account behind. A closed id can be used again
+fn transition_allowed(from: Option<EscrowState>, to: Option<EscrowState>) -> bool {
+    use EscrowState::*;
+    from == to
+        || matches!(
+            (from, to),
+            (None, Some(Active))
+                | (Some(Active), Some(Completed))
+                | (Some(Active | Completed), None)
+        )
+}
+
+// Lamports of every account, and the test mint over every token account
+fn totals(env: &Env) -> (u64, u64) {
+    env.svm
+        .accounts()
+        .fold((0, 0), |(lamports, tokens), (_, account)| {
+            let holds_mint = account.owner == token_2022::ID
+                && account.data.len() >= 165
+                && account.data[..32] == env.mint.to_bytes();
+            let amount = if holds_mint {
+                u64::from_le_bytes(account.data[64..72].try_into().unwrap())
+            } else {
+                0
+            };
+            (lamports + account.lamports, tokens + amount)
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    #[ignore = "needs target/deploy/escrow.so"]
+    fn random_runs_keep_the_invariants(steps in prop::collection::vec(step(), 1..24)) {
+        let mut harness = Harness::new();
+        let mut states = harness.check(&Default::default());
+        for step in &steps {
+            harness.run(step);
+            states = harness.check(&states);
+        }
+    }
+}
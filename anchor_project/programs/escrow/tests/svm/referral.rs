@@ -0,0 +1,276 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token_2022;
+use escrow::{decode::EscrowEvent, errors::EscrowError};
+use escrow_client::{
+    instructions::{self, InitEscrow},
+    pda::{config_address, token_account},
+    Asset, EscrowOptions, Referral,
+};
+
+use crate::setup::*;
+
+fn referred(deal: Deal, referrer: Pubkey, bps: u16) -> Deal {
+    deal.with(EscrowOptions {
+        referral: Some(Referral { referrer, bps }),
+        ..no_options()
+    })
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_config_by_the_upgrade_authority() {
+    let mut env = Env::new();
+    let admin = env.wallet();
+    env.svm.set_upgrade_authority(&admin);
+
+    let outcome = ok(env.send(instructions::init_config(&admin, 250), &[&admin]));
+    let config = env.config().unwrap();
+    assert_eq!(config.admin, admin);
+    assert_eq!(config.max_referral_bps, 250);
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::ConfigUpdated(_)]
+    ));
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn init_config_by_anyone_else_fails() {
+    let mut env = Env::new();
+    let admin = env.wallet();
+    env.svm.set_upgrade_authority(&admin);
+    let stranger = env.wallet();
+
+    assert_error(
+        env.send(instructions::init_config(&stranger, 250), &[&stranger]),
+        EscrowError::UnauthorizedSigner,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn config_caps_at_the_whole_amount() {
+    let mut env = Env::new();
+    let admin = env.wallet();
+    env.svm.set_upgrade_authority(&admin);
+    assert_error(
+        env.send(instructions::init_config(&admin, 10_001), &[&admin]),
+        EscrowError::InvalidConfig,
+    );
+
+    ok(env.send(instructions::init_config(&admin, 10_000), &[&admin]));
+    assert_error(
+        env.send(instructions::update_config(&admin, 10_001, None), &[&admin]),
+        EscrowError::InvalidConfig,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn update_config_by_the_admin() {
+    let mut env = Env::new();
+    let admin = env.configure(100);
+    let next_admin = env.wallet();
+
+    ok(env.send(
+        instructions::update_config(&admin, 50, Some(next_admin)),
+        &[&admin],
+    ));
+    let config = env.config().unwrap();
+    assert_eq!(config.admin, next_admin);
+    assert_eq!(config.max_referral_bps, 50);
+
+    // the old admin is out
+    assert_error(
+        env.send(instructions::update_config(&admin, 80, None), &[&admin]),
+        EscrowError::UnauthorizedSigner,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_pays_the_referrer() {
+    let mut env = Env::new();
+    env.configure(500);
+    let referrer = env.wallet();
+    let before = env.lamports(&referrer);
+    let deal = referred(
+        Deal::new(Asset::Native, 10 * SOL, env.token_2022(), TOKEN),
+        referrer,
+        100,
+    );
+    let escrow = created(env.init("referral", deal));
+
+    let outcome = ok(env.accept(&escrow, false));
+    assert_eq!(env.lamports(&referrer), before + SOL / 10);
+    match outcome.events.as_slice() {
+        [EscrowEvent::Completed(event)] => {
+            assert_eq!(event.referral_fee, SOL / 10);
+            assert_eq!(event.deposit_amount, 10 * SOL - SOL / 10);
+        }
+        events => panic!("unexpected events {events:?}"),
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn settle_pays_the_referrer_in_tokens() {
+    let mut env = Env::new();
+    env.configure(500);
+    let referrer = env.wallet();
+    let deal = Deal::new(env.token_2022(), 10 * TOKEN, Asset::Native, SOL).with(EscrowOptions {
+        referral: Some(Referral { referrer, bps: 250 }),
+        challenge_window: Some(0),
+        ..no_options()
+    });
+    let escrow = created(env.init("token_referral", deal));
+    ok(env.fund(&escrow, false));
+
+    ok(env.settle(&escrow, &env.receiver.clone()));
+    assert_eq!(
+        env.token_balance(&token_account(&referrer, &env.mint, &token_2022::ID)),
+        TOKEN / 4
+    );
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        1000 * TOKEN + 10 * TOKEN - TOKEN / 4
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn referral_above_the_cap_fails() {
+    let mut env = Env::new();
+    env.configure(100);
+    let referrer = env.wallet();
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        referrer,
+        101,
+    );
+
+    assert_error(env.init("too_high", deal), EscrowError::ReferralTooHigh);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn native_fee_below_rent_fails() {
+    let mut env = Env::new();
+    env.configure(100);
+    // no account yet, the fee has to open one
+    let referrer = Pubkey::new_unique();
+
+    // 1 bps of a SOL is short of the rent of an empty account
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        referrer,
+        1,
+    );
+    assert_error(env.init("dust_fee", deal), EscrowError::ReferralBelowRent);
+
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        referrer,
+        10,
+    );
+    let escrow = created(env.init("rent_fee", deal));
+    ok(env.accept(&escrow, false));
+    assert_eq!(env.lamports(&referrer), SOL / 1000);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn invalid_referrals_fail() {
+    let mut env = Env::new();
+    env.configure(100);
+    let referrer = env.wallet();
+
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        referrer,
+        0,
+    );
+    assert_error(env.init("zero_bps", deal), EscrowError::InvalidReferral);
+
+    // the receiver can't refer itself into a cut of its own leg
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        env.receiver,
+        50,
+    );
+    assert_error(
+        env.init("self_referral", deal),
+        EscrowError::InvalidReferral,
+    );
+
+    // nor the authority take back part of what it deposits
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        env.authority,
+        50,
+    );
+    assert_error(
+        env.init("authority_referral", deal),
+        EscrowError::InvalidReferral,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn referral_without_the_config_fails() {
+    let mut env = Env::new();
+    env.configure(100);
+    let referrer = env.wallet();
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        referrer,
+        50,
+    );
+    let instruction = InitEscrow {
+        escrow_id: "no_config".to_string(),
+        escrow_authority: env.authority,
+        receiver: env.receiver,
+        deposit: deal.deposit,
+        deposit_amount: deal.deposit_amount,
+        receive: deal.receive,
+        receive_amount: deal.receive_amount,
+        options: deal.options,
+    }
+    .instruction();
+
+    assert_error(
+        env.send(
+            without(instruction, &config_address().0),
+            &[&env.authority.clone()],
+        ),
+        EscrowError::MissingConfig,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_with_the_wrong_referrer_fails() {
+    let mut env = Env::new();
+    env.configure(100);
+    let referrer = env.wallet();
+    let deal = referred(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        referrer,
+        50,
+    );
+    let escrow = created(env.init("wrong_referrer", deal));
+    let stranger = env.wallet();
+
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    assert_error(
+        env.send(
+            swap_account(instruction.clone(), &referrer, &stranger),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidReferral,
+    );
+    assert_error(
+        env.send(without(instruction, &referrer), &[&env.receiver.clone()]),
+        EscrowError::MissingReferrer,
+    );
+}
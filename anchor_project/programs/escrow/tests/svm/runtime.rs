@@ -0,0 +1,337 @@
+// The escrow program as `anchor build` leaves it, target/deploy/escrow.so,
+// run in LiteSVM next to the token, token-2022 and ATA programs it ships.
+// The program is deployed behind the upgradeable loader like on chain, so
+// init_config finds its ProgramData. Transactions are paid by a bank wallet
+// of their own, the wallets of a test only move what the program moves.
+// Signatures are not verified, a signer missing from `signers` fails the
+// transaction before it runs instead
+
+use std::{fmt, path::PathBuf};
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        instruction::Instruction,
+    },
+};
+use escrow::decode::decode_events;
+use escrow_client::pda::program_data_address;
+use litesvm::{
+    types::{FailedTransactionMetadata, TransactionMetadata},
+    LiteSVM,
+};
+use solana_message::Message;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError as RuntimeError;
+
+pub use escrow::decode::EscrowEvent;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+impl From<solana_account::Account> for Account {
+    fn from(account: solana_account::Account) -> Self {
+        Self {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+        }
+    }
+}
+
+impl From<Account> for solana_account::Account {
+    fn from(account: Account) -> Self {
+        Self {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: u64::MAX,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TransactionError {
+    // index of the failing instruction and its error
+    Instruction(usize, ProgramError),
+    // the account was left with lamports but below rent exemption
+    InsufficientFundsForRent(Pubkey),
+    // rejected by the runtime before or around the instructions
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Instruction(index, error) => write!(f, "instruction {index} failed: {error}"),
+            Self::InsufficientFundsForRent(address) => {
+                write!(f, "{address} left below rent exemption")
+            }
+            Self::Runtime(error) => write!(f, "transaction failed: {error}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    pub error: TransactionError,
+    pub logs: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Outcome {
+    pub logs: Vec<String>,
+    pub events: Vec<EscrowEvent>,
+    // every CPI the transaction made, in order
+    pub inner_instructions: Vec<Instruction>,
+    // used by all its instructions together
+    pub compute_units: u64,
+}
+
+// The bank: LiteSVM, the wallet paying every fee and the clock the tests
+// start from
+pub struct Svm {
+    svm: LiteSVM,
+    bank: Pubkey,
+}
+
+impl Svm {
+    pub fn new() -> Self {
+        // a fresh history per transaction would reject resending the same
+        // unsigned transaction as already processed
+        let mut svm = Svm {
+            svm: LiteSVM::new()
+                .with_sigverify(false)
+                .with_blockhash_check(false)
+                .with_transaction_history(0)
+                .with_log_bytes_limit(None),
+            bank: Pubkey::new_unique(),
+        };
+
+        svm.svm.set_sysvar(&Clock {
+            slot: 1,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        });
+        svm.airdrop(&svm.bank.clone(), 1_000_000 * 1_000_000_000);
+        svm.deploy(&program_so());
+
+        svm
+    }
+
+    // The program and its ProgramData, with no upgrade authority until a
+    // test sets one
+    fn deploy(&mut self, elf: &[u8]) {
+        let program_data = program_data_address();
+
+        let mut data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: None,
+        })
+        .unwrap();
+        data.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+        data.extend(elf);
+        self.set_account(
+            program_data,
+            Account {
+                lamports: self.rent().minimum_balance(data.len()),
+                data,
+                owner: bpf_loader_upgradeable::ID,
+                executable: false,
+            },
+        );
+
+        let data = bincode::serialize(&UpgradeableLoaderState::Program {
+            programdata_address: program_data,
+        })
+        .unwrap();
+        self.set_account(
+            escrow::ID,
+            Account {
+                lamports: self.rent().minimum_balance(data.len()),
+                data,
+                owner: bpf_loader_upgradeable::ID,
+                executable: true,
+            },
+        );
+    }
+
+    // Make `admin` the upgrade authority of the program, as init_config
+    // requires
+    pub fn set_upgrade_authority(&mut self, admin: &Pubkey) {
+        let address = program_data_address();
+        let mut account = self.account(&address).expect("program deployed");
+
+        let header = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(*admin),
+        })
+        .unwrap();
+        account.data[..header.len()].copy_from_slice(&header);
+        self.set_account(address, account);
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Option<Account> {
+        self.svm.get_account(address).map(Account::from)
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = (&Pubkey, Account)> {
+        self.svm
+            .accounts_db()
+            .inner
+            .iter()
+            .map(|(address, account)| {
+                (
+                    address,
+                    solana_account::Account::from(account.clone()).into(),
+                )
+            })
+    }
+
+    pub fn set_account(&mut self, address: Pubkey, account: Account) {
+        self.svm
+            .set_account(address, account.into())
+            .unwrap_or_else(|err| panic!("can't set {address}: {err}"));
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm.get_balance(address).unwrap_or(0)
+    }
+
+    pub fn airdrop(&mut self, address: &Pubkey, lamports: u64) {
+        let mut account = self.account(address).unwrap_or_default();
+        account.lamports += lamports;
+        self.set_account(*address, account);
+    }
+
+    pub fn rent(&self) -> Rent {
+        self.svm.get_sysvar()
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.svm.get_sysvar()
+    }
+
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.clock();
+        clock.unix_timestamp += seconds;
+        clock.slot += seconds.unsigned_abs() * 5 / 2;
+        self.svm.set_sysvar(&clock);
+    }
+
+    // Run `instructions` as one transaction signed by `signers`, keeping
+    // none of their changes if any of them fails
+    pub fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Pubkey],
+    ) -> std::result::Result<Outcome, Failure> {
+        for (index, instruction) in instructions.iter().enumerate() {
+            if let Some(meta) = instruction
+                .accounts
+                .iter()
+                .find(|meta| meta.is_signer && !signers.contains(&&meta.pubkey))
+            {
+                return Err(Failure {
+                    error: TransactionError::Instruction(
+                        index,
+                        ProgramError::MissingRequiredSignature,
+                    ),
+                    logs: vec![format!("{} did not sign", meta.pubkey)],
+                });
+            }
+        }
+
+        let message = Message::new(instructions, Some(&self.bank));
+        let transaction = Transaction::new_unsigned(message.clone());
+
+        match self.svm.send_transaction(transaction) {
+            Ok(meta) => Ok(outcome(&message, meta)),
+            Err(FailedTransactionMetadata { err, meta }) => Err(Failure {
+                error: transaction_error(&message, err),
+                logs: meta.logs,
+            }),
+        }
+    }
+}
+
+impl Default for Svm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// target/deploy/escrow.so of the workspace this file is built in, or the one
+// in SBF_OUT_DIR
+fn program_so() -> Vec<u8> {
+    let path = match std::env::var_os("SBF_OUT_DIR") {
+        Some(dir) => PathBuf::from(dir).join("escrow.so"),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .ancestors()
+            .map(|dir| dir.join("target/deploy/escrow.so"))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| panic!("no target/deploy/escrow.so, run `anchor build` first")),
+    };
+
+    std::fs::read(&path).unwrap_or_else(|err| panic!("can't read {}: {err}", path.display()))
+}
+
+fn outcome(message: &Message, meta: TransactionMetadata) -> Outcome {
+    let keys = &message.account_keys;
+
+    let inner_instructions = meta
+        .inner_instructions
+        .iter()
+        .flatten()
+        .map(|inner| {
+            let instruction = &inner.instruction;
+            Instruction {
+                program_id: keys[instruction.program_id_index as usize],
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|&index| {
+                        let index = index as usize;
+                        AccountMeta {
+                            pubkey: keys[index],
+                            is_signer: message.is_signer(index),
+                            is_writable: message.is_maybe_writable(index, None),
+                        }
+                    })
+                    .collect(),
+                data: instruction.data.clone(),
+            }
+        })
+        .collect();
+
+    Outcome {
+        logs: meta.logs,
+        events: decode_events(keys, &meta.inner_instructions),
+        inner_instructions,
+        compute_units: meta.compute_units_consumed,
+    }
+}
+
+fn transaction_error(message: &Message, error: RuntimeError) -> TransactionError {
+    match error {
+        // runtime checks like lamports out of thin air have no ProgramError
+        RuntimeError::InstructionError(index, error) => {
+            match ProgramError::try_from(error.clone()) {
+                Ok(program_error) => TransactionError::Instruction(index as usize, program_error),
+                Err(_) => TransactionError::Runtime(RuntimeError::InstructionError(index, error)),
+            }
+        }
+        RuntimeError::InsufficientFundsForRent { account_index } => {
+            TransactionError::InsufficientFundsForRent(message.account_keys[account_index as usize])
+        }
+        error => TransactionError::Runtime(error),
+    }
+}
@@ -0,0 +1,290 @@
+use anchor_spl::{token, token_2022};
+use escrow::{decode::EscrowEvent, errors::EscrowError, state::DEFAULT_CHALLENGE_WINDOW};
+use escrow_client::{
+    instructions,
+    pda::{receipt_mint_address, token_account},
+    Asset, BondTerms, EscrowOptions, EscrowState,
+};
+
+use crate::setup::*;
+
+fn sol_for_tokens(env: &Env) -> Deal {
+    Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN)
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn fund_moves_the_receive_leg_into_custody() {
+    let mut env = Env::new();
+    let escrow = created(env.init("fund", sol_for_tokens(&env)));
+
+    let outcome = ok(env.fund(&escrow, false));
+    let account = env.escrow(&escrow);
+    assert_eq!(account.state, EscrowState::Funded);
+    assert_eq!(
+        account.challenge_ends,
+        env.svm.clock().unix_timestamp + DEFAULT_CHALLENGE_WINDOW
+    );
+    assert_eq!(
+        env.token_balance(&token_account(&escrow, &env.mint, &token_2022::ID)),
+        10 * TOKEN
+    );
+    let [EscrowEvent::Funded(funded)] = outcome.events.as_slice() else {
+        panic!("expected EscrowFunded, got {:?}", outcome.events);
+    };
+    // named like every other event, no need to read the escrow back
+    assert_eq!(funded.escrow_id, "fund");
+    assert_eq!(funded.escrow_authority, env.authority);
+    assert_eq!(funded.receiver, env.receiver);
+    assert_eq!(funded.deposit, Asset::Native);
+    assert_eq!(funded.receive, env.token_2022());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn settle_after_the_challenge_window() {
+    let mut env = Env::new();
+    let escrow = created(env.init("settle", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+
+    assert_error(
+        env.settle(&escrow, &env.receiver.clone()),
+        EscrowError::ChallengeWindowOpen,
+    );
+
+    env.svm.warp(DEFAULT_CHALLENGE_WINDOW);
+    let outcome = ok(env.settle(&escrow, &env.receiver.clone()));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1010 * TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        990 * TOKEN
+    );
+    assert!(env.svm.account(&receipt_mint_address(&escrow).0).is_none());
+    assert!(matches!(
+        outcome.events.as_slice(),
+        [EscrowEvent::Completed(_)]
+    ));
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn authority_settles_too() {
+    let mut env = Env::new();
+    let deal = Deal::new(env.spl_token(), 4 * TOKEN, Asset::Native, SOL).with(EscrowOptions {
+        challenge_window: Some(0),
+        ..no_options()
+    });
+    let escrow = created(env.init("authority_settles", deal));
+    ok(env.fund(&escrow, false));
+    let before = env.lamports(&env.authority.clone());
+
+    ok(env.settle(&escrow, &env.authority.clone()));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.spl_mint.clone()),
+        1004 * TOKEN
+    );
+    assert!(env.lamports(&env.authority.clone()) > before + SOL);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn fund_refunds_the_bond() {
+    let mut env = Env::new();
+    let deal = sol_for_tokens(&env).with(EscrowOptions {
+        bond: Some(BondTerms {
+            amount: TOKEN,
+            window: 60,
+        }),
+        ..no_options()
+    });
+    let escrow = created(env.init("fund_committed", deal));
+    ok(env.commit(&escrow, false));
+
+    ok(env.fund(&escrow, false));
+    assert_eq!(env.escrow(&escrow).committed_until, 0);
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        990 * TOKEN
+    );
+    assert_eq!(
+        env.token_balance(&token_account(&escrow, &env.mint, &token_2022::ID)),
+        10 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn fund_after_the_window_forfeits_the_bond() {
+    let mut env = Env::new();
+    let deal = sol_for_tokens(&env).with(EscrowOptions {
+        bond: Some(BondTerms {
+            amount: TOKEN,
+            window: 60,
+        }),
+        ..no_options()
+    });
+    let escrow = created(env.init("fund_lapsed", deal));
+    ok(env.commit(&escrow, false));
+    env.svm.warp(60);
+
+    // the bond stays in custody next to the receive leg
+    let outcome = ok(env.fund(&escrow, false));
+    assert_ne!(env.escrow(&escrow).committed_until, 0);
+    assert_eq!(
+        env.token_balance(&token_account(&escrow, &env.mint, &token_2022::ID)),
+        11 * TOKEN
+    );
+    let [EscrowEvent::Funded(funded)] = outcome.events.as_slice() else {
+        panic!("expected EscrowFunded, got {:?}", outcome.events);
+    };
+    assert_eq!(funded.refunded_bond, 0);
+
+    env.svm.warp(DEFAULT_CHALLENGE_WINDOW);
+    let outcome = ok(env.settle(&escrow, &env.receiver.clone()));
+    assert_eq!(
+        env.balance(&env.receiver.clone(), &env.mint.clone()),
+        989 * TOKEN
+    );
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1011 * TOKEN
+    );
+    let [EscrowEvent::Completed(completed)] = outcome.events.as_slice() else {
+        panic!("expected EscrowCompleted, got {:?}", outcome.events);
+    };
+    assert_eq!(completed.forfeited_bond, TOKEN);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn fund_twice_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("fund_twice", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+
+    assert_error(env.fund(&escrow, false), EscrowError::InvalidState);
+    // a funded escrow only settles or closes
+    assert_error(env.accept(&escrow, false), EscrowError::InvalidState);
+    assert_error(
+        env.counter_offer(&escrow, TOKEN, false, false),
+        EscrowError::InvalidState,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn fund_over_a_funded_counter_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("fund_countered", sol_for_tokens(&env)));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, true, false));
+
+    assert_error(env.fund(&escrow, false), EscrowError::CounterPending);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn challenge_window_past_the_clock_overflows() {
+    let mut env = Env::new();
+    let deal = sol_for_tokens(&env).with(EscrowOptions {
+        challenge_window: Some(i64::MAX),
+        ..no_options()
+    });
+    let escrow = created(env.init("fund_overflow", deal));
+
+    assert_error(env.fund(&escrow, false), EscrowError::OverFlow);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn fund_with_the_wrong_receive_mint_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "fund_wrong_mint",
+        Deal::new(Asset::Native, SOL, env.spl_token(), TOKEN),
+    ));
+    let other_mint = env.create_mint(&token::ID);
+    env.mint_to(&env.receiver.clone(), &other_mint, TOKEN);
+    let instruction = instructions::fund(&env.escrow(&escrow), false);
+
+    assert_error(
+        env.send(
+            swap_mint(
+                instruction,
+                &env.spl_mint.clone(),
+                &other_mint,
+                &token::ID,
+                &[escrow, env.receiver],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidReceiveMint,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn settle_an_active_escrow_fails() {
+    let mut env = Env::new();
+    // nothing is in custody for the receive leg yet, a SOL one needs no vault
+    let escrow = created(env.init(
+        "settle_active",
+        Deal::new(env.token_2022(), TOKEN, Asset::Native, SOL),
+    ));
+
+    assert_error(
+        env.settle(&escrow, &env.receiver.clone()),
+        EscrowError::InvalidState,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn settle_by_someone_else_fails() {
+    let mut env = Env::new();
+    let escrow = created(env.init("settle_stranger", sol_for_tokens(&env)));
+    ok(env.fund(&escrow, false));
+    env.svm.warp(DEFAULT_CHALLENGE_WINDOW);
+    let stranger = env.wallet();
+
+    assert_error(
+        env.settle(&escrow, &stranger),
+        EscrowError::UnauthorizedSigner,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn settle_without_the_deposit_mint_fails() {
+    let mut env = Env::new();
+    let deal = Deal::new(env.token_2022(), TOKEN, Asset::Native, SOL).with(EscrowOptions {
+        challenge_window: Some(0),
+        ..no_options()
+    });
+    let escrow = created(env.init("settle_no_mint", deal));
+    ok(env.fund(&escrow, false));
+    let account = env.escrow(&escrow);
+    let instruction = instructions::settle(
+        &account,
+        &env.receiver.clone(),
+        &env.authority.clone(),
+        None,
+    );
+
+    assert_error(
+        env.send(
+            without_mint(
+                instruction,
+                &env.mint.clone(),
+                &token_2022::ID,
+                &[escrow, env.receiver],
+            ),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::InvalidDepositMint,
+    );
+}
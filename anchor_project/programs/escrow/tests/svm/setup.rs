@@ -0,0 +1,603 @@
+// Wallets, mints and escrows for the tests, sending the instructions the
+// client crate builds
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, program_pack::Pack, system_instruction},
+};
+use anchor_spl::{associated_token, token, token_2022};
+use escrow::{errors::EscrowError, state::legacy::SentinelEscrow};
+use escrow_client::{
+    accounts::{decode_config, decode_escrow, decode_payout_split},
+    instructions::{self, InitEscrow},
+    pda::{
+        config_address, escrow_address, payout_split_address, receipt_mint_address,
+        sol_vault_address, token_account,
+    },
+    Asset, Config, Escrow, EscrowOptions, EscrowState, PayoutSplit,
+};
+
+use crate::runtime::{Account, Failure, Outcome, Svm, TransactionError};
+
+pub const SOL: u64 = 1_000_000_000;
+// one whole token, test mints have 6 decimals
+pub const TOKEN: u64 = 1_000_000;
+
+pub type TxResult = std::result::Result<Outcome, Failure>;
+
+pub struct Env {
+    pub svm: Svm,
+    pub authority: Pubkey,
+    pub receiver: Pubkey,
+    // token-2022 and spl-token mints, both wallets hold 1000 of each
+    pub mint: Pubkey,
+    pub spl_mint: Pubkey,
+    // mint authority of every test mint
+    issuer: Pubkey,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        let mut svm = Svm::new();
+        let issuer = Pubkey::new_unique();
+        svm.airdrop(&issuer, 100 * SOL);
+
+        let mut env = Self {
+            svm,
+            authority: Pubkey::new_unique(),
+            receiver: Pubkey::new_unique(),
+            mint: Pubkey::default(),
+            spl_mint: Pubkey::default(),
+            issuer,
+        };
+
+        env.mint = env.create_mint(&token_2022::ID);
+        env.spl_mint = env.create_mint(&token::ID);
+        for wallet in [env.authority, env.receiver] {
+            env.svm.airdrop(&wallet, 100 * SOL);
+            env.mint_to(&wallet, &env.mint.clone(), 1000 * TOKEN);
+            env.mint_to(&wallet, &env.spl_mint.clone(), 1000 * TOKEN);
+        }
+
+        env
+    }
+
+    pub fn token_2022(&self) -> Asset {
+        Asset::Token2022 { mint: self.mint }
+    }
+
+    pub fn spl_token(&self) -> Asset {
+        Asset::SplToken {
+            mint: self.spl_mint,
+            program: token::ID,
+        }
+    }
+
+    // A funded wallet
+    pub fn wallet(&mut self) -> Pubkey {
+        let wallet = Pubkey::new_unique();
+        self.svm.airdrop(&wallet, 10 * SOL);
+        wallet
+    }
+
+    pub fn send(&mut self, instruction: Instruction, signers: &[&Pubkey]) -> TxResult {
+        self.svm.send(&[instruction], signers)
+    }
+
+    pub fn create_mint(&mut self, token_program: &Pubkey) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        let space = spl_token_2022::state::Mint::LEN;
+
+        self.svm
+            .send(
+                &[
+                    system_instruction::create_account(
+                        &self.issuer,
+                        &mint,
+                        self.svm.rent().minimum_balance(space),
+                        space as u64,
+                        token_program,
+                    ),
+                    spl_token_2022::instruction::initialize_mint2(
+                        token_program,
+                        &mint,
+                        &self.issuer,
+                        None,
+                        6,
+                    )
+                    .unwrap(),
+                ],
+                &[&self.issuer, &mint],
+            )
+            .expect("create mint");
+
+        mint
+    }
+
+    // Mint `amount` into the ATA of `wallet`, creating it if needed
+    pub fn mint_to(&mut self, wallet: &Pubkey, mint: &Pubkey, amount: u64) -> Pubkey {
+        let token_program = self.svm.account(mint).expect("mint exists").owner;
+        let ata = token_account(wallet, mint, &token_program);
+
+        self.svm
+            .send(
+                &[
+                    associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                        &self.issuer,
+                        wallet,
+                        mint,
+                        &token_program,
+                    ),
+                    spl_token_2022::instruction::mint_to(
+                        &token_program,
+                        mint,
+                        &ata,
+                        &self.issuer,
+                        &[],
+                        amount,
+                    )
+                    .unwrap(),
+                ],
+                &[&self.issuer],
+            )
+            .expect("mint tokens");
+
+        ata
+    }
+
+    // Wrap `lamports` of `wallet` into its wSOL ATA under `token_program`
+    pub fn wrap_sol(&mut self, wallet: &Pubkey, token_program: &Pubkey, lamports: u64) -> Pubkey {
+        let native_mint = escrow::state::native_mint_for(token_program).unwrap();
+        if self.svm.account(&native_mint).is_none() {
+            self.create_native_mint(token_program);
+        }
+        let ata = token_account(wallet, &native_mint, token_program);
+
+        self.svm
+            .send(
+                &[
+                    associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                        wallet,
+                        wallet,
+                        &native_mint,
+                        token_program,
+                    ),
+                    system_instruction::transfer(wallet, &ata, lamports),
+                    spl_token_2022::instruction::sync_native(token_program, &ata).unwrap(),
+                ],
+                &[wallet],
+            )
+            .expect("wrap sol");
+
+        ata
+    }
+
+    // The wSOL mints are genesis accounts on a real cluster
+    fn create_native_mint(&mut self, token_program: &Pubkey) {
+        let native_mint = escrow::state::native_mint_for(token_program).unwrap();
+        let mint = spl_token_2022::state::Mint {
+            mint_authority: None.into(),
+            supply: 0,
+            decimals: 9,
+            is_initialized: true,
+            freeze_authority: None.into(),
+        };
+        let mut data = vec![0; spl_token_2022::state::Mint::LEN];
+        spl_token_2022::state::Mint::pack(mint, &mut data).unwrap();
+
+        self.svm.set_account(
+            native_mint,
+            Account {
+                lamports: self.svm.rent().minimum_balance(data.len()),
+                data,
+                owner: *token_program,
+                executable: false,
+            },
+        );
+    }
+
+    pub fn token_balance(&self, account: &Pubkey) -> u64 {
+        self.svm.account(account).map_or(0, |account| {
+            u64::from_le_bytes(account.data[64..72].try_into().unwrap())
+        })
+    }
+
+    // Balance of `wallet`'s ATA for one of the test mints
+    pub fn balance(&self, wallet: &Pubkey, mint: &Pubkey) -> u64 {
+        let token_program = self.svm.account(mint).expect("mint exists").owner;
+        self.token_balance(&token_account(wallet, mint, &token_program))
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm.lamports(address)
+    }
+
+    pub fn escrow(&self, address: &Pubkey) -> Escrow {
+        decode_escrow(&self.svm.account(address).expect("escrow exists").data).unwrap()
+    }
+
+    pub fn payout_split(&self, escrow: &Pubkey) -> Option<PayoutSplit> {
+        self.svm
+            .account(&payout_split_address(escrow).0)
+            .map(|account| decode_payout_split(&account.data).unwrap())
+    }
+
+    pub fn config(&self) -> Option<Config> {
+        self.svm
+            .account(&config_address().0)
+            .map(|account| decode_config(&account.data).unwrap())
+    }
+
+    // Address of an escrow created by the default authority and receiver
+    pub fn address(&self, escrow_id: &str) -> Pubkey {
+        escrow_address(escrow_id, &self.authority, &self.receiver).0
+    }
+
+    pub fn init(&mut self, escrow_id: &str, deal: Deal) -> std::result::Result<Pubkey, Failure> {
+        let (authority, receiver) = (self.authority, self.receiver);
+        self.init_by(&authority, &receiver, escrow_id, deal)
+    }
+
+    pub fn init_by(
+        &mut self,
+        authority: &Pubkey,
+        receiver: &Pubkey,
+        escrow_id: &str,
+        deal: Deal,
+    ) -> std::result::Result<Pubkey, Failure> {
+        let instruction = InitEscrow {
+            escrow_id: escrow_id.to_string(),
+            escrow_authority: *authority,
+            receiver: *receiver,
+            deposit: deal.deposit,
+            deposit_amount: deal.deposit_amount,
+            receive: deal.receive,
+            receive_amount: deal.receive_amount,
+            options: deal.options,
+        }
+        .instruction();
+
+        self.send(instruction, &[authority])?;
+
+        Ok(escrow_address(escrow_id, authority, receiver).0)
+    }
+
+    // Receipt holder of an escrow, escrow_authority without a receipt
+    pub fn authority_side(&self, address: &Pubkey) -> Pubkey {
+        let escrow = self.escrow(address);
+        if !escrow.has_receipt {
+            return escrow.escrow_authority;
+        }
+
+        let (receipt_mint, _) = receipt_mint_address(address);
+        [escrow.escrow_authority, self.authority, self.receiver]
+            .into_iter()
+            .find(|wallet| {
+                self.token_balance(&token_account(wallet, &receipt_mint, &token_2022::ID)) == 1
+            })
+            .unwrap_or(escrow.escrow_authority)
+    }
+
+    // An empty receipt account for `wallet`, the kind a previous holder
+    // keeps when it does not close it
+    pub fn empty_receipt_account(&mut self, address: &Pubkey, wallet: &Pubkey) -> Pubkey {
+        let (receipt_mint, _) = receipt_mint_address(address);
+        self.svm
+            .send(
+                &[associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                    wallet,
+                    wallet,
+                    &receipt_mint,
+                    &token_2022::ID,
+                )],
+                &[wallet],
+            )
+            .expect("create receipt account");
+
+        token_account(wallet, &receipt_mint, &token_2022::ID)
+    }
+
+    pub fn accept(&mut self, address: &Pubkey, wrapped: bool) -> TxResult {
+        let escrow = self.escrow(address);
+        let split = self.payout_split(address);
+        let instruction = instructions::accept(
+            &escrow,
+            &self.authority_side(address),
+            wrapped,
+            split.as_ref(),
+        );
+        self.send(instruction, &[&escrow.receiver])
+    }
+
+    pub fn close(&mut self, address: &Pubkey) -> TxResult {
+        let escrow = self.escrow(address);
+        let holder = self.authority_side(address);
+        self.close_as(&holder, &escrow)
+    }
+
+    pub fn close_as(&mut self, signer: &Pubkey, escrow: &Escrow) -> TxResult {
+        let instruction = instructions::close(escrow, signer);
+        self.send(instruction, &[signer])
+    }
+
+    pub fn commit(&mut self, address: &Pubkey, wrapped: bool) -> TxResult {
+        let escrow = self.escrow(address);
+        let instruction = instructions::commit(&escrow, wrapped);
+        self.send(instruction, &[&escrow.receiver])
+    }
+
+    pub fn fund(&mut self, address: &Pubkey, wrapped: bool) -> TxResult {
+        let escrow = self.escrow(address);
+        let instruction = instructions::fund(&escrow, wrapped);
+        self.send(instruction, &[&escrow.receiver])
+    }
+
+    pub fn counter_offer(
+        &mut self,
+        address: &Pubkey,
+        receive_amount: u64,
+        funded: bool,
+        wrapped: bool,
+    ) -> TxResult {
+        let escrow = self.escrow(address);
+        let instruction = instructions::counter_offer(&escrow, receive_amount, funded, wrapped);
+        self.send(instruction, &[&escrow.receiver])
+    }
+
+    pub fn settle(&mut self, address: &Pubkey, signer: &Pubkey) -> TxResult {
+        let escrow = self.escrow(address);
+        let split = self.payout_split(address);
+        let holder = self.authority_side(address);
+        let instruction = instructions::settle(&escrow, signer, &holder, split.as_ref());
+        self.send(instruction, &[signer])
+    }
+
+    // Accept the counter-offer the escrow holds now
+    pub fn accept_counter(&mut self, address: &Pubkey) -> TxResult {
+        let expected_amount = self.escrow(address).counter_amount;
+        self.accept_counter_of(address, expected_amount)
+    }
+
+    pub fn accept_counter_of(&mut self, address: &Pubkey, expected_amount: u64) -> TxResult {
+        let escrow = self.escrow(address);
+        let split = self.payout_split(address);
+        let holder = self.authority_side(address);
+        let instruction =
+            instructions::accept_counter(&escrow, &holder, expected_amount, split.as_ref());
+        self.send(instruction, &[&holder])
+    }
+
+    pub fn transfer_authority(&mut self, address: &Pubkey, new_authority: &Pubkey) -> TxResult {
+        let escrow = self.escrow(address);
+        let holder = self.authority_side(address);
+        let instruction = instructions::transfer_authority(&escrow, &holder, new_authority);
+        self.send(instruction, &[&holder])
+    }
+
+    // A program Config capping referrals at `max_referral_bps`, run by a new
+    // admin which is returned
+    pub fn configure(&mut self, max_referral_bps: u16) -> Pubkey {
+        let admin = self.wallet();
+        self.svm.set_upgrade_authority(&admin);
+        self.send(
+            instructions::init_config(&admin, max_referral_bps),
+            &[&admin],
+        )
+        .expect("init config");
+        admin
+    }
+
+    // Put raw account data at `address`, owned by the program
+    pub fn set_program_account(&mut self, address: &Pubkey, data: Vec<u8>, lamports: u64) {
+        self.svm.set_account(
+            *address,
+            Account {
+                lamports,
+                data,
+                owner: escrow::ID,
+                executable: false,
+            },
+        );
+    }
+}
+
+// Legs and options of an escrow, SOL for tokens by default
+#[derive(Clone)]
+pub struct Deal {
+    pub deposit: Asset,
+    pub deposit_amount: u64,
+    pub receive: Asset,
+    pub receive_amount: u64,
+    pub options: EscrowOptions,
+}
+
+impl Deal {
+    pub fn new(deposit: Asset, deposit_amount: u64, receive: Asset, receive_amount: u64) -> Self {
+        Self {
+            deposit,
+            deposit_amount,
+            receive,
+            receive_amount,
+            options: no_options(),
+        }
+    }
+
+    pub fn with(mut self, options: EscrowOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+pub fn no_options() -> EscrowOptions {
+    EscrowOptions {
+        bond: None,
+        challenge_window: None,
+        split: None,
+        referral: None,
+    }
+}
+
+// The escrow program error a transaction failed with
+#[track_caller]
+pub fn assert_error<T>(result: std::result::Result<T, Failure>, expected: EscrowError) {
+    assert_program_error(
+        result,
+        ProgramError::Custom(u32::from(expected)),
+        &format!("{expected:?}"),
+    );
+}
+
+// An anchor framework error, a violated constraint or a missing account
+#[track_caller]
+pub fn assert_anchor_error<T>(
+    result: std::result::Result<T, Failure>,
+    expected: anchor_lang::error::ErrorCode,
+) {
+    assert_program_error(
+        result,
+        ProgramError::Custom(u32::from(expected)),
+        &format!("{expected:?}"),
+    );
+}
+
+#[track_caller]
+pub fn assert_program_error<T>(
+    result: std::result::Result<T, Failure>,
+    expected: ProgramError,
+    name: &str,
+) {
+    match result {
+        Err(Failure {
+            error: TransactionError::Instruction(_, error),
+            ..
+        }) if error == expected => {}
+        Err(failure) => panic!(
+            "expected {name}, got {}\n{}",
+            failure.error,
+            failure.logs.join("\n")
+        ),
+        Ok(_) => panic!("expected {name}, the transaction succeeded"),
+    }
+}
+
+// Unwrap a transaction that should succeed, with its logs when it did not
+#[track_caller]
+pub fn ok(result: TxResult) -> Outcome {
+    match result {
+        Ok(outcome) => outcome,
+        Err(failure) => panic!("{}\n{}", failure.error, failure.logs.join("\n")),
+    }
+}
+
+#[track_caller]
+pub fn created(result: std::result::Result<Pubkey, Failure>) -> Pubkey {
+    match result {
+        Ok(escrow) => escrow,
+        Err(failure) => panic!("{}\n{}", failure.error, failure.logs.join("\n")),
+    }
+}
+
+pub fn assert_state(env: &Env, escrow: &Pubkey, state: EscrowState) {
+    assert_eq!(env.escrow(escrow).state, state);
+}
+
+// Leave an optional account out, anchor reads the program id in its place as
+// None
+pub fn without(instruction: Instruction, address: &Pubkey) -> Instruction {
+    swap_account(instruction, address, &escrow::ID)
+}
+
+pub fn swap_account(mut instruction: Instruction, from: &Pubkey, to: &Pubkey) -> Instruction {
+    let meta = instruction
+        .accounts
+        .iter_mut()
+        .find(|meta| meta.pubkey == *from)
+        .expect("account in instruction");
+    meta.pubkey = *to;
+    if *to == escrow::ID {
+        meta.is_writable = false;
+    }
+    instruction
+}
+
+// Point a token leg at another mint, along with the token accounts `wallets`
+// hold of it, so the mint check is the first one to trip
+pub fn swap_mint(
+    instruction: Instruction,
+    from: &Pubkey,
+    to: &Pubkey,
+    token_program: &Pubkey,
+    wallets: &[Pubkey],
+) -> Instruction {
+    wallets.iter().fold(
+        swap_account(instruction, from, to),
+        |instruction, wallet| {
+            swap_account(
+                instruction,
+                &token_account(wallet, from, token_program),
+                &token_account(wallet, to, token_program),
+            )
+        },
+    )
+}
+
+// Leave a token leg's mint out, along with the token accounts `wallets` hold
+// of it
+pub fn without_mint(
+    instruction: Instruction,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    wallets: &[Pubkey],
+) -> Instruction {
+    wallets
+        .iter()
+        .fold(without(instruction, mint), |instruction, wallet| {
+            without(instruction, &token_account(wallet, mint, token_program))
+        })
+}
+
+// Authority of the legacy escrow. The TS tests sign for it with the keypair
+// seeded by sha256("legacy-authority")
+pub fn legacy_authority() -> Pubkey {
+    "FLFhyrQ4Aocyd8qE6EBFRJ7YxyX5wihP9boNPv32nTGU"
+        .parse()
+        .unwrap()
+}
+
+// A native SOL for token deal in the legacy layout, which the program no
+// longer writes, with its deposit in the sol vault. The same bytes the test
+// validator loads from tests/fixtures. Returns the escrow's address
+pub fn legacy_escrow(env: &mut Env) -> Pubkey {
+    let authority = legacy_authority();
+    let receiver = Pubkey::new_from_array([2; 32]);
+    let (escrow, bump) = escrow_address("legacy_01", &authority, &receiver);
+
+    let mut data = escrow::state::Escrow::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(authority.as_ref());
+    data.extend_from_slice(receiver.as_ref());
+    // Pubkey::default() deposit mint, native SOL
+    data.extend_from_slice(&[0; 32]);
+    data.extend_from_slice(&200_000_000u64.to_le_bytes());
+    data.extend_from_slice(&[3; 32]);
+    data.extend_from_slice(&10_000_000u64.to_le_bytes());
+    // Active
+    data.extend_from_slice(&[0, bump]);
+    data.extend_from_slice(&9u32.to_le_bytes());
+    data.extend_from_slice(b"legacy_01");
+    data.resize(8 + SentinelEscrow::INIT_SPACE, 0);
+
+    let rent = env.svm.rent();
+    let escrow_rent = rent.minimum_balance(data.len());
+    env.set_program_account(&escrow, data, escrow_rent);
+    env.svm.set_account(
+        sol_vault_address(&escrow).0,
+        Account {
+            lamports: rent.minimum_balance(0) + 200_000_000,
+            data: vec![],
+            owner: system_program::ID,
+            executable: false,
+        },
+    );
+
+    escrow
+}
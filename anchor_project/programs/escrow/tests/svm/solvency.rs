@@ -0,0 +1,143 @@
+// escrow_client::solvency against what the program really holds: every way
+// an escrow can be open owes exactly what its accounts have, not a lamport
+// more or less
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token_2022;
+use escrow_client::{
+    instructions,
+    pda::token_account,
+    solvency::{custody_accounts, held, requirements},
+    Asset, BondTerms, EscrowOptions,
+};
+
+use crate::setup::*;
+
+// (requirement, held) of every requirement of the escrow
+fn holdings(env: &Env, address: &Pubkey) -> Vec<(u64, u64)> {
+    requirements(address, &env.escrow(address), &env.svm.rent())
+        .iter()
+        .map(|requirement| {
+            let held = env.svm.account(&requirement.account).map_or(0, |account| {
+                held(&requirement.holding, account.lamports, &account.data)
+            });
+            (requirement.amount, held)
+        })
+        .collect()
+}
+
+fn assert_exactly_covered(env: &Env, address: &Pubkey) {
+    for (required, held) in holdings(env, address) {
+        assert_eq!(held, required, "{address}");
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn open_escrows_hold_what_they_owe() {
+    let mut env = Env::new();
+    let sol_deposit = created(env.init(
+        "sol_deposit",
+        Deal::new(Asset::Native, 2 * SOL, env.token_2022(), 10 * TOKEN),
+    ));
+    let token_deposit = created(env.init(
+        "token_deposit",
+        Deal::new(env.spl_token(), 5 * TOKEN, Asset::Native, SOL),
+    ));
+
+    let funded = created(env.init(
+        "funded",
+        Deal::new(env.token_2022(), 5 * TOKEN, Asset::Native, SOL),
+    ));
+    ok(env.fund(&funded, false));
+
+    let bonded = created(env.init(
+        "bonded",
+        Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN).with(EscrowOptions {
+            bond: Some(BondTerms {
+                amount: TOKEN,
+                window: 60,
+            }),
+            ..no_options()
+        }),
+    ));
+    ok(env.commit(&bonded, false));
+
+    let countered = created(env.init(
+        "countered",
+        Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN),
+    ));
+    ok(env.counter_offer(&countered, 8 * TOKEN, true, false));
+
+    for escrow in [sol_deposit, token_deposit, funded, bonded, countered] {
+        assert_exactly_covered(&env, &escrow);
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn migrated_escrow_owes_from_its_sol_vault() {
+    let mut env = Env::new();
+    let escrow = legacy_escrow(&mut env);
+    let payer = env.wallet();
+    ok(env.send(instructions::migrate_escrow(&payer, &escrow), &[&payer]));
+
+    assert_eq!(
+        requirements(&escrow, &env.escrow(&escrow), &env.svm.rent()).len(),
+        2
+    );
+    assert_exactly_covered(&env, &escrow);
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn completed_escrow_owes_only_its_rent() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "completed",
+        Deal::new(env.token_2022(), 5 * TOKEN, Asset::Native, SOL),
+    ));
+    ok(env.accept(&escrow, false));
+
+    assert_eq!(
+        holdings(&env, &escrow),
+        [(
+            env.svm
+                .rent()
+                .minimum_balance(env.svm.account(&escrow).unwrap().data.len()),
+            env.lamports(&escrow)
+        )]
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn a_drained_vault_falls_short() {
+    let mut env = Env::new();
+    let escrow = created(env.init(
+        "drained",
+        Deal::new(env.token_2022(), 5 * TOKEN, Asset::Native, SOL),
+    ));
+
+    let vault = token_account(&escrow, &env.mint, &token_2022::ID);
+    let mut account = env.svm.account(&vault).unwrap();
+    account.data[64..72].copy_from_slice(&(4 * TOKEN).to_le_bytes());
+    env.svm.set_account(vault, account);
+
+    assert!(holdings(&env, &escrow)
+        .iter()
+        .any(|&(required, held)| (required, held) == (5 * TOKEN, 4 * TOKEN)));
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn closed_escrow_leaves_no_custody_behind() {
+    let mut env = Env::new();
+    let deal = Deal::new(Asset::Native, 2 * SOL, env.token_2022(), 10 * TOKEN);
+    let escrow = created(env.init("closed", deal.clone()));
+    ok(env.fund(&escrow, false));
+    ok(env.close(&escrow));
+
+    for account in custody_accounts(&escrow, &deal.deposit, &deal.receive) {
+        assert!(env.svm.account(&account).is_none(), "{account} left over");
+    }
+}
@@ -0,0 +1,268 @@
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_spl::token_2022;
+use escrow::errors::EscrowError;
+use escrow_client::{
+    instructions::{self, InitEscrow},
+    pda::{payout_split_address, receipt_mint_address, token_account},
+    Asset, EscrowOptions, EscrowState, SplitShare,
+};
+
+use crate::setup::*;
+
+// 70/30 split of the receive leg between two wallets
+fn split_deal(deal: Deal, recipients: [Pubkey; 2]) -> Deal {
+    deal.with(split_options(recipients))
+}
+
+fn split_options(recipients: [Pubkey; 2]) -> EscrowOptions {
+    EscrowOptions {
+        split: Some(vec![
+            SplitShare {
+                recipient: recipients[0],
+                bps: 7_000,
+            },
+            SplitShare {
+                recipient: recipients[1],
+                bps: 3_000,
+            },
+        ]),
+        ..no_options()
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_pays_a_sol_leg_across_the_split() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    let before = recipients.map(|recipient| env.lamports(&recipient));
+    let deal = split_deal(
+        Deal::new(env.token_2022(), TOKEN, Asset::Native, 10 * SOL),
+        recipients,
+    );
+    let escrow = created(env.init("split_sol", deal));
+    assert!(env.escrow(&escrow).has_split);
+    assert_eq!(env.payout_split(&escrow).unwrap().shares.len(), 2);
+
+    ok(env.accept(&escrow, false));
+    assert_state(&env, &escrow, EscrowState::Completed);
+    assert_eq!(env.lamports(&recipients[0]), before[0] + 7 * SOL);
+    assert_eq!(env.lamports(&recipients[1]), before[1] + 3 * SOL);
+    // the table closes with the escrow
+    assert!(env.payout_split(&escrow).is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn settle_pays_a_token_leg_across_the_split() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    // recipients need token accounts of the receive mint
+    for recipient in recipients {
+        env.mint_to(&recipient, &env.mint.clone(), 0);
+    }
+    let deal = Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN).with(EscrowOptions {
+        challenge_window: Some(0),
+        ..split_options(recipients)
+    });
+    let escrow = created(env.init("split_tokens", deal));
+    ok(env.fund(&escrow, false));
+
+    ok(env.settle(&escrow, &env.receiver.clone()));
+    assert_eq!(env.balance(&recipients[0], &env.mint.clone()), 7 * TOKEN);
+    assert_eq!(env.balance(&recipients[1], &env.mint.clone()), 3 * TOKEN);
+    assert_eq!(
+        env.balance(&env.authority.clone(), &env.mint.clone()),
+        1000 * TOKEN
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn split_has_to_add_up() {
+    let mut env = Env::new();
+    let recipient = env.wallet();
+    for (id, shares) in [
+        ("short", vec![(recipient, 9_999)]),
+        ("zero_share", vec![(recipient, 10_000), (env.receiver, 0)]),
+        ("twice", vec![(recipient, 5_000), (recipient, 5_000)]),
+        ("empty", vec![]),
+    ] {
+        let deal = Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN).with(EscrowOptions {
+            split: Some(
+                shares
+                    .into_iter()
+                    .map(|(recipient, bps)| SplitShare { recipient, bps })
+                    .collect(),
+            ),
+            ..no_options()
+        });
+        assert_error(env.init(id, deal), EscrowError::InvalidSplit);
+    }
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn split_without_its_account_fails() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    let deal = split_deal(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        recipients,
+    );
+    let instruction = InitEscrow {
+        escrow_id: "no_table".to_string(),
+        escrow_authority: env.authority,
+        receiver: env.receiver,
+        deposit: deal.deposit,
+        deposit_amount: deal.deposit_amount,
+        receive: deal.receive,
+        receive_amount: deal.receive_amount,
+        options: deal.options,
+    }
+    .instruction();
+    let table = payout_split_address(&env.address("no_table")).0;
+
+    assert_error(
+        env.send(without(instruction, &table), &[&env.authority.clone()]),
+        EscrowError::MissingSplit,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_without_the_recipients_fails() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    let deal = split_deal(
+        Deal::new(env.token_2022(), TOKEN, Asset::Native, SOL),
+        recipients,
+    );
+    let escrow = created(env.init("no_recipients", deal));
+
+    // no table to read the recipients from
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    assert_error(
+        env.send(instruction, &[&env.receiver.clone()]),
+        EscrowError::MissingSplit,
+    );
+
+    // without the table account as well
+    let instruction = instructions::accept(&env.escrow(&escrow), &env.authority, false, None);
+    assert_error(
+        env.send(
+            without(instruction, &payout_split_address(&escrow).0),
+            &[&env.receiver.clone()],
+        ),
+        EscrowError::MissingSplit,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn accept_pays_recipients_in_table_order() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    let deal = split_deal(
+        Deal::new(env.token_2022(), TOKEN, Asset::Native, SOL),
+        recipients,
+    );
+    let escrow = created(env.init("swapped_recipients", deal));
+    let split = env.payout_split(&escrow);
+    let mut instruction =
+        instructions::accept(&env.escrow(&escrow), &env.authority, false, split.as_ref());
+
+    let count = instruction.accounts.len();
+    instruction.accounts.swap(count - 2, count - 1);
+    assert_error(
+        env.send(instruction, &[&env.receiver.clone()]),
+        EscrowError::InvalidSplit,
+    );
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn close_without_the_table_fails() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    let deal = split_deal(
+        Deal::new(Asset::Native, SOL, env.token_2022(), TOKEN),
+        recipients,
+    );
+    let escrow = created(env.init("close_split", deal));
+    let instruction = instructions::close(&env.escrow(&escrow), &env.authority);
+
+    assert_error(
+        env.send(
+            without(instruction, &payout_split_address(&escrow).0),
+            &[&env.authority.clone()],
+        ),
+        EscrowError::MissingSplit,
+    );
+
+    // the table's rent goes back with the rest
+    ok(env.close(&escrow));
+    assert!(env.payout_split(&escrow).is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn unfunded_counter_leaves_the_table_open() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    let deal = split_deal(
+        Deal::new(Asset::Native, SOL, env.token_2022(), 10 * TOKEN),
+        recipients,
+    );
+    let escrow = created(env.init("split_counter", deal));
+    ok(env.counter_offer(&escrow, 8 * TOKEN, false, false));
+
+    // the client leaves the table out of an unfunded counter, pass it anyway
+    // in its slot right after the receipt account
+    let mut instruction =
+        instructions::accept_counter(&env.escrow(&escrow), &env.authority, 8 * TOKEN, None);
+    let receipt_account = token_account(
+        &env.authority,
+        &receipt_mint_address(&escrow).0,
+        &token_2022::ID,
+    );
+    let slot = instruction
+        .accounts
+        .iter()
+        .position(|meta| meta.pubkey == receipt_account)
+        .unwrap()
+        + 1;
+    instruction.accounts[slot] = AccountMeta::new(payout_split_address(&escrow).0, false);
+    ok(env.send(instruction, &[&env.authority.clone()]));
+
+    assert!(env.escrow(&escrow).has_split);
+    assert!(env.payout_split(&escrow).is_some());
+
+    // the escrow can still be closed, the table along with it
+    ok(env.close(&escrow));
+    assert!(env.payout_split(&escrow).is_none());
+}
+
+#[test]
+#[ignore = "needs target/deploy/escrow.so"]
+fn split_leg_stays_with_the_recipients_after_a_transfer() {
+    let mut env = Env::new();
+    let recipients = [env.wallet(), env.wallet()];
+    let deal = split_deal(
+        Deal::new(env.token_2022(), TOKEN, Asset::Native, 10 * SOL),
+        recipients,
+    );
+    let escrow = created(env.init("split_transferred", deal));
+    let new_authority = env.wallet();
+    ok(env.transfer_authority(&escrow, &new_authority));
+    let before = recipients.map(|recipient| env.lamports(&recipient));
+    let holder_before = env.lamports(&new_authority);
+
+    ok(env.accept(&escrow, false));
+    assert_eq!(env.lamports(&recipients[0]), before[0] + 7 * SOL);
+    assert_eq!(env.lamports(&recipients[1]), before[1] + 3 * SOL);
+    // the holder of the receipt only gets the rent back
+    let holder_after = env.lamports(&new_authority);
+    assert!(holder_after > holder_before && holder_after < holder_before + SOL);
+    assert_eq!(env.escrow(&escrow).escrow_authority, new_authority);
+}
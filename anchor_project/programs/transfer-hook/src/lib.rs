@@ -0,0 +1,112 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use spl_discriminator::SplDiscriminate;
+use spl_tlv_account_resolution::{
+    account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+declare_id!("EkWF2iFzjQ4xT6BBMBdNgfjLLN4qFA4J9UcZMYt8Y32");
+
+pub const EXTRA_ACCOUNT_METAS_SEED: &str = "extra-account-metas";
+pub const COUNTER_SEED: &str = "counter";
+
+// Minimal transfer hook: every transfer of the mint bumps a counter PDA that is
+// only reachable through the extra account meta list, so a caller that does not
+// forward the extra accounts fails the transfer.
+#[program]
+pub mod transfer_hook {
+    use super::*;
+
+    pub fn initialize_extra_account_meta_list(
+        ctx: Context<InitializeExtraAccountMetaList>,
+    ) -> Result<()> {
+        // counter = ["counter", mint], mint is index 1 of the execute accounts
+        let extra_account_metas = vec![ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: COUNTER_SEED.as_bytes().to_vec(),
+                },
+                Seed::AccountKey { index: 1 },
+            ],
+            false,
+            true,
+        )?];
+
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?,
+            &extra_account_metas,
+        )?;
+
+        Ok(())
+    }
+
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn transfer_hook(ctx: Context<TransferHook>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.transfers = counter.transfers.checked_add(1).unwrap();
+        counter.volume = counter.volume.checked_add(amount).unwrap();
+
+        msg!("Transfer hook invoked: {} transfers", counter.transfers);
+
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Counter {
+    pub transfers: u64,
+    pub volume: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: TLV account validated by seeds, written by ExtraAccountMetaList::init
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(1)?,
+        seeds = [EXTRA_ACCOUNT_METAS_SEED.as_bytes(), mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Counter::INIT_SPACE,
+        seeds = [COUNTER_SEED.as_bytes(), mint.key().as_ref()],
+        bump
+    )]
+    pub counter: Account<'info, Counter>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// Order follows the transfer hook execute interface
+#[derive(Accounts)]
+pub struct TransferHook<'info> {
+    #[account(token::mint = mint)]
+    pub source_token: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(token::mint = mint)]
+    pub destination_token: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: source token account owner or delegate
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: TLV account validated by seeds
+    #[account(seeds = [EXTRA_ACCOUNT_METAS_SEED.as_bytes(), mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [COUNTER_SEED.as_bytes(), mint.key().as_ref()], bump)]
+    pub counter: Account<'info, Counter>,
+}
@@ -1,20 +1,50 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Escrow } from "../target/types/escrow";
+import { TransferHook } from "../target/types/transfer_hook";
 import { publicKey } from "@coral-xyz/anchor/dist/cjs/utils";
-import { LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
 import {
   ASSOCIATED_TOKEN_PROGRAM_ID,
+  createAssociatedTokenAccountIdempotentInstruction,
+  createInitializeMintInstruction,
+  createInitializeTransferHookInstruction,
   createMint,
+  createTransferCheckedWithTransferHookInstruction,
+  ExtensionType,
   getAccount,
   getAssociatedTokenAddress,
+  getAssociatedTokenAddressSync,
+  getMint,
+  getMintLen,
   getOrCreateAssociatedTokenAccount,
+  createNativeMint,
+  createSyncNativeInstruction,
   mintTo,
+  NATIVE_MINT_2022,
+  transferChecked,
   TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
 import { assert, expect } from "chai";
+import { createHash } from "crypto";
 
 const ESCROW_SEED = "ESCROW_SEED";
+const RECEIPT_SEED = "receipt";
+const SPLIT_SEED = "split";
+const CONFIG_SEED = "config";
+// Signs for the legacy escrow the validator loads from tests/fixtures, see
+// Anchor.toml. Anyone can derive it, that escrow only exists in tests
+const LEGACY_AUTHORITY = Keypair.fromSeed(
+  createHash("sha256").update("legacy-authority").digest()
+);
+const LEGACY_RECEIVER = new PublicKey(Buffer.alloc(32, 2));
 
 describe("escrow", () => {
   // Configure the client to use the local cluster.
@@ -22,6 +52,17 @@ describe("escrow", () => {
   anchor.setProvider(provider);
 
   const program = anchor.workspace.escrow as Program<Escrow>;
+  const hookProgram = anchor.workspace.transferHook as Program<TransferHook>;
+  const [legacyEscrow, legacyBump] = getEscrowAddress(
+    "legacy_01",
+    LEGACY_AUTHORITY.publicKey,
+    LEGACY_RECEIVER,
+    program.programId
+  );
+  const [legacySolVault] = PublicKey.findProgramAddressSync(
+    [Buffer.from("sol_vault"), legacyEscrow.toBuffer()],
+    program.programId
+  );
   let Bob = anchor.web3.Keypair.generate();
   let Alice = anchor.web3.Keypair.generate();
 
@@ -169,12 +210,16 @@ describe("escrow", () => {
         await program.methods
           .initEscrow(
             escrowId,
-            mint,
+            toAsset(mint),
             new anchor.BN(depositAmount),
-            PublicKey.default,
-            new anchor.BN(receiveAmount)
+            toAsset(PublicKey.default),
+            new anchor.BN(receiveAmount),
+            { bond: null, challengeWindow: null, split: null, referral: null }
           )
           .accounts({
+            payoutSplit: null,
+            config: null,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             escrowAuthority: Bob.publicKey,
             receiver: Alice.publicKey,
             depositMint: minted.mint,
@@ -207,12 +252,16 @@ describe("escrow", () => {
         await program.methods
           .initEscrow(
             escrowId,
-            new_mint,
+            toAsset(new_mint),
             new anchor.BN(depositAmount),
-            PublicKey.default,
-            new anchor.BN(receiveAmount)
+            toAsset(PublicKey.default),
+            new anchor.BN(receiveAmount),
+            { bond: null, challengeWindow: null, split: null, referral: null }
           )
           .accounts({
+            payoutSplit: null,
+            config: null,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             escrowAuthority: Bob.publicKey,
             receiver: Alice.publicKey,
             depositMint: new_mint,
@@ -231,12 +280,16 @@ describe("escrow", () => {
         await program.methods
           .initEscrow(
             escrowId,
-            mint,
+            toAsset(mint),
             new anchor.BN(depositAmount),
-            PublicKey.default,
-            new anchor.BN(receiveAmount)
+            toAsset(PublicKey.default),
+            new anchor.BN(receiveAmount),
+            { bond: null, challengeWindow: null, split: null, referral: null }
           )
           .accounts({
+            payoutSplit: null,
+            config: null,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             escrowAuthority: Bob.publicKey,
             receiver: Alice.publicKey,
             depositMint: mint,
@@ -255,12 +308,16 @@ describe("escrow", () => {
         await program.methods
           .initEscrow(
             escrowId,
-            mint,
+            toAsset(mint),
             new anchor.BN(depositAmount),
-            PublicKey.default,
-            new anchor.BN(receiveAmount)
+            toAsset(PublicKey.default),
+            new anchor.BN(receiveAmount),
+            { bond: null, challengeWindow: null, split: null, referral: null }
           )
           .accounts({
+            payoutSplit: null,
+            config: null,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             escrowAuthority: Bob.publicKey,
             receiver: Alice.publicKey,
             depositMint: mint,
@@ -271,12 +328,16 @@ describe("escrow", () => {
         await program.methods
           .initEscrow(
             escrowId,
-            mint,
+            toAsset(mint),
             new anchor.BN(depositAmount),
-            PublicKey.default,
-            new anchor.BN(receiveAmount)
+            toAsset(PublicKey.default),
+            new anchor.BN(receiveAmount),
+            { bond: null, challengeWindow: null, split: null, referral: null }
           )
           .accounts({
+            payoutSplit: null,
+            config: null,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             escrowAuthority: Bob.publicKey,
             receiver: Alice.publicKey,
             depositMint: mint,
@@ -371,16 +432,19 @@ describe("escrow", () => {
 
       // Accept Escrow
       await program.methods
-        .accept(escrowId)
-        .accounts({
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
           receiver: Alice.publicKey,
           escrowAuthority: Bob.publicKey,
-          receiveMint: escrow.receiveMint.equals(PublicKey.default)
-            ? escrow.depositMint
-            : escrow.receiveMint,
-          depositMint: escrow.depositMint.equals(PublicKey.default)
-            ? escrow.receiveMint
-            : escrow.depositMint,
+          receiveMint: legMint(escrow.receive),
+          depositMint: legMint(escrow.deposit),
         })
         .signers([Alice])
         .rpc({ commitment: "confirmed" });
@@ -394,7 +458,7 @@ describe("escrow", () => {
         provider,
         mint,
       });
-      escrowAfter = escrow.depositMint.equals(PublicKey.default)
+      escrowAfter = isNative(escrow.deposit)
         ? await getSolBalance({ account: escrowPda, provider })
         : await getMintBalance({ account: escrowPda, provider, mint });
 
@@ -409,6 +473,7 @@ describe("escrow", () => {
       );
 
       expect(escrowAfter).to.be.lessThan(depositAmount);
+      await checkRentReclaimed({ provider, program, escrowPda, mint });
 
       escrow = await program.account.escrow.fetch(escrowPda);
       expect(escrow.state).to.deep.equals({ completed: {} });
@@ -462,22 +527,31 @@ describe("escrow", () => {
         provider,
       });
 
-      escrowBefore = escrow.depositMint.equals(PublicKey.default)
+      escrowBefore = isNative(escrow.deposit)
         ? await getSolBalance({ account: escrowPda, provider })
         : await getMintBalance({ account: escrowPda, provider, mint: newMint });
+      const rentBack = await getAuthorityRentBack({
+        provider,
+        program,
+        escrowPda,
+        mint: newMint,
+      });
 
       // Accept Escrow
       await program.methods
-        .accept(escrowId)
-        .accounts({
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
           receiver: Alice.publicKey,
           escrowAuthority: Bob.publicKey,
-          receiveMint: escrow.receiveMint.equals(PublicKey.default)
-            ? escrow.depositMint
-            : escrow.receiveMint,
-          depositMint: escrow.depositMint.equals(PublicKey.default)
-            ? escrow.receiveMint
-            : escrow.depositMint,
+          receiveMint: legMint(escrow.receive),
+          depositMint: legMint(escrow.deposit),
         })
         .signers([Alice])
         .rpc({ commitment: "confirmed" });
@@ -493,18 +567,18 @@ describe("escrow", () => {
         provider,
       });
 
-      escrowAfter = escrow.depositMint.equals(PublicKey.default)
-        ? await getSolBalance({ account: escrowPda, provider })
-        : await getMintBalance({ account: escrowPda, provider, mint: newMint });
+      expect(escrowBefore).to.equals(depositAmount);
 
       expect(aliceAfterMint).to.equals(depositAmount, "Wrong Amount for Alice");
 
+      // Bob also gets back the rent of the escrow ata and receipt he paid for
+      // at init
       expect(bobAfterSol - bobBeforeSol).to.equals(
-        receiveAmount,
+        receiveAmount + rentBack,
         "Wrong Amount for Bob"
       );
 
-      expect(escrowBefore - escrowAfter).to.equals(depositAmount);
+      await checkRentReclaimed({ provider, program, escrowPda, mint: newMint });
 
       escrow = await program.account.escrow.fetch(escrowPda);
       expect(escrow.state).to.deep.equals({ completed: {} });
@@ -563,14 +637,24 @@ describe("escrow", () => {
       // Accept Escrow
       try {
         await program.methods
-          .accept(escrowId)
-          .accounts({
+          .accept(escrowId, false)
+          .accountsPartial({
+            ...getEscrowAccounts(
+              escrowId,
+              Bob.publicKey,
+              Alice.publicKey,
+              program.programId
+            ),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             receiver: Alice.publicKey,
             escrowAuthority: Bob.publicKey,
-            receiveMint: escrow.receiveMint.equals(PublicKey.default)
-              ? escrow.depositMint
-              : escrow.receiveMint,
+            receiveMint: legMint(escrow.receive),
             depositMint: newMint,
+            escrowDepositMintAta: await getEscrowATA(
+              provider.connection,
+              escrowPda,
+              mint
+            ),
           })
           .signers([Alice])
           .rpc({ commitment: "confirmed" });
@@ -626,12 +710,25 @@ describe("escrow", () => {
       // Accept Escrow
       try {
         await program.methods
-          .accept(escrowId)
-          .accounts({
+          .accept(escrowId, false)
+          .accountsPartial({
+            ...getEscrowAccounts(
+              escrowId,
+              Bob.publicKey,
+              Alice.publicKey,
+              program.programId
+            ),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             receiver: Alice.publicKey,
             escrowAuthority: Bob.publicKey,
             receiveMint: newMint,
-            depositMint: mint,
+            depositMint: null,
+            receiverReceiveMintAta: getAssociatedTokenAddressSync(
+              mint,
+              Alice.publicKey,
+              false,
+              TOKEN_2022_PROGRAM_ID
+            ),
           })
           .signers([Alice])
           .rpc({ commitment: "confirmed" });
@@ -691,16 +788,19 @@ describe("escrow", () => {
 
       // Accept Escrow
       await program.methods
-        .accept(escrowId)
-        .accounts({
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
           receiver: Alice.publicKey,
           escrowAuthority: Bob.publicKey,
-          receiveMint: escrow.receiveMint.equals(PublicKey.default)
-            ? escrow.depositMint
-            : escrow.receiveMint,
-          depositMint: escrow.depositMint.equals(PublicKey.default)
-            ? escrow.receiveMint
-            : escrow.depositMint,
+          receiveMint: legMint(escrow.receive),
+          depositMint: legMint(escrow.deposit),
         })
         .signers([Alice])
         .rpc({ commitment: "confirmed" });
@@ -714,7 +814,7 @@ describe("escrow", () => {
         provider,
         mint,
       });
-      escrowAfter = escrow.depositMint.equals(PublicKey.default)
+      escrowAfter = isNative(escrow.deposit)
         ? await getSolBalance({ account: escrowPda, provider })
         : await getMintBalance({ account: escrowPda, provider, mint });
 
@@ -737,16 +837,19 @@ describe("escrow", () => {
       // Accept Escrow
       try {
         await program.methods
-          .accept(escrowId)
-          .accounts({
+          .accept(escrowId, false)
+          .accountsPartial({
+            ...getEscrowAccounts(
+              escrowId,
+              Bob.publicKey,
+              Alice.publicKey,
+              program.programId
+            ),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
             receiver: Alice.publicKey,
             escrowAuthority: Bob.publicKey,
-            receiveMint: escrow.receiveMint.equals(PublicKey.default)
-              ? escrow.depositMint
-              : escrow.receiveMint,
-            depositMint: escrow.depositMint.equals(PublicKey.default)
-              ? escrow.receiveMint
-              : escrow.depositMint,
+            receiveMint: legMint(escrow.receive),
+            depositMint: legMint(escrow.deposit),
           })
           .signers([Alice])
           .rpc({ commitment: "confirmed" });
@@ -756,6 +859,201 @@ describe("escrow", () => {
     });
   });
 
+  describe("Accept Compute Benchmark", () => {
+    // ceiling for a lean accept. The stats are logged, raise it only with
+    // the numbers that justify it
+    const ACCEPT_COMPUTE_UNITS = 80_000;
+
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    beforeEach(async () => {
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+      await ensureNativeMint(provider, Bob);
+
+      // both sides already hold the mint, so nothing needs creating
+      mint = (
+        await createAndMintTokens(
+          provider.connection,
+          Alice,
+          1 * LAMPORTS_PER_SOL
+        )
+      ).mint;
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        Bob,
+        mint,
+        Bob.publicKey,
+        false,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+    });
+
+    const acceptInstruction = async (escrowId: string) => {
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      const escrow = await program.account.escrow.fetch(escrowPda);
+
+      return program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: legMint(escrow.receive),
+          depositMint: legMint(escrow.deposit),
+          // only legacy escrows hold SOL in a vault
+          solVault: null,
+        })
+        .instruction();
+    };
+
+    // The old context took the mint and the ATAs of both legs, SOL legs as
+    // wSOL, and had the receiver create whichever were missing. It can't be
+    // built any more, so that work is replayed as idempotent ATA creations
+    // in front of the same accept
+    const oldAcceptInstructions = async (escrowId: string) => {
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      const ata = (mint: PublicKey, owner: PublicKey) =>
+        createAssociatedTokenAccountIdempotentInstruction(
+          Alice.publicKey,
+          getAssociatedTokenAddressSync(
+            mint,
+            owner,
+            true,
+            TOKEN_2022_PROGRAM_ID
+          ),
+          owner,
+          mint,
+          TOKEN_2022_PROGRAM_ID
+        );
+      const depositMint = assetMint(escrow.deposit);
+      const receiveMint = assetMint(escrow.receive);
+
+      return [
+        ata(depositMint, escrowPda),
+        ata(depositMint, Alice.publicKey),
+        ata(depositMint, Bob.publicKey),
+        ata(receiveMint, Alice.publicKey),
+        ata(receiveMint, Bob.publicKey),
+        await acceptInstruction(escrowId),
+      ];
+    };
+
+    const send = async (instructions: any[]) => {
+      const signature = await provider.sendAndConfirm(
+        new Transaction().add(...instructions),
+        [Alice],
+        { commitment: "confirmed" }
+      );
+      return getTransactionStats(provider, signature);
+    };
+
+    // SOL -> token used to create the escrow ata plus both deposit mint atas,
+    // now it takes only what the token leg touches
+    it("Should accept a SOL for token escrow without creating accounts", async () => {
+      for (const escrowId of ["bench_01", "bench_01_old"]) {
+        await init_escrow({
+          program,
+          escrowId,
+          escrowAuthority: Bob,
+          receiver: Alice,
+          depositMint: PublicKey.default,
+          receiveMint: mint,
+          depositAmount,
+          receiveAmount,
+        });
+      }
+
+      const aliceBeforeSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+      const lean = await send([await acceptInstruction("bench_01")]);
+
+      // no rent paid by Alice, she gets exactly the deposit
+      const aliceAfterSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+      expect(aliceAfterSol - aliceBeforeSol).to.equal(depositAmount);
+
+      const old = await send(await oldAcceptInstructions("bench_01_old"));
+      console.log("accept SOL -> token", { lean, old });
+
+      expect(lean.createdAccounts).to.equal(0);
+      // the escrow's wSOL ata at the very least
+      expect(old.createdAccounts).to.be.above(0);
+      expect(lean.accounts).to.be.below(old.accounts);
+      expect(lean.computeUnits).to.be.below(old.computeUnits);
+      expect(lean.computeUnits).to.be.below(ACCEPT_COMPUTE_UNITS);
+    });
+
+    it("Should accept a token for SOL escrow without creating accounts", async () => {
+      // Bob deposits tokens this time
+      const res = await createAndMintTokens(
+        provider.connection,
+        Bob,
+        1 * LAMPORTS_PER_SOL
+      );
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        Alice,
+        res.mint,
+        Alice.publicKey,
+        false,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      for (const escrowId of ["bench_02", "bench_02_old"]) {
+        await init_escrow({
+          program,
+          escrowId,
+          escrowAuthority: Bob,
+          receiver: Alice,
+          depositMint: res.mint,
+          receiveMint: PublicKey.default,
+          depositAmount,
+          receiveAmount,
+        });
+      }
+
+      const lean = await send([await acceptInstruction("bench_02")]);
+      const old = await send(await oldAcceptInstructions("bench_02_old"));
+      console.log("accept token -> SOL", { lean, old });
+
+      expect(lean.createdAccounts).to.equal(0);
+      expect(lean.accounts).to.be.below(old.accounts);
+      expect(lean.computeUnits).to.be.below(old.computeUnits);
+      expect(lean.computeUnits).to.be.below(ACCEPT_COMPUTE_UNITS);
+    });
+  });
+
   describe("Close Escrow", () => {
     let depositAmount: number, receiveAmount: number, mint: PublicKey;
     let bobBeforeSol: number, bobAfterSol: number;
@@ -816,12 +1114,19 @@ describe("escrow", () => {
 
       await program.methods
         .close(escrowId)
-        .accounts({
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
           escrowAuthority: Bob.publicKey,
           receiver: Alice.publicKey,
-          depositMint: escrow.depositMint.equals(PublicKey.default)
-            ? escrow.receiveMint
-            : escrow.depositMint,
+          depositMint: isNative(escrow.deposit)
+            ? assetMint(escrow.receive)
+            : assetMint(escrow.deposit),
         })
         .signers([Bob])
         .rpc({ commitment: "confirmed" });
@@ -834,6 +1139,7 @@ describe("escrow", () => {
       expect(bobAfterSol - bobBeforeSol).to.be.greaterThanOrEqual(
         escrow.depositAmount.toNumber()
       );
+      await checkRentReclaimed({ provider, program, escrowPda, mint });
 
       try {
         await program.account.escrow.fetch(escrowPda);
@@ -902,12 +1208,19 @@ describe("escrow", () => {
 
       await program.methods
         .close(escrowId)
-        .accounts({
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
           escrowAuthority: Bob.publicKey,
           receiver: Alice.publicKey,
-          depositMint: escrow.depositMint.equals(PublicKey.default)
-            ? escrow.receiveMint
-            : escrow.depositMint,
+          depositMint: isNative(escrow.deposit)
+            ? assetMint(escrow.receive)
+            : assetMint(escrow.deposit),
         })
         .signers([Bob])
         .rpc({ commitment: "confirmed" });
@@ -921,6 +1234,7 @@ describe("escrow", () => {
       expect(bobAfterMint - bobBeforeMint).to.be.equals(
         escrow.depositAmount.toNumber()
       );
+      await checkRentReclaimed({ provider, program, escrowPda, mint });
 
       try {
         await program.account.escrow.fetch(escrowPda);
@@ -936,112 +1250,2278 @@ describe("escrow", () => {
       }
     });
   });
-});
 
-const init_escrow = async ({
-  program,
-  escrowId,
-  escrowAuthority,
-  receiver,
-  depositMint,
-  receiveMint,
-  depositAmount,
-  receiveAmount,
-}) => {
-  return await program.methods
-    .initEscrow(
-      escrowId,
-      depositMint,
-      new anchor.BN(depositAmount),
-      receiveMint,
-      new anchor.BN(receiveAmount)
-    )
-    .accounts({
-      escrowAuthority: escrowAuthority.publicKey,
-      receiver: receiver.publicKey,
-      depositMint: depositMint == PublicKey.default ? receiveMint : depositMint,
-    })
-    .signers([escrowAuthority])
-    .rpc({ commitment: "confirmed" });
-};
+  describe("Native and Wrapped SOL", () => {
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    beforeEach(async () => {
+      // airdrop Bob
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
 
-const checkInitEscrow = async ({
-  provider,
-  program,
-  escrowId,
-  escrowAuthority,
-  receiver,
-  depositMint,
-  receiveMint,
-  depositAmount,
-  receiveAmount,
-}) => {
-  // Get Pda of the escrow via the ID
-  let [escrowPda, escrowBump] = getEscrowAddress(
-    escrowId,
-    escrowAuthority.publicKey,
-    receiver.publicKey,
-    program.programId
-  );
+      await ensureNativeMint(provider, Bob);
+    });
 
-  let escrow = await program.account.escrow.fetch(escrowPda);
+    it("Should treat native SOL and wSOL as the same token", async () => {
+      const escrowId = "wsol_01";
 
-  // // Basic Asserts
-  expect(escrowBump).to.be.a("number");
-  expect(escrow.escrowAuthority.toString()).to.equal(
-    escrowAuthority.publicKey.toString()
-  );
-  expect(escrow.receiver.toString()).to.equal(receiver.publicKey.toString());
-  expect(escrow.escrowId).to.equal(escrowId);
+      try {
+        await init_escrow({
+          program,
+          escrowId,
+          escrowAuthority: Bob,
+          receiver: Alice,
+          depositMint: PublicKey.default,
+          receiveMint: NATIVE_MINT_2022,
+          depositAmount,
+          receiveAmount,
+        });
+        assert.fail("Expected SOL to wSOL escrow to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Token to the same token is not allowed"
+        );
+      }
+    });
 
-  // Mint Asserts
-  expect(escrow.depositMint.toString()).to.equal(
-    depositMint.toString(),
-    "Deposit mint mismatch"
-  );
+    it("Should allow Bob to deposit wSOL and Alice to receive native SOL", async () => {
+      const escrowId = "wsol_02";
 
-  expect(escrow.receiveMint.toString()).to.equal(
-    receiveMint.toString(),
-    "Receive mint mismatch"
-  );
+      const res = await createAndMintTokens(
+        provider.connection,
+        Alice,
+        1 * LAMPORTS_PER_SOL
+      );
+      mint = res.mint;
 
-  // Amount Asserts
-  expect(escrow.depositAmount.toNumber()).to.equal(
-    depositAmount,
-    "Deposit amount mismatch"
-  );
+      await wrapSol(provider, Bob, depositAmount);
 
-  expect(escrow.receiveAmount.toNumber()).to.equal(
-    receiveAmount,
-    "Receive amount mismatch"
-  );
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: NATIVE_MINT_2022,
+        receiveMint: mint,
+        depositAmount,
+        receiveAmount,
+      });
 
-  // State
-  expect(escrow.state).to.deep.equal({ active: {} });
+      await checkInitEscrow({
+        provider,
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: NATIVE_MINT_2022,
+        receiveMint: mint,
+        depositAmount,
+        receiveAmount,
+      });
 
-  checkEscrowBalance({
-    provider,
-    program,
-    escrow,
-    escrowPda,
-    type: depositMint == PublicKey.default ? "SOL" : "MINT",
-    mint: depositMint == PublicKey.default ? receiveMint : depositMint,
-  });
-};
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.deposit).to.deep.equals({ native: {} });
+      expect(escrow.authorityWrappedSol).to.equals(true);
 
-const checkEscrowBalance = async ({
-  provider,
-  program,
-  escrow,
-  escrowPda,
-  type,
+      const aliceBeforeSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: mint,
+          depositMint: null,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const aliceAfterSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+
+      expect(aliceAfterSol - aliceBeforeSol).to.equals(
+        depositAmount,
+        "Wrong Amount for Alice"
+      );
+    });
+
+    it("Should allow Alice to pay with wSOL and Bob to receive native SOL", async () => {
+      const escrowId = "wsol_03";
+
+      const res = await createAndMintTokens(
+        provider.connection,
+        Bob,
+        1 * LAMPORTS_PER_SOL
+      );
+      mint = res.mint;
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+      });
+
+      await wrapSol(provider, Alice, receiveAmount);
+
+      const bobBeforeSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+      const rentBack = await getAuthorityRentBack({
+        provider,
+        program,
+        escrowPda: getEscrowAddress(
+          escrowId,
+          Bob.publicKey,
+          Alice.publicKey,
+          program.programId
+        )[0],
+        mint,
+      });
+
+      await program.methods
+        .accept(escrowId, true)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: NATIVE_MINT_2022,
+          depositMint: mint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const bobAfterSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+
+      expect(bobAfterSol - bobBeforeSol).to.equals(
+        receiveAmount + rentBack,
+        "Wrong Amount for Bob"
+      );
+    });
+
+    it("Should pay Alice in wSOL when she asks for it", async () => {
+      const escrowId = "wsol_04";
+
+      const res = await createAndMintTokens(
+        provider.connection,
+        Alice,
+        1 * LAMPORTS_PER_SOL
+      );
+      mint = res.mint;
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: PublicKey.default,
+        receiveMint: mint,
+        depositAmount,
+        receiveAmount,
+      });
+
+      await program.methods
+        .accept(escrowId, true)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: mint,
+          depositMint: NATIVE_MINT_2022,
+          // SOL deposits sit in the sol vault, there is no escrow ata
+          escrowDepositMintAta: null,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const aliceWsol = await getMintBalance({
+        account: Alice.publicKey,
+        provider,
+        mint: NATIVE_MINT_2022,
+      });
+
+      expect(aliceWsol).to.equals(depositAmount, "Wrong wSOL for Alice");
+    });
+  });
+
+  describe("SPL Token Assets", () => {
+    let depositAmount: number, receiveAmount: number, splMint: PublicKey;
+    beforeEach(async () => {
+      // airdrop Bob
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.2 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      const res = await createAndMintTokens(
+        provider.connection,
+        Bob,
+        1 * LAMPORTS_PER_SOL,
+        TOKEN_PROGRAM_ID
+      );
+      splMint = res.mint;
+    });
+
+    it("Should Allow Alice to Accept an Escrow funded with a classic SPL token", async () => {
+      const escrowId = "spl_01";
+
+      await program.methods
+        .initEscrow(
+          escrowId,
+          { splToken: { mint: splMint, program: TOKEN_PROGRAM_ID } },
+          new anchor.BN(depositAmount),
+          { native: {} },
+          new anchor.BN(receiveAmount),
+          { bond: null, challengeWindow: null, split: null, referral: null }
+        )
+        .accounts({
+          payoutSplit: null,
+          config: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          escrowAuthority: Bob.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: splMint,
+        })
+        .signers([Bob])
+        .rpc({ commitment: "confirmed" });
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.deposit.splToken.mint.toString()).to.equal(
+        splMint.toString()
+      );
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+          depositMint: splMint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const aliceAfterMint = await getMintBalance({
+        account: Alice.publicKey,
+        provider,
+        mint: splMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      });
+      expect(aliceAfterMint).to.equals(depositAmount, "Wrong Amount for Alice");
+
+      escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.state).to.deep.equals({ completed: {} });
+    });
+
+    it("Should fail when the token program does not own the asset", async () => {
+      const escrowId = "spl_02";
+
+      try {
+        await program.methods
+          .initEscrow(
+            escrowId,
+            { splToken: { mint: splMint, program: TOKEN_2022_PROGRAM_ID } },
+            new anchor.BN(depositAmount),
+            { native: {} },
+            new anchor.BN(receiveAmount),
+            { bond: null, challengeWindow: null, split: null, referral: null }
+          )
+          .accounts({
+            payoutSplit: null,
+            config: null,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            escrowAuthority: Bob.publicKey,
+            receiver: Alice.publicKey,
+            depositMint: splMint,
+          })
+          .signers([Bob])
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Expected init to fail");
+      } catch (error) {
+        assert.strictEqual(error.error.errorMessage, "Invalid token program");
+      }
+    });
+  });
+
+  describe("Commit Bond", () => {
+    let depositAmount: number, receiveAmount: number, bondAmount: number;
+    let mint: PublicKey;
+    beforeEach(async () => {
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+      bondAmount = 0.05 * LAMPORTS_PER_SOL;
+
+      // Bob deposits tokens for SOL, so the bond is in SOL
+      mint = (
+        await createAndMintTokens(provider.connection, Bob, 1 * LAMPORTS_PER_SOL)
+      ).mint;
+    });
+
+    const commit = (escrowId: string, receiveMint: PublicKey | null = null) =>
+      program.methods
+        .commit(escrowId, false)
+        .accountsPartial({
+          escrow: getEscrowAddress(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          )[0],
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+    const close = (escrowId: string) =>
+      program.methods
+        .close(escrowId)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: Bob.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: mint,
+          receiveMint: null,
+        })
+        .signers([Bob])
+        .rpc({ commitment: "confirmed" });
+
+    it("Should lock the offer to Alice and return her bond on accept", async () => {
+      const escrowId = "bond_01";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        bond: { amount: new anchor.BN(bondAmount), window: new anchor.BN(3600) },
+      });
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      const escrowBefore = await getSolBalance({ account: escrowPda, provider });
+
+      await commit(escrowId);
+
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.committedUntil.toNumber()).to.be.greaterThan(0);
+      expect(
+        (await getSolBalance({ account: escrowPda, provider })) - escrowBefore
+      ).to.equal(bondAmount);
+
+      try {
+        await commit(escrowId);
+        assert.fail("Expected a second commit to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Escrow is already committed"
+        );
+      }
+
+      try {
+        await close(escrowId);
+        assert.fail("Expected close to fail inside the commit window");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Escrow is committed to the receiver"
+        );
+      }
+
+      const aliceBeforeSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+          depositMint: mint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const aliceAfterSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+
+      // bond back, receive leg paid; Alice also funds her deposit mint ata
+      expect(aliceAfterSol - aliceBeforeSol).to.be.lessThanOrEqual(
+        bondAmount - receiveAmount
+      );
+      expect(aliceAfterSol - aliceBeforeSol).to.be.greaterThan(0);
+
+      escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.state).to.deep.equals({ completed: {} });
+    });
+
+    it("Should fail to commit to an escrow without bond terms", async () => {
+      const escrowId = "bond_02";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+      });
+
+      try {
+        await commit(escrowId);
+        assert.fail("Expected commit to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Escrow does not take a commit bond"
+        );
+      }
+    });
+
+    it("Should forfeit the bond to Bob once the window ends", async () => {
+      const escrowId = "bond_03";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        bond: { amount: new anchor.BN(bondAmount), window: new anchor.BN(1) },
+      });
+
+      await commit(escrowId);
+
+      // let the commit window pass
+      await new Promise((resolve) => setTimeout(resolve, 3000));
+
+      const bobBeforeSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+
+      await close(escrowId);
+
+      const bobAfterSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+
+      expect(bobAfterSol - bobBeforeSol).to.be.greaterThan(bondAmount);
+    });
+  });
+
+  describe("Two-Phase Settle", () => {
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    beforeEach(async () => {
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      // Bob deposits tokens, Alice pays SOL
+      mint = (
+        await createAndMintTokens(provider.connection, Bob, 1 * LAMPORTS_PER_SOL)
+      ).mint;
+    });
+
+    const fund = (escrowId: string) =>
+      program.methods
+        .fund(escrowId, false)
+        .accountsPartial({
+          escrow: getEscrowAddress(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          )[0],
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+    const settle = (escrowId: string, signer: Keypair) =>
+      program.methods
+        .settle(escrowId)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          signer: signer.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: mint,
+          receiveMint: null,
+          solVault: null,
+        })
+        .signers([signer])
+        .rpc({ commitment: "confirmed" });
+
+    it("Should hold both legs until the challenge window ends", async () => {
+      const escrowId = "settle_01";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        challengeWindow: new anchor.BN(1),
+      });
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      const escrowBefore = await getSolBalance({ account: escrowPda, provider });
+
+      await fund(escrowId);
+
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.state).to.deep.equals({ funded: {} });
+      expect(
+        (await getSolBalance({ account: escrowPda, provider })) - escrowBefore
+      ).to.equal(receiveAmount);
+
+      // a funded escrow can't be accepted the one-shot way
+      try {
+        await program.methods
+          .accept(escrowId, false)
+          .accountsPartial({
+            ...getEscrowAccounts(
+              escrowId,
+              Bob.publicKey,
+              Alice.publicKey,
+              program.programId
+            ),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+            receiver: Alice.publicKey,
+            escrowAuthority: Bob.publicKey,
+            receiveMint: null,
+            depositMint: mint,
+          })
+          .signers([Alice])
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Expected accept to fail");
+      } catch (error) {
+        assert.strictEqual(error.error.errorMessage, "Invalid State");
+      }
+
+      // let the challenge window pass
+      await new Promise((resolve) => setTimeout(resolve, 3000));
+
+      const bobBeforeSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+
+      // Alice settles, Bob gets paid all the same
+      await settle(escrowId, Alice);
+
+      const bobAfterSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+      const aliceAfterMint = await getMintBalance({
+        account: Alice.publicKey,
+        provider,
+        mint,
+      });
+
+      // receive leg plus the escrow ata rent
+      expect(bobAfterSol - bobBeforeSol).to.be.greaterThan(receiveAmount);
+      expect(aliceAfterMint).to.equal(depositAmount);
+
+      escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.state).to.deep.equals({ completed: {} });
+      await checkRentReclaimed({ provider, program, escrowPda, mint });
+    });
+
+    it("Should refund both legs when Bob backs out inside the window", async () => {
+      const escrowId = "settle_02";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        challengeWindow: new anchor.BN(3600),
+      });
+
+      await fund(escrowId);
+
+      try {
+        await settle(escrowId, Bob);
+        assert.fail("Expected settle to fail inside the window");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Challenge window is still open"
+        );
+      }
+
+      const aliceBeforeSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+      const bobBeforeMint = await getMintBalance({
+        account: Bob.publicKey,
+        provider,
+        mint,
+      });
+
+      await program.methods
+        .close(escrowId)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: Bob.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: mint,
+          receiveMint: null,
+        })
+        .signers([Bob])
+        .rpc({ commitment: "confirmed" });
+
+      const aliceAfterSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+      const bobAfterMint = await getMintBalance({
+        account: Bob.publicKey,
+        provider,
+        mint,
+      });
+
+      expect(aliceAfterSol - aliceBeforeSol).to.equal(receiveAmount);
+      expect(bobAfterMint - bobBeforeMint).to.equal(depositAmount);
+    });
+  });
+
+  describe("Counter Offer", () => {
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    const counterAmount = 0.005 * LAMPORTS_PER_SOL;
+
+    beforeEach(async () => {
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      // Bob deposits tokens, Alice pays SOL
+      mint = (
+        await createAndMintTokens(provider.connection, Bob, 1 * LAMPORTS_PER_SOL)
+      ).mint;
+    });
+
+    const setup = async (escrowId: string) => {
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+      });
+
+      return getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      )[0];
+    };
+
+    const counter = (escrowId: string, fund: boolean) =>
+      program.methods
+        .counterOffer(escrowId, new anchor.BN(counterAmount), fund, false)
+        .accountsPartial({
+          escrow: getEscrowAddress(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          )[0],
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+    // the authority side names the counter it accepts
+    const acceptCounter = (escrowId: string, expected = counterAmount) =>
+      program.methods
+        .acceptCounter(escrowId, new anchor.BN(expected))
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: Bob.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: mint,
+          receiveMint: null,
+          solVault: null,
+        })
+        .signers([Bob])
+        .rpc({ commitment: "confirmed" });
+
+    it("Should take an unfunded counter as the new terms", async () => {
+      const escrowId = "counter_01";
+      const escrowPda = await setup(escrowId);
+
+      await counter(escrowId, false);
+
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.counterAmount.toNumber()).to.equal(counterAmount);
+      expect(escrow.counterFunded).to.equal(false);
+
+      await acceptCounter(escrowId);
+
+      escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.state).to.deep.equals({ active: {} });
+      expect(escrow.receiveAmount.toNumber()).to.equal(counterAmount);
+      expect(escrow.counterAmount.toNumber()).to.equal(0);
+
+      // Alice accepts the counter terms the usual way
+      const bobBeforeSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+      const rentBack = await getAuthorityRentBack({
+        provider,
+        program,
+        escrowPda,
+        mint,
+      });
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+          depositMint: mint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const bobAfterSol = await getSolBalance({
+        account: Bob.publicKey,
+        provider,
+      });
+
+      // counter amount plus the escrow ata and receipt rent
+      expect(bobAfterSol - bobBeforeSol).to.equal(counterAmount + rentBack);
+    });
+
+    it("Should settle a funded counter when Bob accepts it", async () => {
+      const escrowId = "counter_02";
+      const escrowPda = await setup(escrowId);
+
+      const escrowBefore = await getSolBalance({ account: escrowPda, provider });
+      await counter(escrowId, true);
+      expect(
+        (await getSolBalance({ account: escrowPda, provider })) - escrowBefore
+      ).to.equal(counterAmount);
+
+      // the stored terms are off the table while the counter is funded
+      try {
+        await program.methods
+          .accept(escrowId, false)
+          .accountsPartial({
+            ...getEscrowAccounts(
+              escrowId,
+              Bob.publicKey,
+              Alice.publicKey,
+              program.programId
+            ),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+            receiver: Alice.publicKey,
+            escrowAuthority: Bob.publicKey,
+            receiveMint: null,
+            depositMint: mint,
+          })
+          .signers([Alice])
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Expected accept to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Escrow has a funded counter-offer pending"
+        );
+      }
+
+      await acceptCounter(escrowId);
+
+      const aliceAfterMint = await getMintBalance({
+        account: Alice.publicKey,
+        provider,
+        mint,
+      });
+      expect(aliceAfterMint).to.equal(depositAmount);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.state).to.deep.equals({ completed: {} });
+      expect(escrow.receiveAmount.toNumber()).to.equal(counterAmount);
+      await checkRentReclaimed({ provider, program, escrowPda, mint });
+    });
+
+    it("Should refund a funded counter when Bob closes", async () => {
+      const escrowId = "counter_03";
+      await setup(escrowId);
+
+      await counter(escrowId, true);
+
+      const aliceBeforeSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+
+      await program.methods
+        .close(escrowId)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: Bob.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: mint,
+          receiveMint: null,
+        })
+        .signers([Bob])
+        .rpc({ commitment: "confirmed" });
+
+      const aliceAfterSol = await getSolBalance({
+        account: Alice.publicKey,
+        provider,
+      });
+      expect(aliceAfterSol - aliceBeforeSol).to.equal(counterAmount);
+    });
+
+    it("Should not accept a counter that was never made", async () => {
+      const escrowId = "counter_04";
+      await setup(escrowId);
+
+      try {
+        await acceptCounter(escrowId);
+        assert.fail("Expected accept_counter to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "No counter-offer to accept"
+        );
+      }
+    });
+  });
+
+  describe("Transfer Authority", () => {
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    let Carol: Keypair;
+
+    beforeEach(async () => {
+      Carol = Keypair.generate();
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      await airdrop(
+        provider.connection,
+        Carol.publicKey,
+        0.1 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      mint = (
+        await createAndMintTokens(provider.connection, Bob, 1 * LAMPORTS_PER_SOL)
+      ).mint;
+    });
+
+    const setup = async (escrowId: string) => {
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+      });
+
+      // the address stays derived from Bob whoever holds the authority side
+      return getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      )[0];
+    };
+
+    const transferAuthority = (
+      escrowId: string,
+      escrowPda: PublicKey,
+      from: Keypair,
+      to: PublicKey
+    ) =>
+      program.methods
+        .transferAuthority(escrowId)
+        .accountsPartial({
+          escrow: escrowPda,
+          ...getReceiptAccounts(escrowPda, from.publicKey, program.programId),
+          newAuthorityReceiptAta: getReceiptAccounts(
+            escrowPda,
+            to,
+            program.programId
+          ).receiptAccount,
+          escrowAuthority: from.publicKey,
+          newAuthority: to,
+        })
+        .signers([from])
+        .rpc({ commitment: "confirmed" });
+
+    it("Should pay the new authority when Alice accepts", async () => {
+      const escrowId = "transfer_01";
+      const escrowPda = await setup(escrowId);
+
+      await transferAuthority(escrowId, escrowPda, Bob, Carol.publicKey);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.escrowAuthority.toBase58()).to.equal(
+        Carol.publicKey.toBase58()
+      );
+      expect(escrow.seedAuthority.toBase58()).to.equal(
+        Bob.publicKey.toBase58()
+      );
+
+      // the receipt went along, Bob gave up the right to cancel
+      const { receiptMint } = getReceiptAccounts(
+        escrowPda,
+        Bob.publicKey,
+        program.programId
+      );
+      expect(
+        await getMintBalance({
+          account: Carol.publicKey,
+          provider,
+          mint: receiptMint,
+        })
+      ).to.equal(1);
+
+      // Bob signed the transfer, his emptied receipt account went back to him
+      const bobReceipt = getReceiptAccounts(
+        escrowPda,
+        Bob.publicKey,
+        program.programId
+      ).receiptAccount;
+      expect(await provider.connection.getAccountInfo(bobReceipt)).to.equal(
+        null
+      );
+
+      try {
+        await program.methods
+          .close(escrowId)
+          .accountsPartial({
+            escrow: escrowPda,
+            ...getReceiptAccounts(escrowPda, Bob.publicKey, program.programId),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+            escrowAuthority: Bob.publicKey,
+            receiver: Alice.publicKey,
+            depositMint: mint,
+            receiveMint: null,
+          })
+          .signers([Bob])
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Expected close to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "The program expected this account to be already initialized"
+        );
+      }
+
+      const carolBeforeSol = await getSolBalance({
+        account: Carol.publicKey,
+        provider,
+      });
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          escrow: escrowPda,
+          ...getReceiptAccounts(escrowPda, Carol.publicKey, program.programId),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Carol.publicKey,
+          receiveMint: null,
+          depositMint: mint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const carolAfterSol = await getSolBalance({
+        account: Carol.publicKey,
+        provider,
+      });
+      const aliceAfterMint = await getMintBalance({
+        account: Alice.publicKey,
+        provider,
+        mint,
+      });
+
+      // receive leg plus the escrow ata rent
+      expect(carolAfterSol - carolBeforeSol).to.be.greaterThan(receiveAmount);
+      expect(aliceAfterMint).to.equal(depositAmount);
+    });
+
+    it("Should let the new authority cancel and get the deposit back", async () => {
+      const escrowId = "transfer_02";
+      const escrowPda = await setup(escrowId);
+
+      await transferAuthority(escrowId, escrowPda, Bob, Carol.publicKey);
+
+      await program.methods
+        .close(escrowId)
+        .accountsPartial({
+          escrow: escrowPda,
+          ...getReceiptAccounts(escrowPda, Carol.publicKey, program.programId),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: Carol.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: mint,
+          receiveMint: null,
+        })
+        .signers([Carol])
+        .rpc({ commitment: "confirmed" });
+
+      const carolAfterMint = await getMintBalance({
+        account: Carol.publicKey,
+        provider,
+        mint,
+      });
+      expect(carolAfterMint).to.equal(depositAmount);
+    });
+
+    it("Should not hand the authority side to the receiver", async () => {
+      const escrowId = "transfer_03";
+      const escrowPda = await setup(escrowId);
+
+      try {
+        await transferAuthority(escrowId, escrowPda, Bob, Alice.publicKey);
+        assert.fail("Expected transfer_authority to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Token to same buyer and seller is not allowed"
+        );
+      }
+    });
+  });
+
+  describe("Escrow Receipt", () => {
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    let Carol: Keypair;
+
+    beforeEach(async () => {
+      Carol = Keypair.generate();
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      await airdrop(
+        provider.connection,
+        Carol.publicKey,
+        0.1 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      mint = (
+        await createAndMintTokens(provider.connection, Bob, 1 * LAMPORTS_PER_SOL)
+      ).mint;
+    });
+
+    const setup = async (escrowId: string) => {
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+      });
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      return {
+        escrowPda,
+        ...getReceiptAccounts(escrowPda, Bob.publicKey, program.programId),
+      };
+    };
+
+    // sell the receipt to Carol outside the program
+    const tradeReceipt = async (receiptMint: PublicKey) => {
+      const carolReceipt = await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        Carol,
+        receiptMint,
+        Carol.publicKey,
+        false,
+        "confirmed",
+        { commitment: "confirmed" },
+        TOKEN_2022_PROGRAM_ID
+      );
+      await transferChecked(
+        provider.connection,
+        Bob,
+        getAssociatedTokenAddressSync(
+          receiptMint,
+          Bob.publicKey,
+          false,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        receiptMint,
+        carolReceipt.address,
+        Bob,
+        1,
+        0,
+        [],
+        { commitment: "confirmed" },
+        TOKEN_2022_PROGRAM_ID
+      );
+    };
+
+    it("Should mint a single receipt to Bob at init", async () => {
+      const { escrowPda, receiptMint, receiptAccount } = await setup(
+        "receipt_01"
+      );
+
+      const receipt = await getMint(
+        provider.connection,
+        receiptMint,
+        "confirmed",
+        TOKEN_2022_PROGRAM_ID
+      );
+      expect(Number(receipt.supply)).to.equal(1);
+      expect(receipt.decimals).to.equal(0);
+      expect(receipt.mintAuthority.toBase58()).to.equal(escrowPda.toBase58());
+
+      const account = await getAccount(
+        provider.connection,
+        receiptAccount,
+        "confirmed",
+        TOKEN_2022_PROGRAM_ID
+      );
+      expect(Number(account.amount)).to.equal(1);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.hasReceipt).to.equal(true);
+    });
+
+    it("Should pay whoever holds the receipt and burn it", async () => {
+      const escrowId = "receipt_02";
+      const { escrowPda, receiptMint } = await setup(escrowId);
+
+      await tradeReceipt(receiptMint);
+
+      const carolBeforeSol = await getSolBalance({
+        account: Carol.publicKey,
+        provider,
+      });
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId,
+            Carol.publicKey
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Carol.publicKey,
+          receiveMint: null,
+          depositMint: mint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const carolAfterSol = await getSolBalance({
+        account: Carol.publicKey,
+        provider,
+      });
+
+      // receive leg plus the escrow ata and receipt mint rent
+      expect(carolAfterSol - carolBeforeSol).to.be.greaterThan(receiveAmount);
+      expect(
+        await getMintBalance({
+          account: Carol.publicKey,
+          provider,
+          mint: receiptMint,
+        })
+      ).to.equal(0);
+      expect(await provider.connection.getAccountInfo(receiptMint)).to.equal(
+        null
+      );
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.hasReceipt).to.equal(false);
+      expect(escrow.escrowAuthority.toBase58()).to.equal(
+        Carol.publicKey.toBase58()
+      );
+    });
+
+    it("Should refund the receipt holder on close", async () => {
+      const escrowId = "receipt_03";
+      const { receiptMint } = await setup(escrowId);
+
+      await tradeReceipt(receiptMint);
+
+      // Bob sold his side, he can't take the deposit back any more
+      try {
+        await program.methods
+          .close(escrowId)
+          .accountsPartial({
+            ...getEscrowAccounts(
+              escrowId,
+              Bob.publicKey,
+              Alice.publicKey,
+              program.programId
+            ),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+            escrowAuthority: Bob.publicKey,
+            receiver: Alice.publicKey,
+            depositMint: mint,
+            receiveMint: null,
+          })
+          .signers([Bob])
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Expected close to fail");
+      } catch (error) {
+        assert.strictEqual(error.error.errorMessage, "Not the receipt holder");
+      }
+
+      await program.methods
+        .close(escrowId)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId,
+            Carol.publicKey
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: Carol.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: mint,
+          receiveMint: null,
+        })
+        .signers([Carol])
+        .rpc({ commitment: "confirmed" });
+
+      expect(
+        await getMintBalance({ account: Carol.publicKey, provider, mint })
+      ).to.equal(depositAmount);
+      expect(await provider.connection.getAccountInfo(receiptMint)).to.equal(
+        null
+      );
+    });
+  });
+
+  describe("Payout Split", () => {
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    let recipients: Keypair[];
+
+    beforeEach(async () => {
+      recipients = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      // one lamport of rounding dust for the first share
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL + 1;
+
+      mint = (
+        await createAndMintTokens(provider.connection, Bob, 1 * LAMPORTS_PER_SOL)
+      ).mint;
+    });
+
+    const toShares = (bps: number[]) =>
+      recipients.map((recipient, i) => ({
+        recipient: recipient.publicKey,
+        bps: bps[i],
+      }));
+
+    const accept = (escrowId: string, order: Keypair[]) => {
+      const accounts = getEscrowAccounts(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      return program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...accounts,
+          payoutSplit: getSplitAddress(accounts.escrow, program.programId),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+          depositMint: mint,
+        })
+        .remainingAccounts(
+          order.map((recipient) => ({
+            pubkey: recipient.publicKey,
+            isWritable: true,
+            isSigner: false,
+          }))
+        )
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+    };
+
+    it("Should pay the receive leg across the split recipients", async () => {
+      const escrowId = "split_01";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        split: toShares([5000, 3000, 2000]),
+      });
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      const splitPda = getSplitAddress(escrowPda, program.programId);
+
+      let escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.hasSplit).to.equal(true);
+      const split = await program.account.payoutSplit.fetch(splitPda);
+      expect(split.shares.map((share) => share.bps)).to.deep.equal([
+        5000, 3000, 2000,
+      ]);
+
+      await accept(escrowId, recipients);
+
+      const balances = await Promise.all(
+        recipients.map((recipient) =>
+          getSolBalance({ account: recipient.publicKey, provider })
+        )
+      );
+      expect(balances).to.deep.equal([5_000_001, 3_000_000, 2_000_000]);
+
+      escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.hasSplit).to.equal(false);
+      expect(await provider.connection.getAccountInfo(splitPda)).to.equal(
+        null
+      );
+      expect(
+        await getMintBalance({ account: Alice.publicKey, provider, mint })
+      ).to.be.greaterThanOrEqual(depositAmount);
+    });
+
+    it("Should fail when the shares don't add up to the whole leg", async () => {
+      try {
+        await init_escrow({
+          program,
+          escrowId: "split_02",
+          escrowAuthority: Bob,
+          receiver: Alice,
+          depositMint: mint,
+          receiveMint: PublicKey.default,
+          depositAmount,
+          receiveAmount,
+          split: toShares([5000, 3000, 1000]),
+        });
+        assert.fail("Expected init to fail");
+      } catch (error) {
+        assert.strictEqual(error.error.errorMessage, "Invalid payout split");
+      }
+    });
+
+    it("Should fail when the recipients are passed out of order", async () => {
+      const escrowId = "split_03";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        split: toShares([5000, 3000, 2000]),
+      });
+
+      try {
+        await accept(escrowId, [...recipients].reverse());
+        assert.fail("Expected accept to fail");
+      } catch (error) {
+        assert.strictEqual(error.error.errorMessage, "Invalid payout split");
+      }
+    });
+  });
+
+  describe("Referral Fee", () => {
+    let depositAmount: number, receiveAmount: number, mint: PublicKey;
+    let Referrer: Keypair;
+    const configPda = getConfigAddress(program.programId);
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    before(async () => {
+      // the provider wallet deployed the program, so it owns the config
+      if (!(await provider.connection.getAccountInfo(configPda))) {
+        await program.methods
+          .initConfig(500)
+          .accountsPartial({
+            admin: provider.wallet.publicKey,
+            programData,
+          })
+          .rpc({ commitment: "confirmed" });
+      }
+    });
+
+    beforeEach(async () => {
+      Referrer = Keypair.generate();
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.5 * LAMPORTS_PER_SOL
+      );
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      mint = (
+        await createAndMintTokens(provider.connection, Bob, 1 * LAMPORTS_PER_SOL)
+      ).mint;
+    });
+
+    it("Should pay the referrer its cut of the deposit leg", async () => {
+      const escrowId = "referral_01";
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: mint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        referral: { referrer: Referrer.publicKey, bps: 250 },
+      });
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.referrer.toBase58()).to.equal(
+        Referrer.publicKey.toBase58()
+      );
+      expect(escrow.referralBps).to.equal(250);
+
+      const aliceBefore = await getMintBalance({
+        account: Alice.publicKey,
+        provider,
+        mint,
+      });
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          referrer: Referrer.publicKey,
+          referrerDepositMintAta: getAssociatedTokenAddressSync(
+            mint,
+            Referrer.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+          depositMint: mint,
+        })
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const fee = (depositAmount * 250) / 10_000;
+      expect(
+        await getMintBalance({ account: Referrer.publicKey, provider, mint })
+      ).to.equal(fee);
+      expect(
+        (await getMintBalance({ account: Alice.publicKey, provider, mint })) -
+          aliceBefore
+      ).to.equal(depositAmount - fee);
+    });
+
+    it("Should fail when the referral is above the configured cap", async () => {
+      try {
+        await init_escrow({
+          program,
+          escrowId: "referral_02",
+          escrowAuthority: Bob,
+          receiver: Alice,
+          depositMint: mint,
+          receiveMint: PublicKey.default,
+          depositAmount,
+          receiveAmount,
+          referral: { referrer: Referrer.publicKey, bps: 501 },
+        });
+        assert.fail("Expected init to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Referral fee above the configured cap"
+        );
+      }
+    });
+
+    it("Should only let the admin update the config", async () => {
+      try {
+        await program.methods
+          .updateConfig(10_000, Bob.publicKey)
+          .accountsPartial({ admin: Bob.publicKey, config: configPda })
+          .signers([Bob])
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Expected update to fail");
+      } catch (error) {
+        assert.strictEqual(error.error.errorMessage, "Unauthorized Signer");
+      }
+
+      const config = await program.account.config.fetch(configPda);
+      expect(config.maxReferralBps).to.equal(500);
+    });
+  });
+
+  describe("Events", () => {
+    // events are self-CPIs: decode the inner instructions to the program,
+    // past the 8 byte event tag
+    const eventsOf = async (signature: string) => {
+      const tx = await provider.connection.getTransaction(signature, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      const keys = tx.transaction.message.getAccountKeys({
+        accountKeysFromLookups: tx.meta.loadedAddresses,
+      });
+      return tx.meta.innerInstructions
+        .flatMap((inner) => inner.instructions)
+        .filter((ix) => keys.get(ix.programIdIndex).equals(program.programId))
+        .map((ix) =>
+          program.coder.events.decode(
+            anchor.utils.bytes.base64.encode(
+              anchor.utils.bytes.bs58.decode(ix.data).subarray(8)
+            )
+          )
+        )
+        .filter((event) => event !== null);
+    };
+
+    it("Should carry the terms and refunds in create and close", async () => {
+      const escrowId = "events_01";
+      const depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      const receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      const { mint } = await createAndMintTokens(
+        provider.connection,
+        Bob,
+        1 * LAMPORTS_PER_SOL
+      );
+
+      const created = await eventsOf(
+        await init_escrow({
+          program,
+          escrowId,
+          escrowAuthority: Bob,
+          receiver: Alice,
+          depositMint: mint,
+          receiveMint: PublicKey.default,
+          depositAmount,
+          receiveAmount,
+        })
+      );
+      const createdEvent = created.find((e) => e.name === "escrowCreated");
+      expect(createdEvent.data.escrowId).to.equal(escrowId);
+      expect(createdEvent.data.depositAmount.toNumber()).to.equal(
+        depositAmount
+      );
+      expect(createdEvent.data.receiveAmount.toNumber()).to.equal(
+        receiveAmount
+      );
+      expect(createdEvent.data.referrer).to.equal(null);
+      expect(createdEvent.data.slot.toNumber()).to.be.greaterThan(0);
+
+      const closed = await eventsOf(
+        await program.methods
+          .close(escrowId)
+          .accountsPartial({
+            ...getEscrowAccounts(
+              escrowId,
+              Bob.publicKey,
+              Alice.publicKey,
+              program.programId
+            ),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+            escrowAuthority: Bob.publicKey,
+            receiver: Alice.publicKey,
+            depositMint: mint,
+            receiveMint: null,
+          })
+          .signers([Bob])
+          .rpc({ commitment: "confirmed" })
+      );
+      const closedEvent = closed.find((e) => e.name === "escrowClosed");
+      expect(closedEvent.data.escrowId).to.equal(escrowId);
+      expect(closedEvent.data.previousState).to.deep.equal({ active: {} });
+      expect(closedEvent.data.refundedDeposit.toNumber()).to.equal(
+        depositAmount
+      );
+      expect(closedEvent.data.refundedReceive.toNumber()).to.equal(0);
+      expect(closedEvent.data.forfeitedBond.toNumber()).to.equal(0);
+    });
+  });
+
+  describe("Migrate Escrow", () => {
+    it("Should start from the legacy layout bytes", async () => {
+      const u64 = (n: number) => {
+        const bytes = Buffer.alloc(8);
+        bytes.writeBigUInt64LE(BigInt(n));
+        return bytes;
+      };
+      const escrowId = Buffer.from("legacy_01");
+      const idLength = Buffer.alloc(4);
+      idLength.writeUInt32LE(escrowId.length);
+
+      // 190 bytes, a mint per leg with the default pubkey as native SOL
+      const data = Buffer.alloc(190);
+      Buffer.concat([
+        Buffer.from(
+          program.idl.accounts.find((account) => account.name === "escrow")
+            .discriminator
+        ),
+        LEGACY_AUTHORITY.publicKey.toBuffer(),
+        LEGACY_RECEIVER.toBuffer(),
+        Buffer.alloc(32),
+        u64(0.2 * LAMPORTS_PER_SOL),
+        Buffer.alloc(32, 3),
+        u64(0.01 * LAMPORTS_PER_SOL),
+        // Active, then the bump
+        Buffer.from([0, legacyBump]),
+        idLength,
+        escrowId,
+      ]).copy(data);
+
+      const escrow = await provider.connection.getAccountInfo(legacyEscrow);
+      expect(escrow.owner.toString()).to.equal(program.programId.toString());
+      expect(escrow.data.equals(data)).to.be.true;
+
+      // the deposit sits in the sol vault on top of its rent
+      const vault = await provider.connection.getAccountInfo(legacySolVault);
+      expect(vault.lamports).to.equal(
+        (await provider.connection.getMinimumBalanceForRentExemption(0)) +
+          0.2 * LAMPORTS_PER_SOL
+      );
+    });
+
+    it("Should upgrade a sentinel layout escrow to Asset legs", async () => {
+      await program.methods
+        .migrateEscrow()
+        .accounts({
+          payer: provider.wallet.publicKey,
+          escrow: legacyEscrow,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const escrow = await program.account.escrow.fetch(legacyEscrow);
+      expect(escrow.version).to.equal(1);
+      expect(escrow.solCustody).to.deep.equals({ vault: {} });
+      expect(escrow.deposit).to.deep.equals({ native: {} });
+      expect(escrow.receive.token2022.mint.toString()).to.equal(
+        "CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8"
+      );
+      expect(escrow.depositAmount.toNumber()).to.equal(
+        0.2 * LAMPORTS_PER_SOL
+      );
+      expect(escrow.escrowId).to.equal("legacy_01");
+      expect(escrow.state).to.deep.equals({ active: {} });
+    });
+
+    it("Should fail to migrate an escrow twice", async () => {
+      try {
+        await program.methods
+          .migrateEscrow()
+          .accounts({
+            payer: provider.wallet.publicKey,
+            escrow: legacyEscrow,
+          })
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Expected migration to fail");
+      } catch (error) {
+        assert.strictEqual(
+          error.error.errorMessage,
+          "Escrow account is already up to date"
+        );
+      }
+    });
+  });
+
+  describe("Legacy SOL Vault", () => {
+    it("Should refund a migrated escrow from its sol vault", async () => {
+      const authorityBefore = await getSolBalance({
+        account: LEGACY_AUTHORITY.publicKey,
+        provider,
+      });
+      const vaultBefore = await getSolBalance({
+        account: legacySolVault,
+        provider,
+      });
+      expect(vaultBefore).to.be.greaterThan(0.2 * LAMPORTS_PER_SOL);
+
+      await program.methods
+        .close("legacy_01")
+        .accountsPartial({
+          escrow: legacyEscrow,
+          // no receipt for migrated legacy escrows
+          receiptMint: null,
+          receiptAccount: null,
+          receiptTokenProgram: null,
+          payoutSplit: null,
+          referrer: null,
+          referrerDepositMintAta: null,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: LEGACY_AUTHORITY.publicKey,
+          receiver: LEGACY_RECEIVER,
+          depositMint: null,
+        })
+        .signers([LEGACY_AUTHORITY])
+        .rpc({ commitment: "confirmed" });
+
+      const authorityAfter = await getSolBalance({
+        account: LEGACY_AUTHORITY.publicKey,
+        provider,
+      });
+
+      // deposit, vault rent and escrow rent all come back
+      expect(authorityAfter - authorityBefore).to.be.greaterThan(vaultBefore);
+      expect(
+        await getSolBalance({ account: legacySolVault, provider })
+      ).to.equal(0);
+    });
+  });
+
+  describe("Transfer Hook Mint", () => {
+    let depositAmount: number, receiveAmount: number, hookMint: PublicKey;
+    let bobBeforeMint: number, bobAfterMint: number;
+    beforeEach(async () => {
+      // airdrop Bob
+      await airdrop(provider.connection, Bob.publicKey, 1 * LAMPORTS_PER_SOL);
+      depositAmount = 0.2 * LAMPORTS_PER_SOL;
+      receiveAmount = 0.01 * LAMPORTS_PER_SOL;
+
+      await airdrop(
+        provider.connection,
+        Alice.publicKey,
+        0.2 * LAMPORTS_PER_SOL
+      );
+
+      // Create Hook Mint
+      hookMint = await createHookMintAndMintTokens(
+        provider,
+        hookProgram,
+        Bob,
+        1 * LAMPORTS_PER_SOL
+      );
+    });
+
+    it("Should fail to Initialize without the hook extra accounts", async () => {
+      const escrowId = "hook_01";
+
+      try {
+        await init_escrow({
+          program,
+          escrowId,
+          escrowAuthority: Bob,
+          receiver: Alice,
+          depositMint: hookMint,
+          receiveMint: PublicKey.default,
+          depositAmount,
+          receiveAmount,
+        });
+        assert.fail("Expected init to fail without hook accounts");
+      } catch (error) {
+        const logs = error.logs || error.transactionLogs || [];
+        assert.isTrue(
+          logs.some((l: string) => l.includes("failed")),
+          "Expected hook transfer to fail"
+        );
+      }
+    });
+
+    it("Should Allow Bob to Initialize and Accept an Escrow with a hook MINT", async () => {
+      const escrowId = "hook_02";
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: hookMint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        remainingAccounts: await getHookRemainingAccounts(
+          provider,
+          hookMint,
+          Bob.publicKey,
+          escrowPda
+        ),
+      });
+
+      await checkInitEscrow({
+        provider,
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: hookMint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+      });
+
+      await program.methods
+        .accept(escrowId, false)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          receiver: Alice.publicKey,
+          escrowAuthority: Bob.publicKey,
+          receiveMint: null,
+          depositMint: hookMint,
+        })
+        .remainingAccounts(
+          await getHookRemainingAccounts(
+            provider,
+            hookMint,
+            escrowPda,
+            Alice.publicKey
+          )
+        )
+        .signers([Alice])
+        .rpc({ commitment: "confirmed" });
+
+      const aliceAfterMint = await getMintBalance({
+        account: Alice.publicKey,
+        provider,
+        mint: hookMint,
+      });
+      expect(aliceAfterMint).to.equals(depositAmount, "Wrong Amount for Alice");
+
+      // init + accept went through the hook
+      const counter = await hookProgram.account.counter.fetch(
+        getHookCounterPda(hookMint, hookProgram.programId)
+      );
+      expect(counter.transfers.toNumber()).to.equals(2);
+
+      const escrow = await program.account.escrow.fetch(escrowPda);
+      expect(escrow.state).to.deep.equals({ completed: {} });
+    });
+
+    it("Successfully close an escrow FUNDED with a hook MINT", async () => {
+      const escrowId = "hook_03";
+
+      const [escrowPda] = getEscrowAddress(
+        escrowId,
+        Bob.publicKey,
+        Alice.publicKey,
+        program.programId
+      );
+
+      await init_escrow({
+        program,
+        escrowId,
+        escrowAuthority: Bob,
+        receiver: Alice,
+        depositMint: hookMint,
+        receiveMint: PublicKey.default,
+        depositAmount,
+        receiveAmount,
+        remainingAccounts: await getHookRemainingAccounts(
+          provider,
+          hookMint,
+          Bob.publicKey,
+          escrowPda
+        ),
+      });
+
+      bobBeforeMint = await getMintBalance({
+        account: Bob.publicKey,
+        provider,
+        mint: hookMint,
+      });
+
+      await program.methods
+        .close(escrowId)
+        .accountsPartial({
+          ...getEscrowAccounts(
+            escrowId,
+            Bob.publicKey,
+            Alice.publicKey,
+            program.programId
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          escrowAuthority: Bob.publicKey,
+          receiver: Alice.publicKey,
+          depositMint: hookMint,
+        })
+        .remainingAccounts(
+          await getHookRemainingAccounts(
+            provider,
+            hookMint,
+            escrowPda,
+            Bob.publicKey
+          )
+        )
+        .signers([Bob])
+        .rpc({ commitment: "confirmed" });
+
+      bobAfterMint = await getMintBalance({
+        account: Bob.publicKey,
+        provider,
+        mint: hookMint,
+      });
+
+      expect(bobAfterMint - bobBeforeMint).to.be.equals(depositAmount);
+    });
+  });
+});
+
+const init_escrow = async ({
+  program,
+  escrowId,
+  escrowAuthority,
+  receiver,
+  depositMint,
+  receiveMint,
+  depositAmount,
+  receiveAmount,
+  bond = null,
+  challengeWindow = null,
+  split = null,
+  referral = null,
+  remainingAccounts = [],
+}: any) => {
+  const [escrowPda] = getEscrowAddress(
+    escrowId,
+    escrowAuthority.publicKey,
+    receiver.publicKey,
+    program.programId
+  );
+
+  return await program.methods
+    .initEscrow(
+      escrowId,
+      toAsset(depositMint),
+      new anchor.BN(depositAmount),
+      toAsset(receiveMint),
+      new anchor.BN(receiveAmount),
+      { bond, challengeWindow, split, referral }
+    )
+    .accountsPartial({
+      payoutSplit: split ? getSplitAddress(escrowPda, program.programId) : null,
+      config: referral ? getConfigAddress(program.programId) : null,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      escrowAuthority: escrowAuthority.publicKey,
+      receiver: receiver.publicKey,
+      depositMint: depositMint == PublicKey.default ? null : depositMint,
+    })
+    .remainingAccounts(remainingAccounts)
+    .signers([escrowAuthority])
+    .rpc({ commitment: "confirmed" });
+};
+
+const checkInitEscrow = async ({
+  provider,
+  program,
+  escrowId,
+  escrowAuthority,
+  receiver,
+  depositMint,
+  receiveMint,
+  depositAmount,
+  receiveAmount,
+}) => {
+  // Get Pda of the escrow via the ID
+  let [escrowPda, escrowBump] = getEscrowAddress(
+    escrowId,
+    escrowAuthority.publicKey,
+    receiver.publicKey,
+    program.programId
+  );
+
+  let escrow = await program.account.escrow.fetch(escrowPda);
+
+  // // Basic Asserts
+  expect(escrowBump).to.be.a("number");
+  expect(escrow.escrowAuthority.toString()).to.equal(
+    escrowAuthority.publicKey.toString()
+  );
+  expect(escrow.receiver.toString()).to.equal(receiver.publicKey.toString());
+  expect(escrow.escrowId).to.equal(escrowId);
+
+  // Mint Asserts
+  expect(assetMint(escrow.deposit).toString()).to.equal(
+    canonicalMint(depositMint).toString(),
+    "Deposit mint mismatch"
+  );
+
+  expect(assetMint(escrow.receive).toString()).to.equal(
+    canonicalMint(receiveMint).toString(),
+    "Receive mint mismatch"
+  );
+
+  // Amount Asserts
+  expect(escrow.depositAmount.toNumber()).to.equal(
+    depositAmount,
+    "Deposit amount mismatch"
+  );
+
+  expect(escrow.receiveAmount.toNumber()).to.equal(
+    receiveAmount,
+    "Receive amount mismatch"
+  );
+
+  // State
+  expect(escrow.state).to.deep.equal({ active: {} });
+
+  checkEscrowBalance({
+    provider,
+    program,
+    escrow,
+    escrowPda,
+    type: canonicalMint(depositMint).equals(NATIVE_MINT_2022) ? "SOL" : "MINT",
+    mint: depositMint == PublicKey.default ? receiveMint : depositMint,
+  });
+};
+
+const checkEscrowBalance = async ({
+  provider,
+  program,
+  escrow,
+  escrowPda,
+  type,
   mint,
 }) => {
   if (type == "SOL") {
     // get sol vault pda
+    // SOL sits on the escrow account on top of its rent, no vault
+    const escrowBalance = await provider.connection.getBalance(escrowPda);
+    expect(escrowBalance).to.greaterThan(escrow.depositAmount.toNumber());
+
     const vaultPda = await getSolVaultPda(escrowPda, program);
-    const escrowBalance = await provider.connection.getBalance(vaultPda);
-    expect(escrowBalance).to.lessThan(escrow.depositAmount.toNumber());
+    expect(await provider.connection.getBalance(vaultPda)).to.equal(0);
   } else {
     // Token deposit case
     const escrow_account = await getAccount(
@@ -1055,7 +3535,58 @@ const checkEscrowBalance = async ({
   }
 };
 
+// Vault swept and escrow ata closed once the deal is over
+const checkRentReclaimed = async ({ provider, program, escrowPda, mint }) => {
+  const vaultPda = await getSolVaultPda(escrowPda, program);
+  expect(await provider.connection.getBalance(vaultPda)).to.equal(0);
+
+  const escrowAta = await getEscrowATA(provider.connection, escrowPda, mint);
+  expect(await provider.connection.getAccountInfo(escrowAta)).to.equal(null);
+
+  // the receipt is burnt and its mint closed
+  const { receiptMint } = getReceiptAccounts(
+    escrowPda,
+    PublicKey.default,
+    program.programId
+  );
+  expect(await provider.connection.getAccountInfo(receiptMint)).to.equal(null);
+};
+
+// Rent the authority side gets back on accept: the escrow ata of the deposit
+// mint and the receipt mint
+const getAuthorityRentBack = async ({ provider, program, escrowPda, mint }) => {
+  const { receiptMint } = getReceiptAccounts(
+    escrowPda,
+    PublicKey.default,
+    program.programId
+  );
+  const escrowAta = await getEscrowATA(provider.connection, escrowPda, mint);
+
+  return (
+    (await provider.connection.getBalance(escrowAta)) +
+    (await provider.connection.getBalance(receiptMint))
+  );
+};
+
 // HELPERS
+// Compute units, account count and accounts brought to life by a transaction
+async function getTransactionStats(provider: any, signature: string) {
+  const tx = await provider.connection.getTransaction(signature, {
+    commitment: "confirmed",
+    maxSupportedTransactionVersion: 0,
+  });
+
+  const { preBalances, postBalances, computeUnitsConsumed } = tx.meta;
+
+  return {
+    computeUnits: computeUnitsConsumed,
+    accounts: tx.transaction.message.staticAccountKeys.length,
+    createdAccounts: preBalances.filter(
+      (before: number, i: number) => before == 0 && postBalances[i] > 0
+    ).length,
+  };
+}
+
 async function airdrop(connection: any, address: any, amount: number) {
   await connection.confirmTransaction(
     await connection.requestAirdrop(address, amount),
@@ -1088,12 +3619,17 @@ async function getSolBalance({ account, provider }) {
 }
 
 // account is public key in here
-async function getMintBalance({ account, provider, mint }) {
+async function getMintBalance({
+  account,
+  provider,
+  mint,
+  tokenProgram = TOKEN_2022_PROGRAM_ID,
+}: any) {
   const ata = await getAssociatedTokenAddress(
     mint,
     account,
     true,
-    TOKEN_2022_PROGRAM_ID
+    tokenProgram
   );
 
   // Token deposit ATA must be empty
@@ -1101,12 +3637,67 @@ async function getMintBalance({ account, provider, mint }) {
     provider.connection,
     ata,
     "confirmed",
-    TOKEN_2022_PROGRAM_ID
+    tokenProgram
   );
 
   return Number(mint_act.amount);
 }
 
+// Escrow of a new layout plus its receipt, held by the authority unless
+// someone else is passed. Escrows with a split or a referral pass those
+// accounts on top
+function getEscrowAccounts(
+  escrow_id: string,
+  authority: PublicKey,
+  receiver: PublicKey,
+  ProgramId: PublicKey,
+  holder: PublicKey = authority
+) {
+  const [escrow] = getEscrowAddress(escrow_id, authority, receiver, ProgramId);
+  return {
+    escrow,
+    ...getReceiptAccounts(escrow, holder, ProgramId),
+    payoutSplit: null,
+    referrer: null,
+    referrerDepositMintAta: null,
+  };
+}
+
+function getReceiptAccounts(
+  escrowPda: PublicKey,
+  holder: PublicKey,
+  ProgramId: PublicKey
+) {
+  const [receiptMint] = PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode(RECEIPT_SEED), escrowPda.toBuffer()],
+    ProgramId
+  );
+  return {
+    receiptMint,
+    receiptAccount: getAssociatedTokenAddressSync(
+      receiptMint,
+      holder,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    ),
+    receiptTokenProgram: TOKEN_2022_PROGRAM_ID,
+  };
+}
+
+function getConfigAddress(ProgramId: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode(CONFIG_SEED)],
+    ProgramId
+  )[0];
+}
+
+function getSplitAddress(escrowPda: PublicKey, ProgramId: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode(SPLIT_SEED), escrowPda.toBuffer()],
+    ProgramId
+  )[0];
+}
+
 function getEscrowAddress(
   escrow_id: string,
   buyer: PublicKey,
@@ -1127,7 +3718,8 @@ function getEscrowAddress(
 async function createAndMintTokens(
   connection: any,
   account: any,
-  amount: number
+  amount: number,
+  tokenProgram = TOKEN_2022_PROGRAM_ID
 ) {
   // Create Mint
   const mint = await createMint(
@@ -1138,7 +3730,7 @@ async function createAndMintTokens(
     6,
     undefined,
     undefined,
-    TOKEN_2022_PROGRAM_ID
+    tokenProgram
   );
 
   const ata = await getOrCreateAssociatedTokenAccount(
@@ -1149,7 +3741,7 @@ async function createAndMintTokens(
     false,
     undefined,
     undefined,
-    TOKEN_2022_PROGRAM_ID,
+    tokenProgram,
     ASSOCIATED_TOKEN_PROGRAM_ID
   );
 
@@ -1162,8 +3754,185 @@ async function createAndMintTokens(
     amount,
     [],
     undefined,
-    TOKEN_2022_PROGRAM_ID
+    tokenProgram
   );
 
   return { mint, ata };
 }
+
+// Mint with a transfer hook pointing at the sample hook program
+async function createHookMintAndMintTokens(
+  provider: any,
+  hookProgram: any,
+  account: any,
+  amount: number
+) {
+  const connection = provider.connection;
+  const mintKeypair = Keypair.generate();
+  const mintLen = getMintLen([ExtensionType.TransferHook]);
+  const lamports = await connection.getMinimumBalanceForRentExemption(mintLen);
+
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: account.publicKey,
+      newAccountPubkey: mintKeypair.publicKey,
+      space: mintLen,
+      lamports,
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    createInitializeTransferHookInstruction(
+      mintKeypair.publicKey,
+      account.publicKey,
+      hookProgram.programId,
+      TOKEN_2022_PROGRAM_ID
+    ),
+    createInitializeMintInstruction(
+      mintKeypair.publicKey,
+      6,
+      account.publicKey,
+      null,
+      TOKEN_2022_PROGRAM_ID
+    )
+  );
+  await anchor.web3.sendAndConfirmTransaction(
+    connection,
+    tx,
+    [account, mintKeypair],
+    { commitment: "confirmed" }
+  );
+
+  await hookProgram.methods
+    .initializeExtraAccountMetaList()
+    .accounts({
+      payer: account.publicKey,
+      mint: mintKeypair.publicKey,
+    })
+    .signers([account])
+    .rpc({ commitment: "confirmed" });
+
+  const ata = await getOrCreateAssociatedTokenAccount(
+    connection,
+    account,
+    mintKeypair.publicKey,
+    account.publicKey,
+    false,
+    undefined,
+    undefined,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_TOKEN_PROGRAM_ID
+  );
+
+  await mintTo(
+    connection,
+    account,
+    mintKeypair.publicKey,
+    ata.address,
+    account.publicKey,
+    amount,
+    [],
+    undefined,
+    TOKEN_2022_PROGRAM_ID
+  );
+
+  return mintKeypair.publicKey;
+}
+
+// Resolve the hook extra accounts the same way a wallet would for a plain
+// transfer and hand everything past the 4 base accounts to the escrow program
+async function getHookRemainingAccounts(
+  provider: any,
+  mint: PublicKey,
+  owner: PublicKey,
+  destinationOwner: PublicKey
+) {
+  const ix = await createTransferCheckedWithTransferHookInstruction(
+    provider.connection,
+    await getEscrowATA(provider.connection, owner, mint),
+    mint,
+    await getEscrowATA(provider.connection, destinationOwner, mint),
+    owner,
+    BigInt(0),
+    6,
+    [],
+    "confirmed",
+    TOKEN_2022_PROGRAM_ID
+  );
+
+  return ix.keys.slice(4).map((key) => ({ ...key, isSigner: false }));
+}
+
+// Pubkey.default is native SOL, anything else a Token-2022 mint
+function toAsset(mint: PublicKey) {
+  return mint.equals(PublicKey.default)
+    ? { native: {} }
+    : { token2022: { mint } };
+}
+
+function isNative(asset: any) {
+  return asset.native !== undefined;
+}
+
+// Mint to pass for a leg on accept, plain SOL legs take no token accounts
+function legMint(asset: any): PublicKey | null {
+  return isNative(asset) ? null : assetMint(asset);
+}
+
+// Native legs report the native mint, same as canonicalMint
+function assetMint(asset: any): PublicKey {
+  if (isNative(asset)) return NATIVE_MINT_2022;
+  return (asset.token2022 ?? asset.splToken).mint;
+}
+
+// Pubkey.default and the native mint are both stored as the native asset
+function canonicalMint(mint: PublicKey) {
+  return mint.equals(PublicKey.default) ? NATIVE_MINT_2022 : mint;
+}
+
+// Token-2022 native mint is not created by default on a local validator
+async function ensureNativeMint(provider: any, payer: any) {
+  const info = await provider.connection.getAccountInfo(NATIVE_MINT_2022);
+  if (!info) {
+    await createNativeMint(
+      provider.connection,
+      payer,
+      { commitment: "confirmed" },
+      NATIVE_MINT_2022,
+      TOKEN_2022_PROGRAM_ID
+    );
+  }
+}
+
+async function wrapSol(provider: any, account: any, amount: number) {
+  const ata = await getOrCreateAssociatedTokenAccount(
+    provider.connection,
+    account,
+    NATIVE_MINT_2022,
+    account.publicKey,
+    false,
+    "confirmed",
+    undefined,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_TOKEN_PROGRAM_ID
+  );
+
+  const tx = new Transaction().add(
+    SystemProgram.transfer({
+      fromPubkey: account.publicKey,
+      toPubkey: ata.address,
+      lamports: amount,
+    }),
+    createSyncNativeInstruction(ata.address, TOKEN_2022_PROGRAM_ID)
+  );
+  await anchor.web3.sendAndConfirmTransaction(provider.connection, tx, [
+    account,
+  ]);
+
+  return ata.address;
+}
+
+function getHookCounterPda(mint: PublicKey, hookProgramId: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("counter"), mint.toBuffer()],
+    hookProgramId
+  )[0];
+}